@@ -0,0 +1,62 @@
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// When the output is colored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Only if stdout is a terminal and `NO_COLOR` is not set.
+    Auto,
+    /// Always, e.g. for a pager keeping ANSI codes.
+    Always,
+    /// Never, e.g. for logs and CI.
+    Never,
+}
+
+impl ColorChoice {
+    /// Returns the matching `env_logger` write style, so warnings follow the same choice.
+    pub fn write_style(self) -> env_logger::WriteStyle {
+        match self {
+            ColorChoice::Auto => env_logger::WriteStyle::Auto,
+            ColorChoice::Always => env_logger::WriteStyle::Always,
+            ColorChoice::Never => env_logger::WriteStyle::Never,
+        }
+    }
+}
+
+static COLOR_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Decides once whether stdout output is colored.
+///
+/// # Parameters
+/// - `choice`: The `--color` option.
+pub fn init(choice: ColorChoice) {
+    let enabled = match choice {
+        ColorChoice::Auto => std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    };
+    COLOR_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Colors a passed result, e.g. a matching checksum.
+pub fn green(text: &str) -> String {
+    paint(text, "32")
+}
+
+/// Colors a warning, e.g. a chunk which needed retries.
+pub fn yellow(text: &str) -> String {
+    paint(text, "33")
+}
+
+/// Colors a failed result, e.g. a checksum mismatch.
+pub fn red(text: &str) -> String {
+    paint(text, "31")
+}
+
+/// Wraps the text in an ANSI color code, unchanged if color is disabled.
+fn paint(text: &str, code: &str) -> String {
+    match COLOR_ENABLED.load(Ordering::Relaxed) {
+        true => format!("\x1b[{code}m{text}\x1b[0m"),
+        false => text.to_string(),
+    }
+}
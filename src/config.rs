@@ -0,0 +1,133 @@
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio::io;
+
+use crate::gdb::Timeouts;
+
+/// Configuration file content, every value can be overridden from the command line.
+///
+/// Example:
+/// ```toml
+/// [timeouts]
+/// restore = 10000
+/// continue = 60000
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Operation timeouts in milliseconds.
+    pub timeouts: TimeoutsConfig,
+}
+
+/// Operation timeouts in milliseconds, shared by the `[timeouts]` table and the CLI flags.
+#[derive(Debug, Clone, Default, Deserialize, clap::Args)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeoutsConfig {
+    /// Time in milliseconds to connect to the GDB server with `target remote` [default: 500].
+    #[arg(long = "connect-timeout", value_name = "MILLIS")]
+    pub connect: Option<u64>,
+
+    /// Time in milliseconds to collect `monitor halt` output, 0 does not wait at all [default: 0].
+    #[arg(long = "halt-timeout", value_name = "MILLIS")]
+    pub halt: Option<u64>,
+
+    /// Time in milliseconds to wait for `monitor reset` output [default: 250].
+    #[arg(long = "reset-timeout", value_name = "MILLIS")]
+    pub reset: Option<u64>,
+
+    /// Time in milliseconds to wait for the target to hit the breakpoint after continuing [default: 30000].
+    #[arg(long = "continue-timeout", value_name = "MILLIS")]
+    #[serde(rename = "continue")]
+    pub continue_execution: Option<u64>,
+
+    /// Time in milliseconds to set a breakpoint or watchpoint [default: 750].
+    #[arg(long = "break-timeout", value_name = "MILLIS")]
+    #[serde(rename = "break")]
+    pub breakpoint: Option<u64>,
+
+    /// Time in milliseconds to call a target function, including the copy function [default: 2250].
+    #[arg(long = "call-timeout", value_name = "MILLIS")]
+    pub call: Option<u64>,
+
+    /// Time in milliseconds to read a variable or memory [default: 250].
+    #[arg(long = "read-timeout", value_name = "MILLIS")]
+    pub read: Option<u64>,
+
+    /// Time in milliseconds to restore a chunk into the RAM buffer, used until the throughput is measured [default: 5000].
+    #[arg(long = "restore-timeout", value_name = "MILLIS")]
+    pub restore: Option<u64>,
+
+    /// Time in milliseconds to restore one KiB of a chunk, used instead of the measured throughput
+    /// to scale the restore timeout with the chunk size.
+    #[arg(long = "restore-timeout-per-kb", value_name = "MILLIS")]
+    pub restore_per_kb: Option<u64>,
+
+    /// Time in milliseconds to flash the ELF with `load` [default: 60000].
+    #[arg(long = "load-timeout", value_name = "MILLIS")]
+    pub load: Option<u64>,
+
+    /// Time in milliseconds to wait for GDB to exit after `quit` before killing it [default: 2000].
+    #[arg(long = "quit-timeout", value_name = "MILLIS")]
+    pub quit: Option<u64>,
+}
+
+impl TimeoutsConfig {
+    /// Resolves timeouts, values set here take precedence over `fallback`, then over defaults.
+    pub fn resolve(&self, fallback: &TimeoutsConfig) -> Timeouts {
+        let defaults = Timeouts::default();
+        let pick = |value: Option<u64>, fallback_value: Option<u64>, default: Duration| {
+            value.or(fallback_value).map(Duration::from_millis).unwrap_or(default)
+        };
+
+        Timeouts {
+            connect: pick(self.connect, fallback.connect, defaults.connect),
+            halt: pick(self.halt, fallback.halt, defaults.halt),
+            reset: pick(self.reset, fallback.reset, defaults.reset),
+            continue_execution: pick(self.continue_execution, fallback.continue_execution, defaults.continue_execution),
+            breakpoint: pick(self.breakpoint, fallback.breakpoint, defaults.breakpoint),
+            call: pick(self.call, fallback.call, defaults.call),
+            read: pick(self.read, fallback.read, defaults.read),
+            restore: pick(self.restore, fallback.restore, defaults.restore),
+            restore_per_kb: self.restore_per_kb.or(fallback.restore_per_kb).map(Duration::from_millis),
+            load: pick(self.load, fallback.load, defaults.load),
+            quit: pick(self.quit, fallback.quit, defaults.quit),
+        }
+    }
+}
+
+/// Loads the TOML configuration file.
+///
+/// # Parameters
+/// - `config_path`: The path to the configuration file.
+///
+/// # Returns
+/// An `io::Result<Config>` with the parsed configuration or an error if reading or parsing fails.
+pub fn load_config<P>(config_path: P) -> io::Result<Config>
+where
+    P: AsRef<Path>
+{
+    let content = std::fs::read_to_string(config_path.as_ref())?;
+    toml::from_str(&content)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Config {:?} corrupted: {e}", config_path.as_ref())))
+}
+
+/// Loads a file of GDB commands, one per line, skipping empty lines and `#` comments.
+///
+/// # Parameters
+/// - `commands_path`: The path to the commands file.
+///
+/// # Returns
+/// An `io::Result` with the commands in file order or an error if reading fails.
+pub fn load_commands_file<P>(commands_path: P) -> io::Result<Vec<String>>
+where
+    P: AsRef<Path>
+{
+    let content = std::fs::read_to_string(commands_path.as_ref())?;
+    Ok(content.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
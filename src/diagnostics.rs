@@ -0,0 +1,66 @@
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tokio::fs;
+use tokio::io;
+
+use crate::gdb::Gdb;
+
+/// Number of frames in the backtrace of the bundle.
+const BACKTRACE_FRAMES: usize = 16;
+/// Number of most recent GDB transcript entries in the bundle.
+const TRANSCRIPT_TAIL: usize = 100;
+
+/// Captures the target state after a failure into a timestamped bundle file, for post-mortem analysis.
+///
+/// The bundle holds the error, the stop reason, the registers, a short backtrace and the tail of
+/// the GDB transcript. Sections which cannot be captured, e.g. because the connection dropped,
+/// contain the reason instead, so a partially broken session still produces a bundle.
+///
+/// # Parameters
+/// - `gdb`: A mutable reference to the GDB connection the failure happened on.
+/// - `dir`: The directory the bundle is written to, created if missing.
+/// - `error`: The original error, only recorded.
+///
+/// # Returns
+/// An `io::Result<PathBuf>` with the bundle path or an error if the file cannot be written.
+pub async fn dump_failure_bundle(gdb: &mut Gdb, dir: &Path, error: &io::Error) -> io::Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default();
+
+    let mut bundle = String::new();
+    let _ = writeln!(bundle, "# gdbloader failure bundle, unix time {timestamp} ms");
+    let _ = writeln!(bundle, "\n## Error\n{error}");
+
+    let stop_reason = match gdb.last_stop_reason() {
+        Some(stop_reason) => stop_reason.to_string(),
+        None => String::from("target not resumed"),
+    };
+    let _ = writeln!(bundle, "\n## Stop reason\n{stop_reason}");
+
+    append_section(&mut bundle, "Registers", gdb.info_registers().await);
+    append_section(&mut bundle, "Backtrace", gdb.backtrace(BACKTRACE_FRAMES).await);
+    append_section(&mut bundle, "GDB transcript", Ok(gdb.transcript_tail(TRANSCRIPT_TAIL)));
+
+    fs::create_dir_all(dir).await?;
+    let bundle_path = dir.join(format!("gdbloader_failure_{timestamp}.txt"));
+    fs::write(&bundle_path, bundle).await?;
+
+    Ok(bundle_path)
+}
+
+/// Appends a titled section with the lines or the reason they are missing.
+fn append_section(bundle: &mut String, title: &str, lines: io::Result<Vec<String>>) {
+    let _ = writeln!(bundle, "\n## {title}");
+    match lines {
+        Ok(lines) => lines.iter().for_each(|line| {
+            let _ = writeln!(bundle, "{line}");
+        }),
+        Err(e) => {
+            let _ = writeln!(bundle, "<unavailable: {e}>");
+        },
+    }
+}
@@ -0,0 +1,109 @@
+use std::path::Path;
+
+use object::{Object, ObjectSection, ObjectSymbol};
+use tokio::io;
+
+use crate::error::GdbError;
+
+/// Parses the content of an ELF file read by the caller.
+///
+/// # Parameters
+/// - `elf_data`: The bytes of the ELF file.
+///
+/// # Returns
+/// An `io::Result` with the parsed file borrowing `elf_data`, an `InvalidData` error if it is not a valid ELF.
+fn parse_elf(elf_data: &[u8]) -> io::Result<object::File<'_>> {
+    object::File::parse(elf_data)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("ELF parsing failed: {e}")))
+}
+
+/// Reads the initial value of a symbol stored in the ELF file.
+///
+/// # Parameters
+/// - `elf_path`: The path to the firmware ELF file.
+/// - `symbol_name`: The name of the symbol, e.g. a build ID array.
+///
+/// # Returns
+/// An `io::Result` with a tuple of the symbol address and its bytes as placed in the ELF,
+/// or an error if the symbol is missing, empty or has no data in the file (e.g. `.bss`).
+pub fn read_symbol_bytes<P>(elf_path: P, symbol_name: &str) -> io::Result<(u32, Vec<u8>)>
+where
+    P: AsRef<Path>
+{
+    let elf_data = std::fs::read(elf_path.as_ref())?;
+    let elf_file = parse_elf(&elf_data)?;
+
+    let symbol = elf_file.symbols()
+        .find(|symbol| symbol.name() == Ok(symbol_name))
+        .ok_or_else(|| io::Error::from(GdbError::SymbolNotFound(symbol_name.to_string())))?;
+
+    let symbol_address = symbol.address();
+    let symbol_size = symbol.size();
+    if symbol_size == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Symbol '{symbol_name}' has no size")));
+    }
+
+    let section = symbol.section_index()
+        .and_then(|index| elf_file.section_by_index(index).ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Symbol '{symbol_name}' has no section")))?;
+
+    let section_data = section.data_range(symbol_address, symbol_size)
+        .ok()
+        .flatten()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Symbol '{symbol_name}' has no data in ELF")))?;
+
+    let address = u32::try_from(symbol_address)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("Symbol '{symbol_name}' address out of 32-bit range")))?;
+    log::debug!("ELF symbol '{symbol_name}' at {address:#x}: {section_data:02x?}");
+
+    Ok((address, section_data.to_vec()))
+}
+
+/// Reads the value of a symbol, e.g. a linker script symbol like `__ext_flash_data_start`.
+///
+/// # Parameters
+/// - `elf_path`: The path to the firmware ELF file.
+/// - `symbol_name`: The name of the symbol.
+///
+/// # Returns
+/// An `io::Result<u64>` with the symbol value (its address for data and code symbols),
+/// or an error if the symbol is missing.
+pub fn read_symbol_value<P>(elf_path: P, symbol_name: &str) -> io::Result<u64>
+where
+    P: AsRef<Path>
+{
+    let elf_data = std::fs::read(elf_path.as_ref())?;
+    let elf_file = parse_elf(&elf_data)?;
+
+    let symbol = elf_file.symbols()
+        .find(|symbol| symbol.name() == Ok(symbol_name))
+        .ok_or_else(|| io::Error::from(GdbError::SymbolNotFound(symbol_name.to_string())))?;
+
+    log::debug!("ELF symbol '{symbol_name}' value: {:#x}", symbol.address());
+    Ok(symbol.address())
+}
+
+/// Reads the entry point of the ELF file, the reset handler on Cortex-M firmwares.
+///
+/// # Parameters
+/// - `elf_path`: The path to the firmware ELF file.
+///
+/// # Returns
+/// An `io::Result<u32>` with the entry address, the Thumb bit cleared so it can be used as
+/// a breakpoint address, or an error if the ELF has no entry point.
+pub fn read_entry_address<P>(elf_path: P) -> io::Result<u32>
+where
+    P: AsRef<Path>
+{
+    let elf_data = std::fs::read(elf_path.as_ref())?;
+    let elf_file = parse_elf(&elf_data)?;
+
+    let entry = u32::try_from(elf_file.entry())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "ELF entry point out of 32-bit range"))?;
+    if entry == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "ELF has no entry point"));
+    }
+
+    log::debug!("ELF entry point: {entry:#x}");
+    Ok(entry & !1)
+}
@@ -0,0 +1,162 @@
+use tokio::io;
+
+/// Categorized failures, carried inside `io::Error` so they pass through the `io::Result` based API.
+/// The category decides the process exit code, see [`exit_code`].
+#[derive(Debug)]
+pub enum GdbError {
+    /// GDB could not connect to the server or the connection dropped.
+    Connection(String),
+    /// A symbol is missing in the ELF or GDB cannot resolve it.
+    SymbolNotFound(String),
+    /// The checksum returned by the target differs from the one computed on the host.
+    ChecksumMismatch { host_checksum: u32, target_checksum: u32 },
+    /// An operation did not finish in time.
+    Timeout(String),
+    /// The run was cancelled by the user.
+    Cancelled,
+    /// GDB could not evaluate an expression, e.g. because of a syntax error.
+    InvalidExpression(String),
+    /// The GDB process exited unexpectedly, e.g. crashed, while a command was pending.
+    ProcessDied { status: std::process::ExitStatus },
+    /// The upload stopped at a chunk boundary as its time budget ran out.
+    Incomplete { chunks_written: usize, chunks_count: usize },
+}
+
+impl std::fmt::Display for GdbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GdbError::Connection(details) => write!(f, "{details}"),
+            GdbError::SymbolNotFound(symbol) => write!(f, "No symbol '{symbol}'"),
+            GdbError::ChecksumMismatch { host_checksum, target_checksum } => {
+                write!(f, "Checksum not match host={host_checksum} target={target_checksum}")
+            },
+            GdbError::Timeout(details) => write!(f, "{details}"),
+            GdbError::Cancelled => write!(f, "Cancelled"),
+            GdbError::InvalidExpression(details) => write!(f, "Invalid expression {details}"),
+            GdbError::ProcessDied { status } => write!(f, "GDB process died unexpectedly ({status})"),
+            GdbError::Incomplete { chunks_written, chunks_count } => {
+                write!(f, "Upload incomplete, {chunks_written} of {chunks_count} chunks written within the time budget")
+            },
+        }
+    }
+}
+
+impl std::error::Error for GdbError {}
+
+impl From<GdbError> for io::Error {
+    fn from(error: GdbError) -> Self {
+        let kind = match &error {
+            GdbError::Connection(_) => io::ErrorKind::ConnectionRefused,
+            GdbError::SymbolNotFound(_) => io::ErrorKind::NotFound,
+            GdbError::ChecksumMismatch { .. } => io::ErrorKind::InvalidData,
+            GdbError::Timeout(_) => io::ErrorKind::TimedOut,
+            GdbError::Cancelled => io::ErrorKind::Interrupted,
+            GdbError::InvalidExpression(_) => io::ErrorKind::InvalidInput,
+            GdbError::ProcessDied { .. } => io::ErrorKind::BrokenPipe,
+            GdbError::Incomplete { .. } => io::ErrorKind::TimedOut,
+        };
+        io::Error::new(kind, error)
+    }
+}
+
+/// An error prefixed with the step it happened in, see [`ResultExt::with_context`].
+#[derive(Debug)]
+struct ContextError {
+    context: String,
+    source: io::Error,
+}
+
+impl std::fmt::Display for ContextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.context, self.source)
+    }
+}
+
+impl std::error::Error for ContextError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Adds human readable context to errors on their way up, e.g.
+/// `chunk 42 at flash offset 0x2a0000: copy function 'loader_copy_to_ext_flash' call failed: No symbol 'loader_copy_to_ext_flash'`.
+pub trait ResultExt<T> {
+    /// Prefixes the error with a description of the failed step, keeping its kind and its [`GdbError`] category.
+    ///
+    /// # Parameters
+    /// - `context`: Builds the description, called only on error.
+    ///
+    /// # Returns
+    /// The result with the error wrapped.
+    fn with_context<C, F>(self, context: F) -> io::Result<T>
+    where
+        C: std::fmt::Display,
+        F: FnOnce() -> C;
+}
+
+impl<T> ResultExt<T> for io::Result<T> {
+    fn with_context<C, F>(self, context: F) -> io::Result<T>
+    where
+        C: std::fmt::Display,
+        F: FnOnce() -> C
+    {
+        self.map_err(|source| io::Error::new(source.kind(), ContextError { context: context().to_string(), source }))
+    }
+}
+
+/// Exit code of an unclassified failure.
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit code of a connection failure.
+pub const EXIT_CONNECTION: i32 = 2;
+/// Exit code of a symbol resolution failure.
+pub const EXIT_SYMBOL: i32 = 3;
+/// Exit code of a checksum mismatch.
+pub const EXIT_CHECKSUM_MISMATCH: i32 = 4;
+/// Exit code of a timeout.
+pub const EXIT_TIMEOUT: i32 = 5;
+/// Exit code of a cancelled run.
+pub const EXIT_CANCELLED: i32 = 6;
+/// Exit code of a run failed by `--fail-on-warning`.
+pub const EXIT_WARNINGS: i32 = 7;
+/// Exit code of a GDB process crash.
+pub const EXIT_PROCESS_DIED: i32 = 8;
+/// Exit code of an upload stopped by `--max-upload-duration`.
+pub const EXIT_PARTIAL: i32 = 9;
+
+/// Returns the wrapped [`GdbError`], if any, also from below added context.
+pub fn as_gdb_error(error: &io::Error) -> Option<&GdbError> {
+    let inner = error.get_ref()?;
+    match inner.downcast_ref::<ContextError>() {
+        Some(context_error) => as_gdb_error(&context_error.source),
+        None => inner.downcast_ref::<GdbError>(),
+    }
+}
+
+/// Maps an error to the process exit code, by its [`GdbError`] category or by its kind.
+///
+/// # Parameters
+/// - `error`: The error the run failed with.
+///
+/// # Returns
+/// The exit code, [`EXIT_FAILURE`] if the error has no category.
+pub fn exit_code(error: &io::Error) -> i32 {
+    match as_gdb_error(error) {
+        Some(GdbError::Connection(_)) => EXIT_CONNECTION,
+        Some(GdbError::SymbolNotFound(_)) => EXIT_SYMBOL,
+        Some(GdbError::ChecksumMismatch { .. }) => EXIT_CHECKSUM_MISMATCH,
+        Some(GdbError::Timeout(_)) => EXIT_TIMEOUT,
+        Some(GdbError::Cancelled) => EXIT_CANCELLED,
+        Some(GdbError::ProcessDied { .. }) => EXIT_PROCESS_DIED,
+        Some(GdbError::Incomplete { .. }) => EXIT_PARTIAL,
+        Some(GdbError::InvalidExpression(_)) => EXIT_FAILURE,
+        None => match error.kind() {
+            io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected => EXIT_CONNECTION,
+            io::ErrorKind::TimedOut => EXIT_TIMEOUT,
+            io::ErrorKind::Interrupted => EXIT_CANCELLED,
+            _ => EXIT_FAILURE,
+        },
+    }
+}
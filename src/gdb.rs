@@ -1,25 +1,586 @@
+use std::collections::VecDeque;
 use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
 use regex::Regex;
 
 use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
-use tokio::io::{self, AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio::io::{self, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
 use tokio::select;
-use tokio::time::{timeout, Duration};
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration, Instant};
+
+use crate::error::{as_gdb_error, GdbError, ResultExt};
+use crate::value::{GdbValue, TargetFunctionResult};
+
+/// Strategy used to decode raw bytes read from GDB output into text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ResponseEncoding {
+    /// Strict UTF-8, a line with invalid bytes stops collecting responses.
+    Utf8Strict,
+    /// UTF-8 with invalid sequences replaced by `U+FFFD`.
+    #[default]
+    Utf8Lossy,
+    /// Each byte is mapped directly to the matching Unicode code point.
+    Latin1,
+}
+
+impl ResponseEncoding {
+    /// Decodes a single line of raw GDB output.
+    ///
+    /// # Parameters
+    /// - `bytes`: The raw bytes of the line.
+    ///
+    /// # Returns
+    /// The decoded text or an `io::Error` if strict decoding fails.
+    fn decode(&self, bytes: &[u8]) -> Result<String, io::Error> {
+        match self {
+            ResponseEncoding::Utf8Strict => String::from_utf8(bytes.to_vec())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            ResponseEncoding::Utf8Lossy => {
+                let decoded = String::from_utf8_lossy(bytes);
+                if matches!(decoded, std::borrow::Cow::Owned(_)) {
+                    log::warn!("GDB output contained invalid UTF-8, replaced: '{}'", decoded.trim());
+                }
+                Ok(decoded.into_owned())
+            },
+            ResponseEncoding::Latin1 => Ok(bytes.iter().map(|&b| b as char).collect()),
+        }
+    }
+}
+
+/// Terminator appended to every command sent to GDB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum LineEnding {
+    /// `\n`, understood by GDB on every platform.
+    Lf,
+    /// `\r\n`, some Windows builds of GDB misinterpret commands without it.
+    Crlf,
+}
+
+impl Default for LineEnding {
+    fn default() -> Self {
+        if cfg!(windows) { LineEnding::Crlf } else { LineEnding::Lf }
+    }
+}
+
+impl LineEnding {
+    /// Returns the terminator characters.
+    fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+}
+
+/// Kind of access a watchpoint triggers on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    /// Triggers when the expression is written, GDB `watch`.
+    Write,
+    /// Triggers when the expression is read, GDB `rwatch`.
+    Read,
+    /// Triggers on both reads and writes, GDB `awatch`.
+    Access,
+}
+
+impl WatchKind {
+    /// Returns the GDB command setting this kind of watchpoint.
+    fn command(&self) -> &'static str {
+        match self {
+            WatchKind::Write => "watch",
+            WatchKind::Read => "rwatch",
+            WatchKind::Access => "awatch",
+        }
+    }
+}
+
+/// Timeouts of individual GDB operations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Connecting with `target remote` in [`Gdb::try_new`].
+    pub connect: Duration,
+    /// Waiting for `monitor halt` output, zero means no response is awaited.
+    pub halt: Duration,
+    /// Waiting for `monitor reset` output.
+    pub reset: Duration,
+    /// Waiting after `continue` for a breakpoint or watchpoint to be hit.
+    pub continue_execution: Duration,
+    /// Setting breakpoints and watchpoints.
+    pub breakpoint: Duration,
+    /// Calling target functions, including the copy function.
+    pub call: Duration,
+    /// Reading variables and memory, memory reads add 10 ms per 8 bytes.
+    pub read: Duration,
+    /// Restoring a chunk file into the RAM buffer before its throughput is measured, also used for loading extra symbol files.
+    pub restore: Duration,
+    /// Restoring per KiB of the chunk, overrides the time per KiB measured on previous chunks.
+    pub restore_per_kb: Option<Duration>,
+    /// Flashing the ELF with `load`.
+    pub load: Duration,
+    /// Waiting for GDB to exit after `quit`, before it is killed.
+    pub quit: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect: Duration::from_millis(500),
+            halt: Duration::from_millis(0),
+            reset: Duration::from_millis(250),
+            continue_execution: Duration::from_millis(30_000),
+            breakpoint: Duration::from_millis(750),
+            call: Duration::from_millis(2250),
+            read: Duration::from_millis(250),
+            restore: Duration::from_millis(5000),
+            restore_per_kb: None,
+            load: Duration::from_millis(60_000),
+            quit: Duration::from_millis(2000),
+        }
+    }
+}
+
+/// Debug probe family, selecting the dialect of monitor commands.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Probe {
+    /// OpenOCD and other generic GDB servers.
+    #[default]
+    Openocd,
+    /// SEGGER J-Link GDB server.
+    Jlink,
+    /// Black Magic Probe, which is its own GDB server.
+    Blackmagic,
+}
+
+impl Probe {
+    /// Returns the default monitor command templates of the probe.
+    pub fn monitor_commands(&self) -> MonitorCommands {
+        match self {
+            Probe::Openocd => MonitorCommands {
+                target: String::from("target remote {server}"),
+                attach: vec![],
+                reset: String::from("monitor reset"),
+                reset_halt: Some(String::from("monitor reset halt")),
+                halt: Some(String::from("monitor halt")),
+                sleep: Some(String::from("monitor sleep {millis}")),
+                flash_erase: Some(String::from("monitor flash erase_address {address} {length}")),
+                semihosting: vec![
+                    String::from("monitor arm semihosting enable"),
+                    String::from("monitor arm semihosting_fileio enable"),
+                ],
+            },
+            Probe::Jlink => MonitorCommands {
+                target: String::from("target remote {server}"),
+                attach: vec![],
+                reset: String::from("monitor reset"),
+                // The J-Link reset leaves the core halted.
+                reset_halt: Some(String::from("monitor reset")),
+                halt: Some(String::from("monitor halt")),
+                sleep: Some(String::from("monitor sleep {millis}")),
+                flash_erase: Some(String::from("monitor flash erase")),
+                semihosting: vec![
+                    String::from("monitor semihosting enable"),
+                    String::from("monitor semihosting IOClient 2"),
+                ],
+            },
+            Probe::Blackmagic => MonitorCommands {
+                target: String::from("target extended-remote {server}"),
+                attach: vec![String::from("monitor swdp_scan"), String::from("attach 1")],
+                reset: String::from("monitor hard_srst"),
+                reset_halt: None,
+                halt: None,
+                sleep: None,
+                flash_erase: Some(String::from("monitor erase_mass")),
+                // Semihosting is always served through GDB File-I/O.
+                semihosting: vec![],
+            },
+        }
+    }
+
+    /// Returns the pattern of the line the probe's GDB server prints once it accepts connections.
+    ///
+    /// # Returns
+    /// The pattern, `None` for probes which are their own GDB server.
+    pub fn server_ready_regex(&self) -> Option<&'static str> {
+        match self {
+            Probe::Openocd => Some(r"Listening on port \d+ for gdb connections"),
+            Probe::Jlink => Some(r"Waiting for GDB connection"),
+            Probe::Blackmagic => None,
+        }
+    }
+}
+
+/// Templates of probe specific commands, placeholders in braces are substituted when used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonitorCommands {
+    /// Connecting to the server, `{server}` is the server address.
+    pub target: String,
+    /// Commands run after connecting, e.g. scanning and attaching to the target.
+    pub attach: Vec<String>,
+    /// Resetting the target.
+    pub reset: String,
+    /// Resetting the target and keeping the core halted at the reset vector, unsupported if `None`.
+    pub reset_halt: Option<String>,
+    /// Halting the core, skipped if `None`.
+    pub halt: Option<String>,
+    /// Sleeping on the probe side, `{millis}` is the duration, the host sleeps instead if `None`.
+    pub sleep: Option<String>,
+    /// Erasing flash, `{address}` and `{length}` describe the region, unsupported if `None`.
+    pub flash_erase: Option<String>,
+    /// Enabling semihosting with the output printed by GDB, see [`Gdb::enable_semihosting`].
+    pub semihosting: Vec<String>,
+}
+
+impl Default for MonitorCommands {
+    fn default() -> Self {
+        Probe::default().monitor_commands()
+    }
+}
+
+/// Stream of the GDB process a line was printed on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    /// Regular output, e.g. values and most monitor replies.
+    Stdout,
+    /// Errors and warnings, also reset messages of OpenOCD.
+    Stderr,
+}
+
+/// Output line of a monitor command, probes split it between stdout and stderr in varying ways.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonitorLine {
+    /// Stream the line was printed on.
+    pub stream: OutputStream,
+    /// The trimmed line.
+    pub text: String,
+}
+
+/// Details of the `target remote` handshake, e.g.
+/// ```text
+/// Remote debugging using localhost:3333
+/// warning: Target not halted at reset
+/// 0x08000100 in Reset_Handler ()
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    /// The remote GDB reports debugging with, the server address if it did not report one.
+    pub remote: String,
+    /// Address the target was stopped at when connected, if reported.
+    pub initial_pc: Option<u32>,
+    /// Warnings printed during the handshake.
+    pub warnings: Vec<String>,
+}
+
+impl ConnectionInfo {
+    /// Parses the response of the probe target command.
+    ///
+    /// # Parameters
+    /// - `lines`: The response lines.
+    /// - `server`: The server address connected to.
+    ///
+    /// # Returns
+    /// The connection details, missing ones are left empty.
+    fn parse(lines: &[String], server: &str) -> Self {
+        let remote = lines.iter()
+            .find_map(|line| get_remote_debugging_regex().captures(line))
+            .map_or_else(|| server.to_string(), |captures| captures[1].trim().to_string());
+        let initial_pc = lines.iter()
+            .find_map(|line| get_frame_address_regex().captures(line))
+            .and_then(|captures| u32::from_str_radix(&captures[1], 16).ok());
+        let warnings = lines.iter()
+            .filter_map(|line| get_connect_warning_regex().captures(line))
+            .filter_map(|captures| captures.get(1).or(captures.get(2)).map(|warning| warning.as_str().to_string()))
+            .collect();
+
+        Self { remote, initial_pc, warnings }
+    }
+}
+
+/// Handler of target output lines, e.g. semihosting prints of a called function.
+pub type TargetOutputHandler = Box<dyn FnMut(&str) + Send>;
+
+/// Argument of a target function call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallArg {
+    /// Unsigned 32-bit integer.
+    U32(u32),
+    /// Signed 32-bit integer.
+    #[allow(unused)]
+    I32(i32),
+    /// Any GDB expression, e.g. `&loader_ram_buffer`.
+    #[allow(unused)]
+    Expression(String),
+}
+
+impl std::fmt::Display for CallArg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CallArg::U32(value) => write!(f, "{value}"),
+            CallArg::I32(value) => write!(f, "{value}"),
+            CallArg::Expression(expression) => write!(f, "{expression}"),
+        }
+    }
+}
+
+/// A single disassembled instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    /// Address of the instruction.
+    pub address: u32,
+    /// Raw instruction bytes in the order GDB prints them.
+    pub bytes: Vec<u8>,
+    /// Mnemonic with operands, e.g. `push {r7, lr}`.
+    pub mnemonic: String,
+    /// Whether it is the current instruction, marked with `=>` by GDB.
+    pub is_current: bool,
+}
+
+/// Location of a breakpoint, an inlined or templated function gets one per copy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BreakpointLocation {
+    /// Index of the location within the breakpoint, from 1, e.g. `2` of `1.2`.
+    pub index: u32,
+    /// Code address of the location.
+    pub address: u32,
+    /// Whether the location is enabled.
+    pub enabled: bool,
+}
+
+/// A breakpoint set by [`Gdb::set_breakpoint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    /// Number of the breakpoint.
+    pub number: u32,
+    /// All locations of the breakpoint, also the disabled ones.
+    pub locations: Vec<BreakpointLocation>,
+}
+
+/// A breakpoint whose address no longer matches its source line, found by [`Gdb::refresh_breakpoints`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleBreakpoint {
+    /// Number of the breakpoint before it was set again.
+    pub number: u32,
+    /// Function the breakpoint is in.
+    pub function: String,
+    /// Address the breakpoint pointed to.
+    pub address: u32,
+    /// Address its source line resolves to now.
+    pub resolved_address: u32,
+}
+
+/// Locations of a multi-location breakpoint which stay enabled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BreakpointLocations {
+    /// Stop at every location, as GDB does.
+    #[default]
+    All,
+    /// Stop only at the location with this index, from 1, the other ones are disabled.
+    Only(u32),
+}
+
+/// Reason why the target stopped after being resumed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    /// A breakpoint was hit, e.g. `Breakpoint 1, MX_ThreadX_Init ()`.
+    Breakpoint { number: u32 },
+    /// A watchpoint was triggered, e.g. `Hardware watchpoint 2: result`.
+    Watchpoint { number: u32 },
+    /// A signal was received, e.g. `Program received signal SIGTRAP, Trace/breakpoint trap.`.
+    Signal { name: String },
+    /// The program exited, e.g. `[Inferior 1 (Remote target) exited normally]`.
+    Exited { code: u32 },
+    /// A step finished at a new source line.
+    Stepped,
+    /// The stop could not be classified.
+    Unknown,
+}
+
+impl std::fmt::Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StopReason::Breakpoint { number } => write!(f, "breakpoint {number}"),
+            StopReason::Watchpoint { number } => write!(f, "watchpoint {number}"),
+            StopReason::Signal { name } => write!(f, "signal {name}"),
+            StopReason::Exited { code } => write!(f, "exited with code {code}"),
+            StopReason::Stepped => write!(f, "step finished"),
+            StopReason::Unknown => write!(f, "unknown reason"),
+        }
+    }
+}
+
+/// Classification of a line GDB printed on stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StderrClass {
+    /// Known informational output, e.g. `Resetting target` of `monitor reset`.
+    Benign,
+    /// Known error output, e.g. `No symbol "foo" in current context.`.
+    Error,
+    /// Anything else.
+    Unclassified,
+}
+
+/// Limits of collected GDB output, protecting the host from a target or command flooding it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseLimits {
+    /// Maximal length of a single line in bytes, longer lines are split.
+    pub max_line_length: usize,
+    /// Maximal number of lines collected for one command.
+    pub max_lines: usize,
+    /// Maximal total size of lines collected for one command in bytes.
+    pub max_total_bytes: usize,
+}
+
+impl Default for ResponseLimits {
+    fn default() -> Self {
+        Self {
+            max_line_length: 64 * 1024,
+            max_lines: 10_000,
+            max_total_bytes: 1024 * 1024,
+        }
+    }
+}
+
+impl ResponseLimits {
+    /// Tells whether collecting should stop, logging a warning if so.
+    fn reached(&self, lines_count: usize, total_bytes: usize) -> bool {
+        let reached = lines_count >= self.max_lines || total_bytes >= self.max_total_bytes;
+        if reached {
+            log::warn!("Response limit reached ({lines_count} lines, {total_bytes} B), stopped collecting.");
+        }
+        reached
+    }
+}
+
+/// Options affecting how the GDB subprocess is driven.
+#[derive(Debug, Clone, Default)]
+pub struct GdbOptions {
+    /// Decoding applied to every line read from GDB stdout and stderr.
+    pub encoding: ResponseEncoding,
+    /// Timeouts of individual operations.
+    pub timeouts: Timeouts,
+    /// Fail the current command if GDB prints a stderr line which is neither known benign nor a known error.
+    pub unclassified_stderr_as_error: bool,
+    /// Limits of collected output.
+    pub response_limits: ResponseLimits,
+    /// Probe specific monitor commands.
+    pub monitor: MonitorCommands,
+    /// Terminator of sent commands, responses are accepted with either one.
+    pub line_ending: LineEnding,
+    /// Script GDB runs at startup with `-x`, before any command of the tool.
+    pub init_script: Option<PathBuf>,
+}
 
 pub struct Gdb {
     gdb_subprocess: Child,
     stdout_reader: BufReader<ChildStdout>,
     stderr_reader: BufReader<ChildStderr>,
     stdin_writer: BufWriter<ChildStdin>,
+    options: GdbOptions,
+    unclassified_stderr_line: Option<String>,
+    async_sender: mpsc::Sender<String>,
+    async_receiver: Option<mpsc::Receiver<String>>,
+    /// Sent commands whose echo may still arrive, oldest first.
+    pending_echoes: VecDeque<String>,
+    last_request_instant: Instant,
+    transcript: VecDeque<String>,
+    last_stop_reason: Option<StopReason>,
+    yield_async_stops: bool,
+    target_output_handler: Option<TargetOutputHandler>,
+    /// Target output printed during the last call, searched by [`Gdb::await_target_output`].
+    call_target_output: VecDeque<String>,
+    restore_ms_per_kb: Option<f64>,
+    /// Way target memory is read, found out on the first read.
+    memory_read_method: Option<MemoryReadMethod>,
+    /// Stream of the last line read, see [`Gdb::read_next_raw_line`].
+    last_line_stream: OutputStream,
+    /// Details of the last `target remote` handshake.
+    connection_info: ConnectionInfo,
+}
+
+/// Way target memory is read, some remote stubs do not support `dump binary memory`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MemoryReadMethod {
+    /// `dump binary memory` into a file, fast for large reads.
+    DumpBinary,
+    /// `x/Nxb` examine commands, parsed from the output.
+    Examine,
+}
+
+/// Maximal number of asynchronous lines buffered until the receiver drains them, newer ones are dropped.
+const ASYNC_CHANNEL_CAPACITY: usize = 256;
+
+/// Time given to GDB to exit after it closed its output, telling a crash from a closed stream.
+const PROCESS_EXIT_GRACE: Duration = Duration::from_millis(100);
+
+/// Lower bound of the restore timeout scaled with the chunk size.
+const RESTORE_TIMEOUT_FLOOR: Duration = Duration::from_millis(250);
+/// Upper bound of the restore timeout scaled with the chunk size.
+const RESTORE_TIMEOUT_CEILING: Duration = Duration::from_secs(120);
+
+/// Factor applied to the measured restore time per KiB, so jitter of the link does not time out.
+const RESTORE_TIMEOUT_MARGIN: f64 = 4.0;
+
+/// Returns the restore timeout scaled with the size of the restored file.
+///
+/// The time per KiB is the configured one, otherwise the one measured on previous restores with a margin,
+/// the timeout is kept within [`RESTORE_TIMEOUT_FLOOR`] and [`RESTORE_TIMEOUT_CEILING`]. Before the first
+/// measurement the fixed restore timeout is used.
+///
+/// # Parameters
+/// - `timeouts`: The timeouts with the fixed restore timeout and the optional time per KiB.
+/// - `measured_ms_per_kb`: The restore time per KiB measured so far, `None` before the first restore.
+/// - `bytes_count`: The size of the restored file in bytes.
+///
+/// # Returns
+/// The timeout of the restore.
+fn scaled_restore_timeout(timeouts: &Timeouts, measured_ms_per_kb: Option<f64>, bytes_count: usize) -> Duration {
+    let ms_per_kb = match (timeouts.restore_per_kb, measured_ms_per_kb) {
+        (Some(configured), _) => configured.as_secs_f64() * 1000.0,
+        (None, Some(measured)) => measured * RESTORE_TIMEOUT_MARGIN,
+        (None, None) => return timeouts.restore,
+    };
+    let timeout_ms = ms_per_kb * bytes_count as f64 / 1024.0;
+
+    Duration::from_secs_f64(timeout_ms / 1000.0).clamp(RESTORE_TIMEOUT_FLOOR, RESTORE_TIMEOUT_CEILING)
 }
 
+/// Time GDB must stay silent while its output is drained, at startup and during a recovery.
+const RECOVERY_IDLE_GAP: Duration = Duration::from_millis(250);
+
+/// Maximal number of bytes read by a single `x/Nxb` command, longer reads are split.
+const EXAMINE_READ_MAX: usize = 4096;
+
+/// Line printed with `echo` after `dump binary memory`, which prints nothing on success.
+const DUMP_DONE_MARKER: &str = "gdbloader-dump-done";
+
+/// Line printed with `echo` after a call of a void function, which prints nothing on success.
+const CALL_DONE_MARKER: &str = "gdbloader-call-done";
+
+/// Time GDB must stay silent after the first output line of a monitor command for the output to count as complete.
+const MONITOR_IDLE_GAP: Duration = Duration::from_millis(250);
+
+/// Pause between the two PC reads of [`Gdb::verify_halted`], a running core moves its PC within microseconds.
+const HALT_CHECK_DELAY: Duration = Duration::from_millis(20);
+
+/// Number of target output lines of the last call kept for [`Gdb::await_target_output`], older ones are dropped.
+const CALL_TARGET_OUTPUT_CAPACITY: usize = 64;
+
+/// Number of sent commands whose echo is awaited, older ones are assumed not echoed.
+const PENDING_ECHOES_CAPACITY: usize = 16;
+
+/// Number of most recent commands and output lines kept in the transcript, older ones are dropped.
+const TRANSCRIPT_CAPACITY: usize = 200;
+
 /// A wrapper for interacting with a GDB process asynchronously.
 ///
 /// This struct spawns a GDB subprocess and provides methods to send commands,
 /// receive responses, and perform common actions such as setting breakpoints,
 /// calling functions, and transferring binary data. Many commands expect responses
 /// in specific formats as noted in the method documentation.
+///
+/// Every method takes `&mut self`, as GDB is a single serial process and a command must not
+/// interleave with another one. To issue commands from several tasks, wrap it in a
+/// [`crate::shared_gdb::SharedGdb`].
 impl Gdb {
     /// Creates a new GDB instance by spawning a GDB subprocess.
     ///
@@ -27,30 +588,43 @@ impl Gdb {
     /// - `executive_path`: The path to the GDB executable.
     /// - `target_elf_path`: The path to the target ELF file.
     /// - `server`: The remote server address to connect to.
+    /// - `options`: Options such as the response decoding strategy.
     ///
     /// # Process Flow
-    /// 1. Spawns the GDB process with piped stdin, stdout, and stderr.
-    /// 2. Sends the command `"set confirm off"` (no expected response).
+    /// 1. Spawns the GDB process with piped stdin, stdout, and stderr, running the init script with `-x` if given.
+    /// 2. Sends the commands `"set confirm off"`, `"set editing off"`, `"set pagination off"`
+    ///    and `"set width 0"` (no expected response).
     /// 3. Clears any pending responses.
-    /// 4. Connects to the remote server with `"target remote {server}"` (response may take time).
+    /// 4. Connects to the remote server with the probe target command, by default `"target remote {server}"`
+    ///    (response may take time), then runs the probe attach commands.
     ///
     /// # Returns
-    /// Returns an instance of `Gdb` on success.
+    /// Returns an instance of `Gdb` on success, an error if GDB cannot be started
+    /// or reports a failed connection.
     pub async fn try_new(
         executive_path: PathBuf,
         target_elf_path: PathBuf,
         server: String,
+        options: GdbOptions,
     ) -> Result<Self, io::Error> {
         log::info!("Creating GDB");
 
-        let mut gdb_subcommand = Command::new(executive_path)
-            .arg("-q")
+        let mut gdb_command = Command::new(executive_path);
+        gdb_command.arg("-q");
+        if let Some(init_script) = &options.init_script {
+            // GDB only prints an error for a missing script and goes on without it.
+            if !init_script.is_file() {
+                return Err(io::Error::new(io::ErrorKind::NotFound, format!("GDB script {init_script:?} not found")));
+            }
+            gdb_command.arg("-x").arg(init_script);
+        }
+        let mut gdb_subcommand = gdb_command
             .arg(target_elf_path)
             .stdin(std::process::Stdio::piped())
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped())
             .spawn()
-            .expect("Failed to start GDB");
+            .map_err(|e| io::Error::new(e.kind(), format!("Failed to start GDB: {e}")))?;
     
         let stdout = gdb_subcommand.stdout.take().expect("Failed to open stdout");
         let stderr = gdb_subcommand.stderr.take().expect("Failed to open stderr");
@@ -60,444 +634,2709 @@ impl Gdb {
         let stderr_reader = BufReader::new(stderr);
         let stdin_writer = BufWriter::new(stdin);
 
+        let (async_sender, async_receiver) = mpsc::channel(ASYNC_CHANNEL_CAPACITY);
+
         let mut gdb = Self {
             gdb_subprocess: gdb_subcommand,
             stdout_reader,
             stderr_reader,
-            stdin_writer
+            stdin_writer,
+            options,
+            unclassified_stderr_line: None,
+            async_sender,
+            async_receiver: Some(async_receiver),
+            pending_echoes: VecDeque::with_capacity(PENDING_ECHOES_CAPACITY),
+            last_request_instant: Instant::now(),
+            transcript: VecDeque::with_capacity(TRANSCRIPT_CAPACITY),
+            last_stop_reason: None,
+            yield_async_stops: false,
+            target_output_handler: None,
+            call_target_output: VecDeque::new(),
+            restore_ms_per_kb: None,
+            memory_read_method: None,
+            last_line_stream: OutputStream::Stdout,
+            connection_info: ConnectionInfo::default(),
         };
 
         // Send "set confirm off" with no expected return response.
         gdb.make_request("set confirm off").await?;
 
-        // Clear all pending responses.
-        let _ = gdb.await_responses(None, Duration::from_millis(250)).await;
+        // Disable line editing, some GDB builds echo commands back with it.
+        gdb.make_request("set editing off").await?;
 
-        // Connect to the target; this command can take a while.
-        let _ = gdb.make_request_await_response(
-            format!("target remote {server}").as_str(),
-            None,
-            Duration::from_millis(500)
-        ).await?;
+        // Long outputs must neither stop at a pager prompt nor be wrapped.
+        gdb.make_request("set pagination off").await?;
+        gdb.make_request("set width 0").await?;
+
+        // Clear all pending responses, including the output of the init script.
+        let discarded = gdb.flush_responses(RECOVERY_IDLE_GAP).await;
+        match &gdb.options.init_script {
+            Some(init_script) => log::info!("Discarded startup output of init script {init_script:?}: {discarded:?}"),
+            None => log::debug!("Discarded startup output: {discarded:?}"),
+        }
 
+        gdb.connect(&server).await?;
         Ok(gdb)
     }
 
-    /// Sends a command to GDB.
+    /// Connects to the remote server with the probe target command and runs the probe attach commands.
+    ///
+    /// # Expected Result
+    /// Lines similar to:
+    /// `Remote debugging using localhost:3333` and `0x08000100 in Reset_Handler ()`
     ///
     /// # Parameters
-    /// - `cmd`: The command string to be sent.
+    /// - `server`: The remote server address to connect to.
     ///
     /// # Returns
-    /// An `io::Result<()>` indicating whether the command was successfully written.
-    pub async fn make_request(&mut self, cmd: &str) -> io::Result<()> {
-        log::debug!("Requesting cmd='{cmd}'...");
-        self.stdin_writer.write_all(format!("{}\n", cmd).as_bytes()).await?;
-        self.stdin_writer.flush().await
+    /// An `io::Result<()>`, [`GdbError::Connection`] if GDB reports a failed connection.
+    async fn connect(&mut self, server: &str) -> Result<(), io::Error> {
+        // Connect to the target; this command can take a while.
+        let lines = self.make_request_await_response(
+            self.options.monitor.target.replace("{server}", server).as_str(),
+            None,
+            self.options.timeouts.connect
+        ).await?;
+
+        if let Some(error_line) = lines.iter().find(|line| get_connect_error_regex().is_match(line)) {
+            return Err(GdbError::Connection(format!("Connecting to '{server}' failed: {error_line}")).into());
+        }
+
+        self.connection_info = ConnectionInfo::parse(&lines, server);
+        for warning in &self.connection_info.warnings {
+            log::warn!("Connecting to '{server}': {warning}");
+        }
+        match self.connection_info.initial_pc {
+            Some(initial_pc) => log::info!("Connected to '{}', PC={initial_pc:#010x}.", self.connection_info.remote),
+            None => log::info!("Connected to '{}'.", self.connection_info.remote),
+        }
+
+        for attach_cmd in self.options.monitor.attach.clone() {
+            self.make_request_await_response(&attach_cmd, None, self.options.timeouts.connect).await?;
+        }
+
+        Ok(())
     }
 
-    /// Awaits responses from GDB until the timeout or until the expected number of responses is collected.
+    /// Returns the details of the last `target remote` handshake, e.g. the PC the target was stopped at.
+    pub fn connection_info(&self) -> &ConnectionInfo {
+        &self.connection_info
+    }
+
+    /// Brings a wedged target, e.g. stuck in a hard fault loop or a bus hang, back to a known halted state
+    /// without restarting GDB, so the caller can retry the failed operation.
+    ///
+    /// # Process Flow
+    /// 1. Drains pending output, e.g. of a timed out command.
+    /// 2. Resets the target with the probe reset command.
+    /// 3. If the connection dropped, connects again with the probe target command,
+    ///    drains the startup output and resets again.
+    /// 4. Halts the core with the probe halt command.
     ///
     /// # Parameters
-    /// - `expected_count`: Optional expected number of responses.
-    /// - `await_timeout`: The maximum duration to wait for responses.
+    /// - `server`: The remote server address, used if the connection must be established again.
     ///
     /// # Returns
-    /// A `Vec<String>` containing the lines received from GDB.
-    async fn await_responses(&mut self, expected_count: Option<usize>, await_timeout: Duration) -> Vec<String> {
-        let mut responses = Vec::new();
-
-        let _ = timeout(await_timeout, async {
+    /// An `io::Result<()>`, [`GdbError::Connection`] if connecting again failed.
+    /// [`GdbError::ProcessDied`] is passed through as GDB itself cannot be recovered.
+    #[allow(unused)]
+    pub async fn reset_and_reconnect(&mut self, server: &str) -> Result<(), io::Error> {
+        let discarded = self.flush_responses(RECOVERY_IDLE_GAP).await;
+        log::debug!("Discarded output before recovery: {discarded:?}");
+        let mut connection_lost = discarded.iter().any(|line| get_disconnected_regex().is_match(line));
 
-            loop {
-                let mut line_stdout_buffer = String::new();
-                let mut line_stderr_buffer = String::new();
-
-                select! {
-                    stdout_result = self.stdout_reader.read_line(&mut line_stdout_buffer) => {
-                        match stdout_result {
-                            Ok(0) => {
-                                log::warn!("GDB process stdout closed unexpectedly!");
-                                break;
-                            },
-                            Ok(_) => {
-                                let trimmed_line = line_stdout_buffer.trim().to_string();
-                                log::debug!("STDOUT: {trimmed_line}");
-                                responses.push(trimmed_line);
-                                line_stdout_buffer.clear();
-                            },
-                            Err(e) => {
-                                log::error!("Error reading stdout: {e}");
-                                break;
-                            }
-                        }
-                    },
+        if !connection_lost {
+            match self.monitor_reset().await {
+                Ok(lines) => connection_lost = lines.iter().any(|line| get_disconnected_regex().is_match(&line.text)),
+                Err(e) if get_disconnected_regex().is_match(&e.to_string()) => connection_lost = true,
+                Err(e) => return Err(e).with_context(|| "recovery reset failed"),
+            }
+        }
 
-                    stderr_result = self.stderr_reader.read_line(&mut line_stderr_buffer) => {
-                        match stderr_result {
-                            Ok(0) => {
-                                log::warn!("GDB process stdout closed unexpectedly!");
-                                break;
-                            },
-                            Ok(_) => {
-                                let trimmed_line = line_stderr_buffer.trim().to_string();
-                                log::debug!("STDERR: {trimmed_line}");
-                                responses.push(trimmed_line);
-                                line_stderr_buffer.clear();
-                            },
-                            Err(e) => {
-                                log::error!("Error reading stderr: {e}");
-                                break;
-                            }
-                        }
-                    }
-                }
+        if connection_lost {
+            log::warn!("Connection to '{server}' lost, connecting again.");
+            self.connect(server).await?;
+            let discarded = self.flush_responses(RECOVERY_IDLE_GAP).await;
+            log::debug!("Discarded startup output: {discarded:?}");
+            self.monitor_reset().await.with_context(|| "recovery reset after connecting again failed")?;
+        }
 
-                // If a specific number of responses was expected and reached, exit early.
-                if let Some(expected_responses_count) = expected_count {
-                    if expected_responses_count == responses.len() {
-                        log::trace!("Collected enough responses {expected_responses_count}.");
-                        break;
-                    }
-                }
-            }
-        })
-        .await
-        .is_ok();
-    
-        log::debug!("Responses: {responses:?}");
-        responses
+        self.monitor_halt().await?;
+        self.last_stop_reason = None;
+        log::info!("Target reset and halted.");
+        Ok(())
     }
 
-    /// Sends a command to GDB and awaits responses.
+    /// Sends a command to GDB.
     ///
     /// # Parameters
     /// - `cmd`: The command string to be sent.
-    /// - `expected_count`: Optional expected number of responses.
-    /// - `await_timeout`: The maximum duration to wait for responses.
     ///
     /// # Returns
-    /// A `Result` with a vector of response lines, or an `io::Error`.
-    pub async fn make_request_await_response(
-        &mut self,
-        cmd: &str,
-        expected_count: Option<usize>,
-        await_timeout: Duration
-    ) -> Result<Vec<String>, io::Error> {
-        // Make request
-        self.make_request(cmd).await?;
-    
-        if matches!(expected_count, Some(0)) {
-            // No response is expected.
-            Ok(vec![])
-        } else {
-            Ok(self.await_responses(expected_count, await_timeout).await)
+    /// An `io::Result<()>` indicating whether the command was successfully written,
+    /// [`GdbError::ProcessDied`] if GDB has exited.
+    pub async fn make_request(&mut self, cmd: &str) -> io::Result<()> {
+        log::debug!("Requesting cmd='{cmd}'...");
+        // Queued, a request sent before the response of the previous one is read keeps both echoes recognized.
+        if self.pending_echoes.len() == PENDING_ECHOES_CAPACITY {
+            self.pending_echoes.pop_front();
         }
+        self.pending_echoes.push_back(cmd.to_string());
+        self.last_request_instant = Instant::now();
+        self.record_transcript(format!("> {cmd}"));
+        let line_ending = self.options.line_ending.as_str();
+        let result = match self.stdin_writer.write_all(format!("{cmd}{line_ending}").as_bytes()).await {
+            Ok(()) => self.stdin_writer.flush().await,
+            Err(e) => Err(e),
+        };
+
+        // A write usually fails because GDB is gone, report that instead of a broken pipe.
+        if result.is_err() {
+            if let Ok(Some(status)) = self.gdb_subprocess.try_wait() {
+                return Err(GdbError::ProcessDied { status }.into());
+            }
+        }
+        result
     }
 
-    /// Sends the "quit" command to GDB and wait until subprocess is finished.
+    /// Returns the most recent transcript entries, sent commands are prefixed with `> `.
+    ///
+    /// # Parameters
+    /// - `count`: The maximal number of entries, at most [`TRANSCRIPT_CAPACITY`] are kept.
     ///
     /// # Returns
-    /// An `io::Result<()>` indicating whether the command was successfully sent.
-    pub async fn quit_and_wait(&mut self) -> io::Result<()> {
-        self.make_request(
-            "quit", 
-        ).await?;
-
-        self.gdb_subprocess
-            .wait()
-            .await
-            .map(|status_code| {
-                if status_code.success() {
-                    log::info!("Subprocess finished successfully!");
-                } else {
-                    log::info!("Subprocess finished failed: {}!", status_code.to_string());
-                };
-            })       
+    /// The entries, oldest first.
+    pub fn transcript_tail(&self, count: usize) -> Vec<String> {
+        self.transcript.iter()
+            .skip(self.transcript.len().saturating_sub(count))
+            .cloned()
+            .collect()
     }
 
-    /// Sends the "help" command to GDB and awaits the response.
+    /// Sets the handler receiving target output, e.g. semihosting prints, instead of dropping it.
     ///
-    /// # Returns
-    /// A `Result` containing the help text lines or an `io::Error`.
-    #[allow(unused)]
-    pub async fn help(&mut self) -> Result<Vec<String>, io::Error> {
-        self.make_request_await_response(
-            "help", 
-            None,
-            Duration::from_millis(500)
-        ).await
+    /// # Parameters
+    /// - `handler`: Called with every target output line.
+    pub fn set_target_output_handler(&mut self, handler: TargetOutputHandler) {
+        self.target_output_handler = Some(handler);
     }
 
-    /// Sends the "monitor halt" command.
-    ///
-    /// # Expected Result
-    /// Generally, no response is expected after sending this command.
-    ///
-    /// # Returns
-    /// A `Result` containing an empty vector or an `io::Error`.
-    pub async fn monitor_halt(&mut self) -> Result<Vec<String>, io::Error> {
-        self.make_request_await_response(
-            "monitor halt", 
-            Some(0),
-            Duration::from_millis(0)
-        ).await
+    /// Logs a target output line and passes it to the handler, if any.
+    fn forward_target_output(&mut self, line: &str) {
+        log::info!("TARGET: {line}");
+        if let Some(handler) = &mut self.target_output_handler {
+            handler(line);
+        }
     }
 
-    /// Sends the "continue" command to resume execution.
-    ///
-    /// # Expected Result
-    /// Several lines may be returned. For example, one of the lines might be:
-    /// `Breakpoint 1, MX_ThreadX_Init ()`
-    ///
-    /// # Returns
-    /// A `Result` containing the response lines or an `io::Error`.
-    pub async fn continue_execution(&mut self) -> Result<Vec<String>, io::Error> {
-        self.make_request_await_response(
-            "continue", 
-            None,
-            Duration::from_millis(5000)
-        ).await
+    /// Forwards a target output line printed during a call and keeps it for [`Gdb::await_target_output`].
+    fn collect_call_target_output(&mut self, line: &str) {
+        self.forward_target_output(line);
+        if self.call_target_output.len() == CALL_TARGET_OUTPUT_CAPACITY {
+            self.call_target_output.pop_front();
+        }
+        self.call_target_output.push_back(line.to_string());
     }
 
-    /// Sends the "monitor reset" command to reset the target.
+    /// Waits for a target output line matching a pattern, e.g. a confirmation printed by the called function.
+    /// The output printed during the last call is searched first, lines up to the match are consumed.
     ///
     /// # Expected Result
-    /// A single stderr response line, for example:
-    /// `Resetting target`
+    /// A semihosting line similar to:
+    /// `Wrote 65536 bytes at 0x200000`
+    ///
+    /// # Parameters
+    /// - `pattern`: The pattern of the awaited line.
+    /// - `wait`: How long to wait for new output if the last call printed no matching line.
     ///
     /// # Returns
-    /// A `Result` containing the response lines or an `io::Error`.
-    /// 
-    /// # Note
-    /// Response for some reason is on stderr.
-    pub async fn monitor_reset(&mut self) -> Result<Vec<String>, io::Error> {
-        self.make_request_await_response(
-            "monitor reset",
-            Some(1),
-            Duration::from_millis(250)
-        ).await
+    /// An `io::Result` with the matching line, [`GdbError::Timeout`] if none was printed in time.
+    pub async fn await_target_output(&mut self, pattern: &Regex, wait: Duration) -> Result<String, io::Error> {
+        while let Some(line) = self.call_target_output.pop_front() {
+            if pattern.is_match(&line) {
+                return Ok(line);
+            }
+        }
+
+        let deadline = Instant::now() + wait;
+        loop {
+            match timeout(deadline.saturating_duration_since(Instant::now()), self.read_next_line()).await {
+                Ok(Ok(Some(line))) if is_target_output_line(&line) => {
+                    self.forward_target_output(&line);
+                    if pattern.is_match(&line) {
+                        return Ok(line);
+                    }
+                },
+                Ok(Ok(Some(line))) => log::debug!("Skipped while awaiting target output: {line}"),
+                Ok(Ok(None)) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "GDB output closed")),
+                Ok(Err(e)) => return Err(e),
+                Err(_) => return Err(GdbError::Timeout(
+                    format!("No target output matching '{pattern}' within {} ms", wait.as_millis())
+                ).into()),
+            }
+        }
     }
 
-    /// Generic helper to call a function on the target.
+    /// Returns the reason of the last stop after `continue` or `step`, `None` if the target was not resumed yet.
+    pub fn last_stop_reason(&self) -> Option<&StopReason> {
+        self.last_stop_reason.as_ref()
+    }
+
+    /// Appends an entry to the transcript, dropping the oldest one if full.
+    fn record_transcript(&mut self, entry: String) {
+        if self.transcript.len() == TRANSCRIPT_CAPACITY {
+            self.transcript.pop_front();
+        }
+        self.transcript.push_back(entry);
+    }
+
+    /// Takes the receiver of asynchronous GDB output.
     ///
-    /// # Parameters
-    /// - `function`: The function call string (e.g., `"foo()"` or `"bar(42)"`).
-    /// - `has_return`: Indicates if a return value is expected.
+    /// Lines which are not responses to commands, e.g. MI async records (`*running`, `*stopped`,
+    /// `=thread-group-started`) or thread notifications (`[New Thread ...]`), are routed to it
+    /// instead of being mixed into command responses. If the receiver is not drained,
+    /// lines exceeding its capacity are dropped.
     ///
     /// # Returns
-    /// On success, returns the output of the function call as a `String` (empty if no return is expected).
-    async fn call_generic(&mut self, function: &str, has_return: bool) -> Result<String, io::Error> {
-        let results = self.make_request_await_response(
-            format!("call {function}").as_str(), 
-            if has_return { Some(1) } else { None },
-            Duration::from_millis(2250)
-        )
-        .await?;
+    /// The receiver on the first call, `None` afterwards.
+    #[allow(unused)]
+    pub fn take_async_receiver(&mut self) -> Option<mpsc::Receiver<String>> {
+        self.async_receiver.take()
+    }
 
-        extract_call_result(results, has_return)
+    /// Reads the next response line, routing asynchronous lines to the async channel
+    /// and skipping the echoes of sent commands, if GDB prints them. Known chatter
+    /// such as symbol reload notices is logged and skipped too.
+    ///
+    /// # Returns
+    /// The decoded and trimmed line, `None` if one of the streams was closed,
+    /// or an `io::Error` if reading or decoding failed.
+    async fn read_next_line(&mut self) -> io::Result<Option<String>> {
+        loop {
+            let result = self.read_next_raw_line().await;
+            if let Ok(Some(line)) = &result {
+                self.record_transcript(line.clone());
+            }
+
+            match result {
+                Ok(Some(line)) if is_async_line(&line) => {
+                    log::debug!("ASYNC: {line}");
+                    // A stop record awaited by `wait_for_stop` is returned too.
+                    let yielded_line = (self.yield_async_stops && is_mi_stop_line(&line)).then(|| line.clone());
+                    if self.async_sender.try_send(line).is_err() {
+                        log::trace!("Async channel full, line dropped.");
+                    }
+                    if yielded_line.is_some() {
+                        return Ok(yielded_line);
+                    }
+                },
+                Ok(Some(line)) if is_chatter_line(&line) => log::warn!("GDB: {line}"),
+                Ok(Some(line)) => {
+                    // Echoes arrive in the order of the requests, commands sent earlier were not echoed.
+                    match self.pending_echoes.iter().position(|cmd| is_echo_line(&line, cmd)) {
+                        Some(index) => {
+                            self.pending_echoes.drain(..=index);
+                            log::debug!("ECHO: {line}");
+                        },
+                        None => return Ok(Some(line)),
+                    }
+                },
+                Ok(None) => {
+                    // A closed stream usually means GDB is exiting, give it a moment to be reaped.
+                    if let Ok(status) = timeout(PROCESS_EXIT_GRACE, self.gdb_subprocess.wait()).await {
+                        return Err(GdbError::ProcessDied { status: status? }.into());
+                    }
+                    return Ok(None);
+                },
+                result => return result,
+            }
+        }
     }
 
-    /// Calls a function on the target with no arguments.
+    /// Reads the next line from either GDB stdout or stderr, whichever comes first.
     ///
-    /// # Expected Result
-    /// - If the function returns a value, the output might be something like:
-    ///   `$23 = 118 'v'`
-    /// - If the function returns void, an empty string is returned.
+    /// # Returns
+    /// The decoded and trimmed line, `None` if one of the streams was closed,
+    /// or an `io::Error` if reading or decoding failed. If the GDB process exits while
+    /// its streams stay open, e.g. inherited by a child, fails with [`GdbError::ProcessDied`].
+    async fn read_next_raw_line(&mut self) -> io::Result<Option<String>> {
+        let mut line_stdout_buffer = Vec::new();
+        let mut line_stderr_buffer = Vec::new();
+        let max_line_length = self.options.response_limits.max_line_length as u64;
+        let mut stdout_reader = (&mut self.stdout_reader).take(max_line_length);
+        let mut stderr_reader = (&mut self.stderr_reader).take(max_line_length);
+
+        // Biased, so output buffered before an exit is still read.
+        select! {
+            biased;
+
+            stdout_result = stdout_reader.read_until(b'\n', &mut line_stdout_buffer) => {
+                match stdout_result.and_then(|_| self.options.encoding.decode(&line_stdout_buffer)) {
+                    Ok(line) if line.is_empty() => {
+                        log::warn!("GDB process stdout closed unexpectedly!");
+                        Ok(None)
+                    },
+                    Ok(line) => {
+                        if line_stdout_buffer.len() as u64 == max_line_length && !line_stdout_buffer.ends_with(b"\n") {
+                            log::warn!("GDB stdout line longer than {max_line_length} B, split.");
+                        }
+                        // Trimming strips both `\n` and `\r\n` line endings.
+                        let trimmed_line = line.trim().to_string();
+                        log::debug!("STDOUT: {trimmed_line}");
+                        self.last_line_stream = OutputStream::Stdout;
+                        Ok(Some(trimmed_line))
+                    },
+                    Err(e) => {
+                        log::error!("Error reading stdout: {e}");
+                        Err(e)
+                    }
+                }
+            },
+
+            stderr_result = stderr_reader.read_until(b'\n', &mut line_stderr_buffer) => {
+                match stderr_result.and_then(|_| self.options.encoding.decode(&line_stderr_buffer)) {
+                    Ok(line) if line.is_empty() => {
+                        log::warn!("GDB process stderr closed unexpectedly!");
+                        Ok(None)
+                    },
+                    Ok(line) => {
+                        if line_stderr_buffer.len() as u64 == max_line_length && !line_stderr_buffer.ends_with(b"\n") {
+                            log::warn!("GDB stderr line longer than {max_line_length} B, split.");
+                        }
+                        let trimmed_line = line.trim().to_string();
+                        self.last_line_stream = OutputStream::Stderr;
+                        match classify_stderr_line(&trimmed_line) {
+                            StderrClass::Benign => log::info!("STDERR: {trimmed_line}"),
+                            StderrClass::Error => log::error!("STDERR: {trimmed_line}"),
+                            StderrClass::Unclassified => {
+                                log::warn!("STDERR: {trimmed_line}");
+                                if self.options.unclassified_stderr_as_error && self.unclassified_stderr_line.is_none() {
+                                    self.unclassified_stderr_line = Some(trimmed_line.clone());
+                                }
+                            },
+                        }
+                        Ok(Some(trimmed_line))
+                    },
+                    Err(e) => {
+                        log::error!("Error reading stderr: {e}");
+                        Err(e)
+                    }
+                }
+            },
+
+            status_result = self.gdb_subprocess.wait() => {
+                let status = status_result?;
+                log::error!("GDB process died: {status}");
+                Err(GdbError::ProcessDied { status }.into())
+            }
+        }
+    }
+
+    /// Awaits responses from GDB until the timeout or until the expected number of responses is collected.
     ///
     /// # Parameters
-    /// - `function_name`: The name of the function to call.
-    /// - `has_return`: Whether a return value is expected.
+    /// - `expected_count`: Optional expected number of responses.
+    /// - `await_timeout`: The maximum duration to wait for responses.
     ///
     /// # Returns
-    /// A `Result` containing the function output or an `io::Error`.
-    #[allow(unused)]
-    pub async fn call(&mut self, function_name: &str, has_return: bool) -> Result<String, io::Error> {
-        self.call_generic(format!("{function_name}()").as_str(), has_return).await
+    /// An `io::Result` with the lines received from GDB, or [`GdbError::ProcessDied`]
+    /// as soon as the GDB process exits, instead of waiting out the timeout.
+    async fn await_responses(&mut self, expected_count: Option<usize>, await_timeout: Duration) -> io::Result<Vec<String>> {
+        let mut responses = Vec::new();
+        let mut total_bytes = 0;
+
+        let result = timeout(await_timeout, async {
+            loop {
+                let line = match self.read_next_line().await {
+                    Ok(Some(line)) => line,
+                    Err(e) if is_process_died(&e) => return Err(e),
+                    _ => return Ok(()),
+                };
+                total_bytes += line.len();
+                responses.push(line);
+
+                if self.options.response_limits.reached(responses.len(), total_bytes) {
+                    break;
+                }
+
+                // If a specific number of responses was expected and reached, exit early.
+                if let Some(expected_responses_count) = expected_count {
+                    if expected_responses_count == responses.len() {
+                        log::trace!("Collected enough responses {expected_responses_count}.");
+                        break;
+                    }
+                }
+            }
+            Ok(())
+        })
+        .await;
+    
+        log::debug!("Responses: {responses:?}");
+        result.unwrap_or(Ok(()))?;
+        Ok(responses)
     }
 
-    /// Calls a function on the target with one `u32` argument.
-    ///
-    /// # Expected Result
-    /// Works similarly to [`Gdb::call`], returning the function's output if a return value is expected.
+    /// Awaits responses from GDB until a line satisfying `is_final` arrives or the timeout expires.
     ///
     /// # Parameters
-    /// - `function_name`: The name of the function to call.
-    /// - `arg`: The `u32` argument.
-    /// - `has_return`: Whether a return value is expected.
+    /// - `is_final`: Predicate marking the line which completes the response.
+    /// - `await_timeout`: The maximum duration to wait for the final line.
     ///
     /// # Returns
-    /// A `Result` containing the function output or an `io::Error`.
-    #[allow(unused)]
-    pub async fn call_with_u32(
-        &mut self, 
-        function_name: &str, 
-        arg: u32, 
-        has_return: bool
-    ) -> Result<String, io::Error> {
-        self.call_generic(format!("{function_name}({arg})").as_str(), has_return).await
+    /// An `io::Result` with a tuple of all collected lines and a flag telling whether the final line was found,
+    /// or [`GdbError::ProcessDied`] as soon as the GDB process exits.
+    async fn await_responses_until<F>(&mut self, is_final: F, await_timeout: Duration) -> io::Result<(Vec<String>, bool)>
+    where
+        F: Fn(&str) -> bool
+    {
+        let mut responses = Vec::new();
+        let mut total_bytes = 0;
+
+        let found = timeout(await_timeout, async {
+            loop {
+                let line = match self.read_next_line().await {
+                    Ok(Some(line)) => line,
+                    Err(e) if is_process_died(&e) => return Err(e),
+                    _ => return Ok(false),
+                };
+                let done = is_final(&line);
+                total_bytes += line.len();
+                responses.push(line);
+
+                if done {
+                    return Ok(true);
+                }
+
+                if self.options.response_limits.reached(responses.len(), total_bytes) {
+                    return Ok(false);
+                }
+            }
+        })
+        .await
+        .unwrap_or(Ok(false));
+
+        log::debug!("Responses: {responses:?}, final line found: {found:?}");
+        Ok((responses, found?))
     }
 
-    /// Calls a function on the target with two `u32` arguments.
-    ///
-    /// # Expected Result
-    /// Works similarly to [`Gdb::call`], returning the function's output if a return value is expected.
+    /// Sends a command to GDB and awaits responses.
     ///
     /// # Parameters
-    /// - `function_name`: The name of the function to call.
-    /// - `arg1`: The first `u32` argument.
-    /// - `arg2`: The second `u32` argument.
-    /// - `has_return`: Whether a return value is expected.
+    /// - `cmd`: The command string to be sent.
+    /// - `expected_count`: Optional expected number of responses.
+    /// - `await_timeout`: The maximum duration to wait for responses.
     ///
     /// # Returns
-    /// A `Result` containing the function output or an `io::Error`.
-    pub async fn call_with_u32_u32(
-        &mut self, function_name: &str, 
-        arg1: u32, 
-        arg2: u32, 
-        has_return: bool
-    ) -> Result<String, io::Error> {
-        self.call_generic(format!("{function_name}({arg1}, {arg2})").as_str(), has_return).await
+    /// A `Result` with a vector of response lines, or an `io::Error`. A response reporting a missing
+    /// symbol fails with [`GdbError::SymbolNotFound`]. If unclassified stderr lines are treated
+    /// as errors, the first such line collected fails the request.
+    pub async fn make_request_await_response(
+        &mut self,
+        cmd: &str,
+        expected_count: Option<usize>,
+        await_timeout: Duration
+    ) -> Result<Vec<String>, io::Error> {
+        // Make request
+        self.make_request(cmd).await?;
+    
+        if matches!(expected_count, Some(0)) {
+            // No response is expected.
+            Ok(vec![])
+        } else {
+            let responses = self.await_responses(expected_count, await_timeout).await?;
+            if let Some(symbol) = responses.iter().find_map(|line| extract_missing_symbol_from_response_line(line)) {
+                return Err(io::Error::from(GdbError::SymbolNotFound(symbol))).with_context(|| format!("'{cmd}'"));
+            }
+
+            match self.unclassified_stderr_line.take() {
+                Some(line) => Err(io::Error::other(format!("Unexpected GDB stderr after '{cmd}': {line}"))),
+                None => Ok(responses),
+            }
+        }
     }
 
-    /// Calls a function on the target with two `u32` arguments and extracts a `u32` return value.
+    /// Sends a harmless remote `?` packet if no command was sent for `idle_interval`,
+    /// keeping links whose probe watchdog drops idle connections alive.
+    ///
+    /// GDB is not reentrant, so it can only be used between commands, never while one is pending.
     ///
     /// # Expected Result
-    /// For example, if the function output is `$23 = 118 'v'`, this method extracts and returns `118`.
+    /// Lines similar to:
+    /// `sending: ?` and `received: "T05thread:01;"`
     ///
     /// # Parameters
-    /// - `function_name`: The name of the function to call.
-    /// - `arg1`: The first `u32` argument.
-    /// - `arg2`: The second `u32` argument.
-    /// - `has_return`: Whether a return value is expected.
+    /// - `idle_interval`: The idle time after which the packet is sent.
     ///
     /// # Returns
-    /// A `Result` containing the extracted `u32` value or an `io::Error` if request or parsing fails.
-    pub async fn call_with_u32_u32_resulting_u32(
-        &mut self, 
-        function_name: &str, 
-        arg1: u32, 
-        arg2: u32, 
-        has_return: bool
-    ) -> Result<u32, io::Error> {
-        let result = self.call_with_u32_u32(function_name, arg1, arg2, has_return)
-            .await?;
-        extract_variable_value_from_response_line(&result)
-            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Read format corrupted"))
+    /// An `io::Result<()>` with an error if the packet got no reply.
+    pub async fn keep_alive(&mut self, idle_interval: Duration) -> Result<(), io::Error> {
+        if self.last_request_instant.elapsed() < idle_interval {
+            return Ok(());
+        }
+
+        log::debug!("Link idle for {} ms, sending keep-alive.", self.last_request_instant.elapsed().as_millis());
+        self.make_request("maint packet ?").await?;
+        let (_, replied) = self.await_responses_until(
+            |line| line.starts_with("received:"),
+            self.options.timeouts.read
+        ).await?;
+
+        if !replied {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "Keep-alive packet got no reply"));
+        }
+        Ok(())
     }
 
-    /// Reads a `u32` variable from the target.
+    /// Reads the GDB version banner.
     ///
     /// # Expected Result
-    /// The response should be a single line in the format, for example:
-    /// `$12 = 8228421`
+    /// Several lines, the first one similar to:
+    /// `GNU gdb (Arm GNU Toolchain 13.2.rel1 (Release)) 13.2.90.20231008-git`
     ///
-    /// # Parameters
-    /// - `variable_name`: The name of the variable to read.
+    /// # Returns
+    /// A `Result` containing the banner lines or an `io::Error` if the request fails.
+    pub async fn show_version(&mut self) -> Result<Vec<String>, io::Error> {
+        self.make_request_await_response(
+            "show version",
+            None,
+            self.options.timeouts.read
+        ).await
+    }
+
+    /// Reads a short status of the target: program state and the program counter.
+    ///
+    /// # Expected Result
+    /// Lines similar to:
+    /// `Program stopped at 0x8001234.` and `pc             0x8001234           0x8001234 <main+4>`
     ///
     /// # Returns
-    /// A `Result` containing the parsed `u32` value or an `io::Error` if request or parsing fails.
-    #[allow(unused)]
-    pub async fn read_variable_u32(&mut self, variable_name: &str) -> Result<u32, io::Error> {
-        let response = self.make_request_await_response(
-            format!("print {variable_name}").as_str(), 
-            Some(1),
-            Duration::from_millis(250)
+    /// A `Result` containing the status lines or an `io::Error` if a request fails.
+    pub async fn target_status(&mut self) -> Result<Vec<String>, io::Error> {
+        let mut lines = self.make_request_await_response(
+            "info program",
+            None,
+            self.options.timeouts.read
         ).await?;
 
-        let first_line = response.first().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Read missing result"))?;
-        extract_variable_value_from_response_line(first_line)
-            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Read format corrupted"))
+        lines.extend(self.make_request_await_response(
+            "info registers pc",
+            Some(1),
+            self.options.timeouts.read
+        ).await?);
+
+        Ok(lines)
     }
 
-    /// Sets a breakpoint at the specified function.
+    /// Reads all general purpose registers.
     ///
     /// # Expected Result
-    /// A single response line similar to:
-    /// `Breakpoint 1 at 0x8009bc8: file /path/to/file, line 118.`
-    ///
-    /// # Parameters
-    /// - `function_name`: The function where the breakpoint should be set.
+    /// One line per register, similar to:
+    /// `r0             0x20000400          536871936`
     ///
     /// # Returns
-    /// A `Result` containing the response lines or an `io::Error`.
-    pub async fn break_at(&mut self, function_name: &str) -> Result<Vec<String>, io::Error> {
+    /// A `Result` containing the register lines or an `io::Error` if the request fails.
+    pub async fn info_registers(&mut self) -> Result<Vec<String>, io::Error> {
         self.make_request_await_response(
-            format!("break {function_name}").as_str(), 
-            Some(1),
-            Duration::from_millis(750)
+            "info registers",
+            None,
+            self.options.timeouts.read
         ).await
     }
 
-    /// Instructs the target to sleep for a specified number of milliseconds.
+    /// Prints the innermost frames of the call stack.
     ///
     /// # Expected Result
-    /// A single response line similar to: `"Sleep 250ms"`
+    /// One line per frame, similar to:
+    /// `#0  Loader_Breakpoint () at Core/Src/loader.c:42`
     ///
     /// # Parameters
-    /// - `millis`: The number of milliseconds to sleep.
+    /// - `frames_count`: The maximal number of frames.
     ///
     /// # Returns
-    /// A `Result` containing the response lines or an `io::Error`.
-    pub async fn monitor_sleep(&mut self, millis: u32) -> Result<Vec<String>, io::Error> {
+    /// A `Result` containing the frame lines or an `io::Error` if the request fails.
+    pub async fn backtrace(&mut self, frames_count: usize) -> Result<Vec<String>, io::Error> {
         self.make_request_await_response(
-            format!("monitor sleep {millis}").as_str(), 
-            Some(1),
-            Duration::from_millis(millis as u64 + 250)
+            &format!("backtrace {frames_count}"),
+            None,
+            self.options.timeouts.read
         ).await
     }
 
-    /// Writes a binary file into memory.
+    /// Enables semihosting with the output printed by GDB, so target prints reach the target output handler.
     ///
     /// # Expected Result
-    /// The response should be a single line similar to:
-    /// `Restoring binary file <filepath> binary <ram_buffer_name> into memory (0x200b76a8 to 0x200c76a8)`
-    /// and calculates the byte count from the resulting addresses.
+    /// Lines similar to:
+    /// `semihosting is enabled`
+    ///
+    /// # Returns
+    /// An `io::Result<()>` with an error if a request fails.
+    pub async fn enable_semihosting(&mut self) -> Result<(), io::Error> {
+        for semihosting_cmd in self.options.monitor.semihosting.clone() {
+            self.make_request_await_response(&semihosting_cmd, None, self.options.timeouts.read).await?;
+        }
+        Ok(())
+    }
+
+    /// Collects target output printed while no command is pending, e.g. while the target runs,
+    /// and forwards it to the target output handler.
     ///
     /// # Parameters
-    /// - `ram_buffer_name`: The name of the RAM buffer.
-    /// - `binary_filepath`: The file path of the binary file.
+    /// - `wait`: How long to collect output.
     ///
     /// # Returns
-    /// A `Result` containing the number of bytes written or an `io::Error` if parsing fails.
-    pub async fn write_binary_file_to_mem<P>(&mut self, ram_buffer_name: &str, binary_filepath: P) -> Result<u32, io::Error> 
-    where 
-        P: AsRef<Path>
-    {
-        // 1 line - chars=106, response_out='Restoring binary file C:\WS\gdbloader\tmp_bin_chunks\chunk_0_.bin into memory (0x200b76a8 to 0x200c76a8)',
-        let lines = self.make_request_await_response(
-            format!(
-                "restore {} binary {}", 
-                binary_filepath.as_ref().to_str().unwrap(), 
-                ram_buffer_name
-            ).as_str(),
-            Some(1),
-            Duration::from_millis(5000)
-        ).await?;
-
-        let first_line = lines.first().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Read missing result"))?;
-        let (from_address, to_address) = extract_adresses_from_response_line(first_line)
-            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Corrupted result format"))?;
-        let bytes_count = to_address - from_address;
-        Ok(bytes_count)
+    /// An `io::Result` with the collected lines.
+    #[allow(unused)]
+    pub async fn poll_target_printf(&mut self, wait: Duration) -> Result<Vec<String>, io::Error> {
+        let lines = self.await_responses(None, wait).await?;
+        lines.iter().for_each(|line| self.forward_target_output(line));
+        Ok(lines)
     }
-}
-
-/// Returns a reference to the static regex for hexadecimal addresses.
-fn get_hex_adress_regex() -> &'static Regex {
-    static REGEX_HEX_ADRESSES: OnceLock<Regex> = OnceLock::new();
 
-    REGEX_HEX_ADRESSES.get_or_init(|| {
-        Regex::new(r"0x([0-9a-fA-F]+)").unwrap()
-    })
+    /// Drains pending output until GDB stays silent for `idle_gap`, to resynchronize the stream,
+    /// e.g. after out-of-band commands whose output was not awaited.
+    ///
+    /// Draining also ends at the response limits, or if the output is closed or GDB died, which is
+    /// logged as the next request reports it anyway.
+    ///
+    /// # Parameters
+    /// - `idle_gap`: How long GDB must stay silent for the stream to count as drained.
+    ///
+    /// # Returns
+    /// The discarded lines, so the caller can log them.
+    pub async fn flush_responses(&mut self, idle_gap: Duration) -> Vec<String> {
+        let mut discarded = Vec::new();
+        let mut total_bytes = 0;
+
+        loop {
+            match timeout(idle_gap, self.read_next_line()).await {
+                Ok(Ok(Some(line))) => {
+                    total_bytes += line.len();
+                    discarded.push(line);
+                    if self.options.response_limits.reached(discarded.len(), total_bytes) {
+                        log::warn!("Flushing stopped at response limits after {} lines.", discarded.len());
+                        break;
+                    }
+                },
+                Ok(Ok(None)) => {
+                    log::warn!("Flushing stopped, GDB output closed.");
+                    break;
+                },
+                Ok(Err(e)) => {
+                    log::warn!("Flushing stopped: {e}");
+                    break;
+                },
+                Err(_) => {
+                    log::trace!("Flushing done after {} ms idle.", idle_gap.as_millis());
+                    break;
+                },
+            }
+        }
+
+        discarded
+    }
+
+    /// Sends the "quit" command to GDB and wait until subprocess is finished.
+    ///
+    /// Pending output is drained first. If GDB does not exit within the quit timeout, it is killed.
+    ///
+    /// # Returns
+    /// An `io::Result<()>` indicating whether the command was successfully sent and GDB exited.
+    pub async fn quit_and_wait(&mut self) -> io::Result<()> {
+        // Drain output of earlier commands, so GDB is idle when quitting.
+        let _ = self.await_responses(None, Duration::from_millis(50)).await;
+
+        self.make_request(
+            "quit", 
+        ).await?;
+
+        let status_code = match timeout(self.options.timeouts.quit, self.gdb_subprocess.wait()).await {
+            Ok(status_code) => status_code?,
+            Err(_) => {
+                log::warn!("GDB did not exit within {} ms after quit, killing it.", self.options.timeouts.quit.as_millis());
+                self.gdb_subprocess.start_kill()?;
+                self.gdb_subprocess.wait().await?
+            }
+        };
+
+        if status_code.success() || is_terminated_by_quit(&status_code) {
+            log::info!("Subprocess finished successfully!");
+        } else {
+            log::warn!("Subprocess finished failed: {status_code}!");
+        }
+        Ok(())
+    }
+
+    /// Executes an arbitrary command, e.g. a line of a commands file, and collects its output.
+    ///
+    /// # Expected Result
+    /// Any output, a line matching a known error pattern, e.g. `Undefined command: "foo".`, fails the command.
+    ///
+    /// # Parameters
+    /// - `cmd`: The command to execute.
+    ///
+    /// # Returns
+    /// A `Result` containing the response lines or an `io::Error` if the request fails or GDB reports an error.
+    pub async fn execute(&mut self, cmd: &str) -> Result<Vec<String>, io::Error> {
+        let lines = self.make_request_await_response(cmd, None, self.options.timeouts.read).await?;
+
+        match lines.iter().find(|line| get_error_stderr_regex().is_match(line)) {
+            Some(error_line) => Err(io::Error::other(format!("Command '{cmd}' failed: {error_line}"))),
+            None => Ok(lines),
+        }
+    }
+
+    /// Sends the "help" command to GDB and awaits the response.
+    ///
+    /// # Returns
+    /// A `Result` containing the help text lines or an `io::Error`.
+    #[allow(unused)]
+    pub async fn help(&mut self) -> Result<Vec<String>, io::Error> {
+        self.make_request_await_response(
+            "help", 
+            None,
+            Duration::from_millis(500)
+        ).await
+    }
+
+    /// Sends a monitor command and collects its output from both streams: up to `wait` for the first line,
+    /// then until GDB stays silent for [`MONITOR_IDLE_GAP`], as the number of lines and their stream vary by probe.
+    ///
+    /// # Expected Result
+    /// Any number of lines on stdout and stderr, e.g. for OpenOCD `monitor flash info 0`:
+    /// `#0 : stm32h7x at 0x08000000, size 0x00200000, ...` on stdout, `Info : ...` lines on stderr.
+    ///
+    /// # Parameters
+    /// - `cmd`: The full command, e.g. `monitor reset`.
+    /// - `wait`: The maximum duration to wait for the first line, e.g. a long erase prints only once done.
+    ///
+    /// # Returns
+    /// A `Result` containing the lines tagged by stream in arrival order, or an `io::Error`. If unclassified
+    /// stderr lines are treated as errors, the first such line collected fails the request.
+    pub async fn monitor_request(&mut self, cmd: &str, wait: Duration) -> Result<Vec<MonitorLine>, io::Error> {
+        self.make_request(cmd).await?;
+
+        let mut lines = Vec::new();
+        let mut total_bytes = 0;
+        let mut line_wait = wait;
+        loop {
+            match timeout(line_wait, self.read_next_line()).await {
+                Ok(Ok(Some(text))) => {
+                    total_bytes += text.len();
+                    lines.push(MonitorLine { stream: self.last_line_stream, text });
+                    if self.options.response_limits.reached(lines.len(), total_bytes) {
+                        break;
+                    }
+                    line_wait = MONITOR_IDLE_GAP;
+                },
+                Ok(Ok(None)) => break,
+                Ok(Err(e)) => return Err(e),
+                Err(_) => break,
+            }
+        }
+        log::debug!("Monitor responses: {lines:?}");
+
+        match self.unclassified_stderr_line.take() {
+            Some(line) => Err(io::Error::other(format!("Unexpected GDB stderr after '{cmd}': {line}"))),
+            None => Ok(lines),
+        }
+    }
+
+    /// Sends the probe halt command, by default "monitor halt".
+    ///
+    /// # Expected Result
+    /// Generally, no response is expected after sending this command.
+    /// Output is collected only if the halt timeout is not zero, see [`Gdb::monitor_request`].
+    ///
+    /// # Returns
+    /// A `Result` containing the response lines (usually empty) or an `io::Error`.
+    /// Nothing is sent if the probe has no halt command.
+    pub async fn monitor_halt(&mut self) -> Result<Vec<MonitorLine>, io::Error> {
+        let Some(halt_cmd) = self.options.monitor.halt.clone() else {
+            log::debug!("Probe has no halt command, skipped.");
+            return Ok(vec![]);
+        };
+
+        let halt_timeout = self.options.timeouts.halt;
+        if halt_timeout.is_zero() {
+            self.make_request(&halt_cmd).await?;
+            return Ok(vec![]);
+        }
+        self.monitor_request(&halt_cmd, halt_timeout).await
+    }
+
+    /// Sends the "continue" command to resume execution and waits until the target stops.
+    ///
+    /// # Expected Result
+    /// Several lines may be returned, the last one should be similar to:
+    /// `Breakpoint 1, MX_ThreadX_Init ()`
+    ///
+    /// Other stops are reported as well, for example a triggered watchpoint
+    /// (`Hardware watchpoint 2: result`, `Old value = 0`, `New value = 1`),
+    /// a fault (`Program received signal SIGTRAP, Trace/breakpoint trap.`)
+    /// or an exit (`[Inferior 1 (Remote target) exited normally]`).
+    ///
+    /// The wait is bounded by the continue timeout, slow booting targets may need several seconds.
+    ///
+    /// # Returns
+    /// A `Result` containing the reason of the stop or an `io::Error` of kind `TimedOut`
+    /// if the target did not stop before the deadline.
+    pub async fn continue_execution(&mut self) -> Result<StopReason, io::Error> {
+        self.resume_until_stop("continue", is_stop_line).await
+    }
+
+    /// Reads a core register, e.g. `pc` or `sp`.
+    ///
+    /// # Expected Result
+    /// A single response line similar to:
+    /// `$4 = 0x8000124`
+    ///
+    /// # Parameters
+    /// - `name`: The register name without the `$` prefix.
+    ///
+    /// # Returns
+    /// A `Result` containing the register value or an `io::Error` if the register is unknown or the read fails.
+    pub async fn read_register(&mut self, name: &str) -> Result<u32, io::Error> {
+        self.read_variable_fmt(&format!("${name}"), 'x').await
+    }
+
+    /// Checks that a halted target really stands still by reading the PC twice with a short pause in between.
+    /// Some probes report a halt while the core keeps running, e.g. with a misconfigured clock or power domain,
+    /// and all later reads and writes of it return garbage.
+    ///
+    /// GDB caches registers of a stopped target, the cache is flushed before each read
+    /// with "maintenance flush register-cache", so both values come from the core.
+    ///
+    /// # Returns
+    /// An `io::Result<u32>` with the PC, or an error if it moved between the reads.
+    pub async fn verify_halted(&mut self) -> Result<u32, io::Error> {
+        self.execute("maintenance flush register-cache").await?;
+        let first_pc = self.read_register("pc").await?;
+        tokio::time::sleep(HALT_CHECK_DELAY).await;
+        self.execute("maintenance flush register-cache").await?;
+        let second_pc = self.read_register("pc").await?;
+
+        if first_pc != second_pc {
+            return Err(io::Error::other(format!(
+                "Target did not actually halt, PC moved from {first_pc:#x} to {second_pc:#x} within {} ms",
+                HALT_CHECK_DELAY.as_millis()
+            )));
+        }
+
+        log::info!("Target halted at PC {first_pc:#x}.");
+        Ok(first_pc)
+    }
+
+    /// Sends the "continue &" command resuming the target in the background, GDB accepts further commands
+    /// while it runs. Pair with [`Gdb::wait_for_stop`] to learn when and why it stopped.
+    ///
+    /// # Returns
+    /// A `Result` with an `io::Error` if sending fails.
+    #[allow(unused)]
+    pub async fn continue_in_background(&mut self) -> Result<(), io::Error> {
+        self.make_request("continue &").await
+    }
+
+    /// Waits until the target stops, without sending any command, e.g. after [`Gdb::continue_in_background`]
+    /// or to catch a fault of a freely running target.
+    ///
+    /// # Expected Result
+    /// A CLI stop line such as `Breakpoint 1, MX_ThreadX_Init ()` or
+    /// `Program received signal SIGSEGV, Segmentation fault.`, or an MI record such as
+    /// `*stopped,reason="signal-received",signal-name="SIGSEGV",...`. The MI record is also
+    /// delivered to the async receiver.
+    ///
+    /// A stop reported while other commands were awaiting their responses is not seen anymore.
+    ///
+    /// # Parameters
+    /// - `wait_timeout`: The maximum duration to wait for the stop.
+    ///
+    /// # Returns
+    /// A `Result` containing the reason of the stop or an `io::Error` of kind `TimedOut`
+    /// if the target did not stop before the deadline.
+    #[allow(unused)]
+    pub async fn wait_for_stop(&mut self, wait_timeout: Duration) -> Result<StopReason, io::Error> {
+        self.yield_async_stops = true;
+        let result = self.await_responses_until(|line| is_stop_line(line) || is_mi_stop_line(line), wait_timeout).await;
+        self.yield_async_stops = false;
+
+        let (lines, stopped) = result?;
+        if !stopped {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("Target did not stop within {} ms", wait_timeout.as_millis())
+            ));
+        }
+
+        let stop_reason = extract_stop_reason(&lines);
+        log::info!("Target stopped: {stop_reason}");
+        self.last_stop_reason = Some(stop_reason.clone());
+        Ok(stop_reason)
+    }
+
+    /// Sends the "step" command to execute a single source line and waits until the target stops.
+    ///
+    /// # Expected Result
+    /// Lines ending with the new source line, for example:
+    /// `119   HAL_Delay(10);`
+    ///
+    /// # Returns
+    /// A `Result` containing the reason of the stop or an `io::Error` of kind `TimedOut`.
+    #[allow(unused)]
+    pub async fn step(&mut self) -> Result<StopReason, io::Error> {
+        self.resume_until_stop("step", |line| is_stop_line(line) || get_step_end_regex().is_match(line)).await
+    }
+
+    /// Sends a command resuming the target and waits for a line marking the stop.
+    ///
+    /// # Parameters
+    /// - `cmd`: The resuming command, e.g. `continue` or `step`.
+    /// - `is_final`: Predicate marking the line which completes the stop report.
+    ///
+    /// # Returns
+    /// A `Result` containing the parsed `StopReason` or an `io::Error` of kind `TimedOut`.
+    async fn resume_until_stop(&mut self, cmd: &str, is_final: fn(&str) -> bool) -> Result<StopReason, io::Error> {
+        self.resume_until_stop_with_lines(cmd, is_final).await.map(|(stop_reason, _)| stop_reason)
+    }
+
+    /// Same as [`Gdb::resume_until_stop`], also returning the response lines, e.g. to check how the target was resumed.
+    async fn resume_until_stop_with_lines(&mut self, cmd: &str, is_final: fn(&str) -> bool) -> Result<(StopReason, Vec<String>), io::Error> {
+        let stop_deadline = self.options.timeouts.continue_execution;
+        self.make_request(cmd).await?;
+
+        let (lines, stopped) = self.await_responses_until(is_final, stop_deadline).await?;
+        if !stopped {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("Target did not stop after '{cmd}' within {} ms", stop_deadline.as_millis())
+            ));
+        }
+
+        let stop_reason = extract_stop_reason(&lines);
+        log::info!("Target stopped after '{cmd}': {stop_reason}");
+        self.last_stop_reason = Some(stop_reason.clone());
+        Ok((stop_reason, lines))
+    }
+
+    /// Sets the program counter of the halted target, so the next resume starts at the address,
+    /// e.g. to skip an init routine during bring-up.
+    ///
+    /// # Expected Result
+    /// No response, an error such as `Cannot access memory at address 0x...` fails the command.
+    ///
+    /// # Parameters
+    /// - `address`: The new program counter.
+    ///
+    /// # Returns
+    /// An `io::Result<()>` with an error if GDB rejects the command.
+    #[allow(unused)]
+    pub async fn set_pc(&mut self, address: u32) -> Result<(), io::Error> {
+        self.execute(&format!("set $pc = {address:#x}")).await.map(|_| ())
+    }
+
+    /// Sends the "jump" command resuming the target at a location instead of the current PC and waits until it stops,
+    /// e.g. to enter freshly loaded code without a reset.
+    ///
+    /// # Expected Result
+    /// The line `Continuing at 0x8000124.` followed by the stop lines, as for [`Gdb::continue_execution`].
+    ///
+    /// # Parameters
+    /// - `location`: A GDB location, e.g. `*0x08000124` for an address or a function name.
+    ///
+    /// # Returns
+    /// A `Result` containing the reason of the stop, an `io::Error` if the target was not resumed,
+    /// e.g. for an unknown function, or of kind `TimedOut` if it did not stop before the deadline.
+    #[allow(unused)]
+    pub async fn jump(&mut self, location: &str) -> Result<StopReason, io::Error> {
+        let cmd = format!("jump {location}");
+        let (stop_reason, lines) = self.resume_until_stop_with_lines(&cmd, is_stop_line).await?;
+
+        match lines.iter().find_map(|line| get_continuing_at_regex().captures(line)) {
+            Some(captures) => log::info!("Jumped to {}.", &captures[1]),
+            None => return Err(io::Error::other(format!(
+                "Jump to '{location}' did not resume the target: {}", lines.first().map_or("no response", String::as_str)
+            ))),
+        }
+
+        Ok(stop_reason)
+    }
+
+    /// Sends the probe reset halt command, by default "monitor reset halt", so the core stays at
+    /// the reset vector and no user code runs until the target is continued.
+    ///
+    /// # Expected Result
+    /// A stderr response line, for example:
+    /// `target halted due to debug-request, current mode: Thread`
+    ///
+    /// # Returns
+    /// A `Result` containing the response lines of both streams, see [`Gdb::monitor_request`],
+    /// or an `io::Error` of kind `Unsupported` if the probe has no reset halt command.
+    pub async fn monitor_reset_halt(&mut self) -> Result<Vec<MonitorLine>, io::Error> {
+        let Some(reset_halt_cmd) = self.options.monitor.reset_halt.clone() else {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "Probe has no reset halt command"));
+        };
+
+        self.monitor_request(&reset_halt_cmd, self.options.timeouts.reset).await
+    }
+
+    /// Sends the probe reset command, by default "monitor reset", to reset the target.
+    ///
+    /// # Expected Result
+    /// Usually a single stderr response line, for example:
+    /// `Resetting target`
+    ///
+    /// # Returns
+    /// A `Result` containing the response lines of both streams, see [`Gdb::monitor_request`], or an `io::Error`.
+    pub async fn monitor_reset(&mut self) -> Result<Vec<MonitorLine>, io::Error> {
+        let reset_cmd = self.options.monitor.reset.clone();
+        self.monitor_request(&reset_cmd, self.options.timeouts.reset).await
+    }
+
+    /// Generic helper to call a function on the target.
+    ///
+    /// # Parameters
+    /// - `function`: The function call string (e.g., `"foo()"` or `"bar(42)"`).
+    /// - `has_return`: Indicates if a return value is expected.
+    ///
+    /// # Returns
+    /// On success, returns the output of the function call as a `String` (empty if no return is expected).
+    ///
+    /// # Note
+    /// Semihosting output of the called function is printed by GDB before the value,
+    /// such lines are forwarded as target output instead of being taken as the result.
+    async fn call_generic(&mut self, function: &str, has_return: bool) -> Result<String, io::Error> {
+        self.call_target_output.clear();
+        let call_deadline = Instant::now() + self.options.timeouts.call;
+        let cmd = format!("call {function}");
+
+        let mut results = match has_return {
+            true => self.make_request_await_response(&cmd, Some(1), self.options.timeouts.call).await?,
+            false => {
+                // A void call prints nothing on success, the echoed marker tells when it returned.
+                self.make_request(&cmd).await?;
+                self.make_request(&format!("echo {CALL_DONE_MARKER}\\n")).await?;
+                let (mut lines, done) = self.await_responses_until(|line| line.trim() == CALL_DONE_MARKER, self.options.timeouts.call).await?;
+                match done {
+                    true => { lines.pop(); },
+                    false => log::debug!("'{cmd}' did not return within {} ms.", self.options.timeouts.call.as_millis()),
+                }
+                lines
+            },
+        };
+
+        if has_return {
+            while let Some(line) = results.first().filter(|line| is_target_output_line(line)).cloned() {
+                self.collect_call_target_output(&line);
+                results = self.await_responses(Some(1), call_deadline.saturating_duration_since(Instant::now())).await?;
+            }
+        } else {
+            // Anything but GDB messages comes from the target.
+            let (target_lines, gdb_lines): (Vec<String>, Vec<String>) = results.into_iter()
+                .partition(|line| is_target_output_line(line));
+            target_lines.iter().for_each(|line| self.collect_call_target_output(line));
+            results = gdb_lines;
+        }
+
+        extract_call_result(results, has_return)
+    }
+
+    /// Calls a function on the target with any number of arguments and parses the returned value.
+    ///
+    /// # Expected Result
+    /// - If the function returns a value, a line such as `$1 = 0`, `$2 = 0x1234` or `$3 = 118 'v'`.
+    /// - If the function returns void, no line.
+    ///
+    /// # Parameters
+    /// - `function_name`: The name of the function to call.
+    /// - `args`: The arguments.
+    /// - `has_return`: Whether a return value is expected.
+    ///
+    /// # Returns
+    /// A `Result` containing the returned value in the representation GDB printed it in,
+    /// [`TargetFunctionResult::Void`] if no value is expected, or an `io::Error`.
+    pub async fn call_typed(&mut self, function_name: &str, args: &[CallArg], has_return: bool) -> Result<TargetFunctionResult, io::Error> {
+        let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().join(", ");
+        let result = self.call_generic(format!("{function_name}({args})").as_str(), has_return).await?;
+        Ok(extract_target_function_result(&result, has_return))
+    }
+
+    /// Calls a function on the target with no arguments.
+    ///
+    /// # Expected Result
+    /// - If the function returns a value, the output might be something like:
+    ///   `$23 = 118 'v'`
+    /// - If the function returns void, an empty string is returned.
+    ///
+    /// # Parameters
+    /// - `function_name`: The name of the function to call.
+    /// - `has_return`: Whether a return value is expected.
+    ///
+    /// # Returns
+    /// A `Result` containing the function output or an `io::Error`.
+    pub async fn call(&mut self, function_name: &str, has_return: bool) -> Result<String, io::Error> {
+        self.call_generic(format!("{function_name}()").as_str(), has_return).await
+    }
+
+    /// Calls a function on the target with no arguments and extracts a `u32` return value.
+    ///
+    /// # Expected Result
+    /// For example, if the function output is `$4 = 4096`, this method extracts and returns `4096`.
+    ///
+    /// # Parameters
+    /// - `function_name`: The name of the function to call.
+    ///
+    /// # Returns
+    /// A `Result` containing the extracted `u32` value or an `io::Error` if request or parsing fails.
+    pub async fn call_resulting_u32(&mut self, function_name: &str) -> Result<u32, io::Error> {
+        let result = self.call_typed(function_name, &[], true).await?;
+        result_as_u32(&result)
+    }
+
+    /// Calls a function on the target with one `u32` argument.
+    ///
+    /// # Expected Result
+    /// Works similarly to [`Gdb::call`], returning the function's output if a return value is expected.
+    ///
+    /// # Parameters
+    /// - `function_name`: The name of the function to call.
+    /// - `arg`: The `u32` argument.
+    /// - `has_return`: Whether a return value is expected.
+    ///
+    /// # Returns
+    /// A `Result` containing the function output or an `io::Error`.
+    #[allow(unused)]
+    pub async fn call_with_u32(
+        &mut self, 
+        function_name: &str, 
+        arg: u32, 
+        has_return: bool
+    ) -> Result<String, io::Error> {
+        self.call_generic(format!("{function_name}({arg})").as_str(), has_return).await
+    }
+
+    /// Calls a function on the target with two `u32` arguments.
+    ///
+    /// # Expected Result
+    /// Works similarly to [`Gdb::call`], returning the function's output if a return value is expected.
+    ///
+    /// # Parameters
+    /// - `function_name`: The name of the function to call.
+    /// - `arg1`: The first `u32` argument.
+    /// - `arg2`: The second `u32` argument.
+    /// - `has_return`: Whether a return value is expected.
+    ///
+    /// # Returns
+    /// A `Result` containing the function output or an `io::Error`.
+    #[allow(unused)]
+    pub async fn call_with_u32_u32(
+        &mut self, function_name: &str, 
+        arg1: u32, 
+        arg2: u32, 
+        has_return: bool
+    ) -> Result<String, io::Error> {
+        self.call_generic(format!("{function_name}({arg1}, {arg2})").as_str(), has_return).await
+    }
+
+    /// Calls a function on the target with two `u32` arguments and extracts a `u32` return value.
+    ///
+    /// # Expected Result
+    /// For example, if the function output is `$23 = 118 'v'`, this method extracts and returns `118`.
+    ///
+    /// # Parameters
+    /// - `function_name`: The name of the function to call.
+    /// - `arg1`: The first `u32` argument.
+    /// - `arg2`: The second `u32` argument.
+    /// - `has_return`: Whether a return value is expected.
+    ///
+    /// # Returns
+    /// A `Result` containing the extracted `u32` value or an `io::Error` if request or parsing fails.
+    pub async fn call_with_u32_u32_resulting_u32(
+        &mut self, 
+        function_name: &str, 
+        arg1: u32, 
+        arg2: u32, 
+        has_return: bool
+    ) -> Result<u32, io::Error> {
+        let result = self.call_typed(function_name, &[CallArg::U32(arg1), CallArg::U32(arg2)], has_return)
+            .await?;
+        result_as_u32(&result)
+    }
+
+    /// Calls a function on the target with any number of arguments and extracts a `u32` return value.
+    ///
+    /// # Expected Result
+    /// Same as [`Gdb::call_with_u32_u32_resulting_u32`].
+    ///
+    /// # Parameters
+    /// - `function_name`: The name of the function to call.
+    /// - `args`: The arguments.
+    ///
+    /// # Returns
+    /// A `Result` containing the extracted `u32` value or an `io::Error` if request or parsing fails.
+    pub async fn call_with_args_resulting_u32(&mut self, function_name: &str, args: &[CallArg]) -> Result<u32, io::Error> {
+        let result = self.call_typed(function_name, args, true).await?;
+        result_as_u32(&result)
+    }
+
+    /// Calls a function on the target by its address, for firmwares without symbols.
+    ///
+    /// The address is cast to a function pointer described by `signature`,
+    /// e.g. `unsigned int (unsigned int, unsigned int)` produces the command
+    /// `call ((unsigned int (*)(unsigned int, unsigned int))0x8001235)(arg1, arg2)`.
+    ///
+    /// # Expected Result
+    /// Works similarly to [`Gdb::call`], a value is expected unless the return type is `void`.
+    ///
+    /// # Parameters
+    /// - `address`: The function address, GDB sets the Thumb bit on Cortex-M itself.
+    /// - `signature`: The function type as `return_type (argument_types)`.
+    /// - `args`: The arguments.
+    ///
+    /// # Returns
+    /// A `Result` containing the function output or an `io::Error` if the signature is malformed or the call fails.
+    pub async fn call_at_address(&mut self, address: u32, signature: &str, args: &[CallArg]) -> Result<String, io::Error> {
+        let (return_type, argument_types) = signature.split_once('(')
+            .map(|(return_type, argument_types)| (return_type.trim(), argument_types.trim()))
+            .filter(|(return_type, argument_types)| !return_type.is_empty() && argument_types.ends_with(')'))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Malformed function signature '{signature}'")))?;
+
+        let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().join(", ");
+        self.call_generic(
+            format!("(({return_type} (*)({argument_types}){address:#x})({args})").as_str(),
+            return_type != "void"
+        ).await
+    }
+
+    /// Calls a function on the target by its address and extracts a `u32` return value.
+    ///
+    /// # Expected Result
+    /// Same as [`Gdb::call_with_u32_u32_resulting_u32`].
+    ///
+    /// # Parameters
+    /// - `address`: The function address, GDB sets the Thumb bit on Cortex-M itself.
+    /// - `signature`: The function type as `return_type (argument_types)`.
+    /// - `args`: The arguments.
+    ///
+    /// # Returns
+    /// A `Result` containing the extracted `u32` value or an `io::Error` if request or parsing fails.
+    pub async fn call_at_address_resulting_u32(&mut self, address: u32, signature: &str, args: &[CallArg]) -> Result<u32, io::Error> {
+        let result = self.call_at_address(address, signature, args).await?;
+        result_as_u32(&extract_target_function_result(&result, true))
+    }
+
+    /// Reads a `u32` variable from the target.
+    ///
+    /// # Expected Result
+    /// The response should be a single line in the format, for example:
+    /// `$12 = 8228421`
+    ///
+    /// # Parameters
+    /// - `variable_name`: The name of the variable to read.
+    ///
+    /// # Returns
+    /// A `Result` containing the parsed `u32` value or an `io::Error` if request or parsing fails.
+    pub async fn read_variable_u32(&mut self, variable_name: &str) -> Result<u32, io::Error> {
+        let response = self.make_request_await_response(
+            format!("print {variable_name}").as_str(), 
+            Some(1),
+            self.options.timeouts.read
+        ).await?;
+
+        let first_line = response.first().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Read missing result"))?;
+        extract_variable_value_from_response_line(first_line)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Read format corrupted"))
+    }
+
+    /// Reads a `u32` variable from the target printed in the given format, e.g. `print/x flags`.
+    ///
+    /// # Expected Result
+    /// A single response line, depending on the format, for example:
+    /// - `x`: `$3 = 0x1f`
+    /// - `d` and `u`: `$3 = 31`
+    /// - `o`: `$3 = 037`
+    /// - `t`: `$3 = 11111`
+    /// - `c`: `$3 = 31 '\037'`
+    ///
+    /// # Parameters
+    /// - `variable_name`: The name of the variable to read.
+    /// - `fmt`: The print format, one of `x`, `d`, `u`, `o`, `t` and `c`.
+    ///
+    /// # Returns
+    /// A `Result` containing the parsed `u32` value, negative values of `d` reinterpreted as two's complement,
+    /// or an `io::Error` if the format is unsupported, the request fails or the value cannot be parsed.
+    #[allow(unused)]
+    pub async fn read_variable_fmt(&mut self, variable_name: &str, fmt: char) -> Result<u32, io::Error> {
+        if !matches!(fmt, 'x' | 'd' | 'u' | 'o' | 't' | 'c') {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unsupported print format '{fmt}'")));
+        }
+
+        let response = self.make_request_await_response(
+            format!("print/{fmt} {variable_name}").as_str(),
+            Some(1),
+            self.options.timeouts.read
+        ).await?;
+
+        let first_line = response.first().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Read missing result"))?;
+        get_value_history_regex().captures(first_line)
+            .and_then(|captures| captures.get(1))
+            .and_then(|value_text| extract_formatted_u32(value_text.as_str(), fmt))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Read of '{variable_name}' failed: {first_line}")))
+    }
+
+    /// Reads the value of an expression of any type, e.g. a structure, an array or a float.
+    ///
+    /// # Expected Result
+    /// A single response line similar to:
+    /// `$1 = {a = 1, b = {2, 3}}`
+    ///
+    /// # Parameters
+    /// - `expression`: The expression to evaluate.
+    ///
+    /// # Returns
+    /// A `Result` containing the parsed value or an `io::Error` if the request fails or GDB prints no value.
+    #[allow(unused)]
+    pub async fn read_value(&mut self, expression: &str) -> Result<GdbValue, io::Error> {
+        let value_text = self.print_expression(expression).await?;
+        Ok(GdbValue::parse(&value_text))
+    }
+
+    /// Evaluates an expression and returns the printed value as is, e.g. for `buf[0]@16` or `*(struct foo*)0x20000000`.
+    ///
+    /// # Expected Result
+    /// A single response line similar to:
+    /// `$1 = "abc", '\000' <repeats 12 times>`
+    ///
+    /// # Parameters
+    /// - `expression`: The expression to evaluate.
+    ///
+    /// # Returns
+    /// A `Result` containing the text after `$N = `, [`GdbError::SymbolNotFound`] for an unknown symbol
+    /// or [`GdbError::InvalidExpression`] if GDB rejects the expression.
+    pub async fn print_expression(&mut self, expression: &str) -> Result<String, io::Error> {
+        let response = self.make_request_await_response(
+            format!("print {expression}").as_str(),
+            Some(1),
+            self.options.timeouts.read
+        ).await?;
+
+        let first_line = response.first().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Read missing result"))?;
+        match get_value_history_regex().captures(first_line).and_then(|captures| captures.get(1)) {
+            Some(value_text) => Ok(value_text.as_str().to_string()),
+            None => Err(GdbError::InvalidExpression(format!("'{expression}': {first_line}")).into()),
+        }
+    }
+
+    /// Checks if GDB knows a symbol, from the ELF or an added symbol file.
+    ///
+    /// # Expected Result
+    /// A single response line similar to:
+    /// `Symbol "fw_version" is static storage at address 0x8001000.`
+    /// or `No symbol "fw_version" in current context.` if it is missing.
+    ///
+    /// # Parameters
+    /// - `name`: The symbol name.
+    ///
+    /// # Returns
+    /// An `io::Result<bool>`, `false` if the symbol is missing.
+    pub async fn symbol_exists(&mut self, name: &str) -> Result<bool, io::Error> {
+        match self.make_request_await_response(format!("info address {name}").as_str(), Some(1), self.options.timeouts.read).await {
+            Ok(response) => Ok(response.first().is_some_and(|line| line.starts_with("Symbol "))),
+            Err(e) if matches!(as_gdb_error(&e), Some(GdbError::SymbolNotFound(_))) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Checks a batch of symbols, see [`Gdb::symbol_exists`].
+    ///
+    /// # Parameters
+    /// - `names`: The symbol names.
+    ///
+    /// # Returns
+    /// An `io::Result` with the missing names in the given order, empty if all exist.
+    pub async fn missing_symbols(&mut self, names: &[String]) -> Result<Vec<String>, io::Error> {
+        let mut missing = Vec::new();
+        for name in names {
+            if !self.symbol_exists(name).await? {
+                missing.push(name.clone());
+            }
+        }
+        Ok(missing)
+    }
+
+    /// Reads a block of target memory, with `dump binary memory` into a temporary file or with the `x` examine command.
+    ///
+    /// The dump is tried first, if GDB does not support it, e.g. for a minimal remote stub, the read falls back
+    /// to examine commands and the method is kept for all later reads of the session. Other dump errors are returned.
+    ///
+    /// # Expected Result
+    /// No output of the dump, examine lines with up to 8 bytes each, similar to:
+    /// `0x8001000 <build_id>: 0x12 0x34 0x56 0x78 0x9a 0xbc 0xde 0xf0`
+    ///
+    /// # Parameters
+    /// - `address`: The start address of the block.
+    /// - `length`: The number of bytes to read.
+    ///
+    /// # Returns
+    /// A `Result` containing the read bytes or an `io::Error` if request or parsing fails.
+    pub async fn read_memory(&mut self, address: u32, length: usize) -> Result<Vec<u8>, io::Error> {
+        self.read_memory_at(&format!("{address:#x}"), length).await
+    }
+
+    /// Reads a block of target memory starting at an address expression, e.g. `&loader_ram_buffer`.
+    ///
+    /// # Expected Result
+    /// Same as [`Gdb::read_memory`].
+    ///
+    /// # Parameters
+    /// - `location`: The expression evaluating to the start address of the block.
+    /// - `length`: The number of bytes to read.
+    ///
+    /// # Returns
+    /// A `Result` containing the read bytes or an `io::Error` if request or parsing fails.
+    pub async fn read_memory_at(&mut self, location: &str, length: usize) -> Result<Vec<u8>, io::Error> {
+        if length == 0 {
+            return Ok(vec![]);
+        }
+
+        match self.memory_read_method {
+            Some(MemoryReadMethod::DumpBinary) => self.dump_memory(location, length).await,
+            Some(MemoryReadMethod::Examine) => self.examine_memory(location, length).await,
+            None => match self.dump_memory(location, length).await {
+                Ok(bytes) => {
+                    self.memory_read_method = Some(MemoryReadMethod::DumpBinary);
+                    Ok(bytes)
+                },
+                // Only a missing command decides the method, other errors may be specific to this read.
+                Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+                    log::warn!("Reading memory with 'dump binary memory' failed, falling back to 'x' for this session: {e}");
+                    self.memory_read_method = Some(MemoryReadMethod::Examine);
+                    self.examine_memory(location, length).await
+                },
+                Err(e) => Err(e),
+            },
+        }
+    }
+
+    /// Reads a block of target memory with `dump binary memory` into a temporary file.
+    ///
+    /// # Expected Result
+    /// No output on success, followed by the [`DUMP_DONE_MARKER`] line of the `echo` sent after the dump.
+    async fn dump_memory(&mut self, location: &str, length: usize) -> Result<Vec<u8>, io::Error> {
+        let dump_path = std::env::temp_dir().join(format!("gdbloader_dump_{}.bin", std::process::id()));
+        let dump_path_text = dump_path.to_str()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Dump path {dump_path:?} is not valid UTF-8")))?;
+        let start = format!("(unsigned char *)({location})");
+        let cmd = format!("dump binary memory {dump_path_text} {start} {start} + {length}");
+        let read_timeout = self.options.timeouts.read + Duration::from_millis(10 * length.div_ceil(8) as u64);
+
+        self.make_request(&cmd).await?;
+        self.make_request(&format!("echo {DUMP_DONE_MARKER}\\n")).await?;
+        let (lines, done) = self.await_responses_until(|line| line.trim() == DUMP_DONE_MARKER, read_timeout).await?;
+
+        let error_line = lines.iter().find(|line| get_error_stderr_regex().is_match(line)).cloned()
+            .or_else(|| self.unclassified_stderr_line.take());
+        let result = match error_line {
+            Some(error_line) if get_unsupported_command_regex().is_match(&error_line) => {
+                Err(io::Error::new(io::ErrorKind::Unsupported, format!("'{cmd}' is not supported: {error_line}")))
+            },
+            Some(error_line) => Err(io::Error::other(format!("'{cmd}' failed: {error_line}"))),
+            None if !done => Err(io::Error::new(io::ErrorKind::TimedOut, format!("'{cmd}' did not finish within {} ms", read_timeout.as_millis()))),
+            None => tokio::fs::read(&dump_path).await,
+        };
+        let _ = tokio::fs::remove_file(&dump_path).await;
+
+        let bytes = result?;
+        if bytes.len() != length {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Memory dump returned {} B, expected {length} B", bytes.len())
+            ));
+        }
+
+        Ok(bytes)
+    }
+
+    /// Reads a block of target memory with `x/Nxb` commands of up to [`EXAMINE_READ_MAX`] bytes each.
+    ///
+    /// # Expected Result
+    /// Lines with up to 8 bytes each, similar to:
+    /// `0x8001000 <build_id>: 0x12 0x34 0x56 0x78 0x9a 0xbc 0xde 0xf0`
+    async fn examine_memory(&mut self, location: &str, length: usize) -> Result<Vec<u8>, io::Error> {
+        let mut bytes = Vec::with_capacity(length);
+
+        while bytes.len() < length {
+            let read_length = (length - bytes.len()).min(EXAMINE_READ_MAX);
+            let read_location = match bytes.len() {
+                0 => location.to_string(),
+                done_bytes => format!("(unsigned char *)({location}) + {done_bytes}"),
+            };
+
+            let lines_count = read_length.div_ceil(8);
+            let read_timeout = self.options.timeouts.read + Duration::from_millis(10 * lines_count as u64);
+            let lines = self.make_request_await_response(
+                format!("x/{read_length}xb {read_location}").as_str(),
+                Some(lines_count),
+                read_timeout
+            ).await?;
+
+            let read_bytes = lines.iter()
+                .map(|line| extract_memory_bytes_from_response_line(line))
+                .collect::<Option<Vec<Vec<u8>>>>()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Memory read format corrupted"))?
+                .concat();
+
+            if read_bytes.len() != read_length {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Memory read returned {} B, expected {read_length} B", read_bytes.len())
+                ));
+            }
+            bytes.extend(read_bytes);
+        }
+
+        Ok(bytes)
+    }
+
+    /// Disassembles instructions with their raw bytes.
+    ///
+    /// # Expected Result
+    /// Lines between `Dump of assembler code ...` and `End of assembler dump.`, similar to:
+    /// `=> 0x08000100 <main+0>:\t80 b5\tpush\t{r7, lr}`
+    ///
+    /// # Parameters
+    /// - `address`: The start address, the current PC if `None`.
+    /// - `count`: The number of instructions to return.
+    ///
+    /// # Returns
+    /// A `Result` containing up to `count` instructions or an `io::Error` if the request fails.
+    #[allow(unused)]
+    pub async fn disassemble(&mut self, address: Option<u32>, count: usize) -> Result<Vec<Instruction>, io::Error> {
+        let start = address.map(|address| format!("{address:#x}")).unwrap_or(String::from("$pc"));
+        // Thumb instructions take at most 4 bytes each.
+        let cmd = format!("disassemble /r {start},+{}", count * 4);
+
+        self.make_request(&cmd).await?;
+        let read_timeout = self.options.timeouts.read + Duration::from_millis(10 * count as u64);
+        let (lines, finished) = self.await_responses_until(
+            |line| line.starts_with("End of assembler dump"),
+            read_timeout
+        ).await?;
+
+        if !finished {
+            let reason = lines.last().cloned().unwrap_or(String::from("no output"));
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Disassemble failed: {reason}")));
+        }
+
+        Ok(lines.iter()
+            .filter_map(|line| extract_instruction_from_response_line(line))
+            .take(count)
+            .collect())
+    }
+
+    /// Sets a breakpoint at the specified function.
+    ///
+    /// # Expected Result
+    /// A single response line similar to:
+    /// `Breakpoint 1 at 0x8009bc8: file /path/to/file, line 118.`
+    ///
+    /// # Parameters
+    /// - `function_name`: The function where the breakpoint should be set.
+    ///
+    /// # Returns
+    /// A `Result` containing the response lines or an `io::Error`.
+    pub async fn break_at(&mut self, function_name: &str) -> Result<Vec<String>, io::Error> {
+        self.make_request_await_response(
+            format!("break {function_name}").as_str(), 
+            Some(1),
+            self.options.timeouts.breakpoint
+        ).await
+    }
+
+    /// Sets a breakpoint at the specified function and resolves all its locations.
+    ///
+    /// # Expected Result
+    /// A single response line similar to:
+    /// `Breakpoint 1 at 0x8009bc8: file /path/to/file, line 118.`
+    ///
+    /// A function inlined or instantiated several times gets a breakpoint with several locations:
+    /// `Breakpoint 1 at 0x8000130: foo. (3 locations)`
+    /// which are then listed with `info breakpoints 1`:
+    /// ```text
+    /// Num     Type           Disp Enb Address    What
+    /// 1       breakpoint     keep y   <MULTIPLE>
+    /// 1.1                         y   0x08000130 in foo at Core/Src/foo.h:10
+    /// 1.2                         y   0x08000150 in foo at Core/Src/foo.h:10
+    /// 1.3                         y   0x08000172 in foo at Core/Src/foo.h:10
+    /// ```
+    ///
+    /// # Parameters
+    /// - `function_name`: The function where the breakpoint should be set.
+    /// - `locations`: The locations kept enabled, the other ones are disabled with `disable <bp>.<idx>`.
+    ///
+    /// # Returns
+    /// A `Result` containing the breakpoint with all its locations, or an `io::Error` if the breakpoint
+    /// was not set or the requested location does not exist.
+    pub async fn set_breakpoint(&mut self, function_name: &str, locations: BreakpointLocations) -> Result<Breakpoint, io::Error> {
+        let lines = self.break_at(function_name).await?;
+        let (number, address, locations_count) = lines.iter()
+            .find_map(|line| extract_breakpoint_from_response_line(line))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Breakpoint at '{function_name}' not set: {lines:?}")))?;
+
+        let mut breakpoint = Breakpoint { number, locations: vec![] };
+        if locations_count == 1 {
+            breakpoint.locations.extend(address.map(|address| BreakpointLocation { index: 1, address, enabled: true }));
+            return Ok(breakpoint);
+        }
+
+        let lines = self.make_request_await_response(
+            format!("info breakpoints {number}").as_str(),
+            None,
+            self.options.timeouts.breakpoint
+        ).await?;
+        breakpoint.locations = lines.iter()
+            .filter_map(|line| extract_breakpoint_location_from_response_line(line, number))
+            .collect();
+        log::info!("Breakpoint {number} at '{function_name}' has {locations_count} locations: {:?}", breakpoint.locations);
+
+        if let BreakpointLocations::Only(kept_index) = locations {
+            if !breakpoint.locations.iter().any(|location| location.index == kept_index) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Breakpoint {number} at '{function_name}' has no location {kept_index}, only {locations_count}")
+                ));
+            }
+
+            for location in breakpoint.locations.iter_mut().filter(|location| location.index != kept_index) {
+                self.make_request_await_response(
+                    format!("disable {number}.{}", location.index).as_str(),
+                    Some(0),
+                    self.options.timeouts.breakpoint
+                ).await?;
+                location.enabled = false;
+            }
+        }
+
+        Ok(breakpoint)
+    }
+
+    /// Flashes the ELF into the target with `load`.
+    ///
+    /// Breakpoints must be set after the load, a breakpoint set before it may point into overwritten flash,
+    /// see [`Gdb::refresh_breakpoints`].
+    ///
+    /// # Expected Result
+    /// Lines similar to:
+    /// ```text
+    /// Loading section .isr_vector, size 0x1ac lma 0x8000000
+    /// Loading section .text, size 0x5f3c lma 0x80001b0
+    /// Start address 0x08000b5c, load size 25620
+    /// Transfer rate: 17 KB/sec, 1423 bytes/write.
+    /// ```
+    ///
+    /// # Returns
+    /// A `Result` containing the response lines or an `io::Error` if the load failed or timed out.
+    pub async fn load(&mut self) -> Result<Vec<String>, io::Error> {
+        let load_timeout = self.options.timeouts.load;
+        self.make_request("load").await?;
+
+        let (lines, finished) = self.await_responses_until(
+            |line| line.starts_with("Transfer rate:") || get_error_stderr_regex().is_match(line),
+            load_timeout
+        ).await?;
+
+        match lines.last() {
+            Some(line) if finished && line.starts_with("Transfer rate:") => {
+                log::info!("ELF loaded: {line}");
+                Ok(lines)
+            },
+            Some(line) if finished => Err(io::Error::other(format!("Load failed: {line}"))),
+            _ => Err(io::Error::new(io::ErrorKind::TimedOut, format!("Load not finished within {} ms", load_timeout.as_millis()))),
+        }
+    }
+
+    /// Re-resolves the source lines of all breakpoints and sets the stale ones again, e.g. after `load`
+    /// of a rebuilt ELF left them pointing into overwritten code. A stale breakpoint is deleted and
+    /// set again at its function, so it gets a new number.
+    ///
+    /// # Expected Result
+    /// The breakpoint table of `info breakpoints`, see [`Gdb::set_breakpoint`], and for every
+    /// location the line of `info line <file>:<line>` similar to:
+    /// `Line 42 of "Core/Src/loader.c" starts at address 0x8009bc8 <Loader_Breakpoint+4> and ends at 0x8009bcc.`
+    ///
+    /// # Returns
+    /// A `Result` containing the stale breakpoints, each also logged as a warning, or an `io::Error`.
+    pub async fn refresh_breakpoints(&mut self) -> Result<Vec<StaleBreakpoint>, io::Error> {
+        let lines = self.make_request_await_response("info breakpoints", None, self.options.timeouts.breakpoint).await?;
+        let locations = lines.iter()
+            .filter_map(|line| extract_breakpoint_source_from_response_line(line))
+            .collect::<Vec<_>>();
+
+        let mut stale_breakpoints: Vec<StaleBreakpoint> = Vec::new();
+        for (number, address, function, source_line) in locations {
+            let lines = self.make_request_await_response(
+                format!("info line {source_line}").as_str(),
+                Some(1),
+                self.options.timeouts.breakpoint
+            ).await?;
+            let Some(resolved_address) = lines.iter().find_map(|line| extract_line_address_from_response_line(line)) else {
+                log::warn!("Source line {source_line} of breakpoint {number} not resolved: {lines:?}");
+                continue;
+            };
+
+            if resolved_address != address && !stale_breakpoints.iter().any(|stale| stale.number == number) {
+                log::warn!("Breakpoint {number} in '{function}' at {address:#x} is stale, {source_line} is at {resolved_address:#x} now.");
+                stale_breakpoints.push(StaleBreakpoint { number, function, address, resolved_address });
+            }
+        }
+
+        for stale_breakpoint in &stale_breakpoints {
+            self.make_request_await_response(
+                format!("delete {}", stale_breakpoint.number).as_str(),
+                Some(0),
+                self.options.timeouts.breakpoint
+            ).await?;
+            let breakpoint = self.set_breakpoint(&stale_breakpoint.function, BreakpointLocations::All).await?;
+            log::info!("Breakpoint {} in '{}' set again as {}.", stale_breakpoint.number, stale_breakpoint.function, breakpoint.number);
+        }
+
+        Ok(stale_breakpoints)
+    }
+
+    /// Sets a breakpoint at a raw code address, useful for targets without symbols.
+    ///
+    /// # Expected Result
+    /// A single response line similar to:
+    /// `Breakpoint 1 at 0x8009bc8`
+    ///
+    /// # Parameters
+    /// - `address`: The code address where the breakpoint should be set.
+    ///
+    /// # Returns
+    /// A `Result` containing the response lines or an `io::Error`.
+    pub async fn break_at_address(&mut self, address: u32) -> Result<Vec<String>, io::Error> {
+        self.make_request_await_response(
+            format!("break *{address:#x}").as_str(), 
+            Some(1),
+            self.options.timeouts.breakpoint
+        ).await
+    }
+
+    /// Sets a temporary breakpoint at a raw code address, GDB deletes it once it is hit.
+    ///
+    /// # Expected Result
+    /// A single response line similar to:
+    /// `Temporary breakpoint 1 at 0x80001c4: file startup_stm32h7.s, line 60.`
+    ///
+    /// # Parameters
+    /// - `address`: The code address where the breakpoint should be set.
+    ///
+    /// # Returns
+    /// A `Result` containing the response lines or an `io::Error`.
+    pub async fn tbreak_at_address(&mut self, address: u32) -> Result<Vec<String>, io::Error> {
+        self.make_request_await_response(
+            format!("tbreak *{address:#x}").as_str(),
+            Some(1),
+            self.options.timeouts.breakpoint
+        ).await
+    }
+
+    /// Skips the next crossings of a breakpoint, so the target stops on a later hit.
+    ///
+    /// # Expected Result
+    /// A single response line similar to:
+    /// `Will ignore next 2 crossings of breakpoint 1.`
+    ///
+    /// # Parameters
+    /// - `number`: The breakpoint number, see [`extract_breakpoint_number`].
+    /// - `count`: The number of hits to skip, `0` stops on the next hit again.
+    ///
+    /// # Returns
+    /// A `Result` containing the response lines or an `io::Error`.
+    pub async fn ignore_breakpoint(&mut self, number: u32, count: u32) -> Result<Vec<String>, io::Error> {
+        self.make_request_await_response(
+            format!("ignore {number} {count}").as_str(),
+            Some(1),
+            self.options.timeouts.breakpoint
+        ).await
+    }
+
+    /// Reads how many times a breakpoint was hit so far, skipped hits included.
+    ///
+    /// # Expected Result
+    /// A breakpoint table, the hit count line is missing if it was not hit yet:
+    /// ```text
+    /// Num     Type           Disp Enb Address    What
+    /// 1       breakpoint     keep y   0x08009bc8 in Loader_Breakpoint at Core/Src/loader.c:42
+    ///         breakpoint already hit 3 times
+    /// ```
+    ///
+    /// # Parameters
+    /// - `number`: The breakpoint number.
+    ///
+    /// # Returns
+    /// A `Result` containing the hit count or an `io::Error`.
+    pub async fn breakpoint_hit_count(&mut self, number: u32) -> Result<u32, io::Error> {
+        let lines = self.make_request_await_response(
+            format!("info breakpoints {number}").as_str(),
+            None,
+            self.options.timeouts.breakpoint
+        ).await?;
+
+        Ok(lines.iter()
+            .find_map(|line| extract_hit_count_from_response_line(line))
+            .unwrap_or(0))
+    }
+
+    /// Sets a watchpoint on an expression, typically a variable name.
+    ///
+    /// # Expected Result
+    /// A single response line similar to:
+    /// `Hardware watchpoint 2: result`, `Hardware read watchpoint 3: result`
+    /// or `Hardware access (read/write) watchpoint 4: result`.
+    ///
+    /// # Parameters
+    /// - `kind`: The kind of access to watch.
+    /// - `expression`: The watched expression.
+    ///
+    /// # Returns
+    /// A `Result` containing the watchpoint number or an `io::Error`. The error is of kind
+    /// `Unsupported` if the target lacks hardware support for the requested watchpoint.
+    pub async fn watch(&mut self, kind: WatchKind, expression: &str) -> Result<u32, io::Error> {
+        let lines = self.make_request_await_response(
+            format!("{} {expression}", kind.command()).as_str(),
+            Some(1),
+            self.options.timeouts.breakpoint
+        ).await?;
+
+        let first_line = lines.first().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Watch missing result"))?;
+        if first_line.starts_with("Can't set read/access watchpoint") {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, first_line.clone()));
+        }
+
+        let number = extract_watchpoint_number_from_response_line(first_line)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Watchpoint not set: {first_line}")))?;
+        if !first_line.starts_with("Hardware") {
+            log::warn!("Software watchpoint {number} set on '{expression}', target will run very slowly.");
+        }
+
+        Ok(number)
+    }
+
+    /// Sets a write watchpoint on a variable, see [`Gdb::watch`].
+    #[allow(unused)]
+    pub async fn watch_variable(&mut self, name: &str) -> Result<u32, io::Error> {
+        self.watch(WatchKind::Write, name).await
+    }
+
+    /// Sets a read watchpoint on a variable, see [`Gdb::watch`].
+    #[allow(unused)]
+    pub async fn rwatch_variable(&mut self, name: &str) -> Result<u32, io::Error> {
+        self.watch(WatchKind::Read, name).await
+    }
+
+    /// Sets an access (read or write) watchpoint on a variable, see [`Gdb::watch`].
+    #[allow(unused)]
+    pub async fn awatch_variable(&mut self, name: &str) -> Result<u32, io::Error> {
+        self.watch(WatchKind::Access, name).await
+    }
+
+    /// Instructs the probe to sleep for a specified number of milliseconds,
+    /// the host sleeps instead if the probe has no sleep command.
+    ///
+    /// # Expected Result
+    /// A single response line similar to: `"Sleep 250ms"`
+    ///
+    /// # Parameters
+    /// - `millis`: The number of milliseconds to sleep.
+    ///
+    /// # Returns
+    /// A `Result` containing the response lines of both streams, see [`Gdb::monitor_request`], or an `io::Error`.
+    pub async fn monitor_sleep(&mut self, millis: u32) -> Result<Vec<MonitorLine>, io::Error> {
+        let Some(sleep_cmd) = self.options.monitor.sleep.clone() else {
+            tokio::time::sleep(Duration::from_millis(millis as u64)).await;
+            return Ok(vec![]);
+        };
+
+        self.monitor_request(
+            sleep_cmd.replace("{millis}", &millis.to_string()).as_str(),
+            Duration::from_millis(millis as u64 + 250)
+        ).await
+    }
+
+    /// Erases a flash region with the probe erase command.
+    ///
+    /// # Parameters
+    /// - `address`: The start address of the region.
+    /// - `length`: The length of the region in bytes, probes erasing the whole flash ignore it.
+    ///
+    /// # Returns
+    /// A `Result` containing the response lines of both streams, see [`Gdb::monitor_request`], or an `io::Error`,
+    /// of kind `Unsupported` if the probe has no erase command.
+    pub async fn monitor_flash_erase(&mut self, address: u32, length: usize) -> Result<Vec<MonitorLine>, io::Error> {
+        let erase_cmd = self.options.monitor.flash_erase.clone()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "Probe has no flash erase command"))?;
+
+        self.monitor_request(
+            erase_cmd
+                .replace("{address}", &format!("{address:#x}"))
+                .replace("{length}", &length.to_string())
+                .as_str(),
+            self.options.timeouts.call
+        ).await
+    }
+
+    /// Loads symbols of an additional ELF file, e.g. a bootloader next to the application.
+    ///
+    /// # Expected Result
+    /// Lines similar to:
+    /// `add symbol table from file "boot.elf" at`, `.text_addr = 0x8000000`
+    /// and `Reading symbols from boot.elf...`
+    ///
+    /// # Parameters
+    /// - `path`: The path to the ELF file.
+    /// - `text_address`: The load address of `.text`, taken from the file if `None`.
+    ///
+    /// # Returns
+    /// An `io::Result<()>` with an error if GDB did not acknowledge reading the symbols.
+    pub async fn add_symbol_file(&mut self, path: &Path, text_address: Option<u32>) -> Result<(), io::Error> {
+        let path_str = path.to_str()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Path {path:?} is not valid UTF-8")))?;
+        let cmd = match text_address {
+            Some(text_address) => format!("add-symbol-file \"{path_str}\" {text_address:#x}"),
+            None => format!("add-symbol-file \"{path_str}\""),
+        };
+
+        self.make_request(&cmd).await?;
+        self.await_symbols_read(path).await
+    }
+
+    /// Reloads the main ELF, e.g. after it was rebuilt during the session.
+    ///
+    /// # Expected Result
+    /// A line similar to:
+    /// `Reading symbols from fw.elf...`
+    ///
+    /// # Parameters
+    /// - `elf_path`: The path to the ELF file.
+    ///
+    /// # Returns
+    /// An `io::Result<()>` with an error if GDB did not acknowledge reading the symbols.
+    #[allow(unused)]
+    pub async fn reload_symbols(&mut self, elf_path: &Path) -> Result<(), io::Error> {
+        let path_str = elf_path.to_str()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Path {elf_path:?} is not valid UTF-8")))?;
+
+        self.make_request(&format!("file \"{path_str}\"")).await?;
+        self.await_symbols_read(elf_path).await
+    }
+
+    /// Awaits the acknowledgment of reading a symbol file.
+    async fn await_symbols_read(&mut self, path: &Path) -> Result<(), io::Error> {
+        let (lines, finished) = self.await_responses_until(
+            |line| line.starts_with("Reading symbols from") || get_error_stderr_regex().is_match(line),
+            self.options.timeouts.restore
+        ).await?;
+
+        match lines.last() {
+            Some(line) if finished && line.starts_with("Reading symbols from") => {
+                log::info!("Loaded symbols from {path:?}.");
+                Ok(())
+            },
+            Some(line) => Err(io::Error::new(io::ErrorKind::InvalidData, format!("Loading symbols from {path:?} failed: {line}"))),
+            None => Err(io::Error::new(io::ErrorKind::TimedOut, format!("Loading symbols from {path:?} not acknowledged"))),
+        }
+    }
+
+    /// Writes a binary file into memory.
+    ///
+    /// # Expected Result
+    /// The response should be a single line similar to:
+    /// `Restoring binary file <filepath> binary <ram_buffer_name> into memory (0x200b76a8 to 0x200c76a8)`
+    /// and calculates the byte count from the resulting addresses.
+    ///
+    /// # Parameters
+    /// - `ram_buffer_name`: The name of the RAM buffer or an expression of its address,
+    ///   e.g. `&loader_ram_buffer` or `0x20000000`.
+    /// - `binary_filepath`: The file path of the binary file.
+    ///
+    /// # Returns
+    /// A `Result` containing the number of bytes written or an `io::Error` if parsing fails.
+    pub async fn write_binary_file_to_mem<P>(&mut self, ram_buffer_name: &str, binary_filepath: P) -> Result<u32, io::Error> 
+    where 
+        P: AsRef<Path>
+    {
+        let file_size = tokio::fs::metadata(binary_filepath.as_ref()).await?.len() as usize;
+        let timeout = scaled_restore_timeout(&self.options.timeouts, self.restore_ms_per_kb, file_size);
+        let restore_start = Instant::now();
+
+        // 1 line - chars=106, response_out='Restoring binary file C:\WS\gdbloader\tmp_bin_chunks\chunk_0_.bin into memory (0x200b76a8 to 0x200c76a8)',
+        let lines = self.make_request_await_response(
+            format!(
+                "restore {} binary {}", 
+                binary_filepath.as_ref().to_str().unwrap(), 
+                ram_buffer_name
+            ).as_str(),
+            Some(1),
+            timeout
+        ).await?;
+
+        let first_line = lines.first().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Read missing result"))?;
+        let (from_address, to_address) = extract_adresses_from_response_line(first_line)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Corrupted result format"))?;
+        let bytes_count = to_address - from_address;
+
+        if bytes_count > 0 {
+            // Exponential moving average, so the estimate follows a link getting slower.
+            let sample = restore_start.elapsed().as_secs_f64() * 1000.0 / (bytes_count as f64 / 1024.0);
+            let estimate = self.restore_ms_per_kb.map_or(sample, |estimate| (estimate + sample) / 2.0);
+            log::debug!("Restored {bytes_count} B with timeout {} ms, measured {sample:.2} ms/KiB.", timeout.as_millis());
+            self.restore_ms_per_kb = Some(estimate);
+        }
+
+        Ok(bytes_count)
+    }
+
+    /// Writes bytes into target memory with the GDB/MI `-data-write-memory-bytes` command,
+    /// avoiding a temporary file per chunk.
+    ///
+    /// The bytes are written in the given order, any endianness conversion is up to the caller.
+    ///
+    /// # Parameters
+    /// - `address`: The start address of the written block.
+    /// - `data`: The bytes to write.
+    ///
+    /// # Returns
+    /// An `io::Result<()>`, the error is of kind `Unsupported` if GDB has no MI interpreter.
+    #[allow(unused)]
+    pub async fn write_memory_bytes(&mut self, address: u32, data: &[u8]) -> Result<(), io::Error> {
+        self.write_memory_bytes_at(&format!("{address:#x}"), data).await
+    }
+
+    /// Writes bytes into target memory starting at an address expression, e.g. `&loader_ram_buffer`.
+    ///
+    /// # Expected Result
+    /// A single MI result record `^done`, or `^error,msg="..."` on failure.
+    ///
+    /// # Parameters
+    /// - `location`: The expression evaluating to the start address of the block.
+    /// - `data`: The bytes to write.
+    ///
+    /// # Returns
+    /// Same as [`Gdb::write_memory_bytes`].
+    pub async fn write_memory_bytes_at(&mut self, location: &str, data: &[u8]) -> Result<(), io::Error> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let hex_data: String = data.iter().map(|byte| format!("{byte:02x}")).collect();
+        self.make_request(&format!("interpreter-exec mi \"-data-write-memory-bytes {location} {hex_data}\"")).await?;
+
+        // A GDB without the MI interpreter answers with a CLI error line instead of a result record,
+        // stop at it instead of waiting for a record which never comes.
+        let is_mi_missing = |line: &str| line.to_lowercase().contains("interpreter") || get_unsupported_command_regex().is_match(line);
+        let (lines, finished) = self.await_responses_until(
+            |line| line.starts_with("^done") || line.starts_with("^error") || is_mi_missing(line),
+            self.options.timeouts.restore
+        ).await?;
+
+        match lines.last() {
+            Some(line) if finished && line.starts_with("^done") => Ok(()),
+            Some(line) if finished && line.starts_with("^error") => Err(io::Error::other(format!("MI write failed: {line}"))),
+            Some(_) if finished => Err(io::Error::new(io::ErrorKind::Unsupported, "GDB MI interpreter not available")),
+            _ => Err(io::Error::new(io::ErrorKind::TimedOut, "MI write not acknowledged")),
+        }
+    }
+}
+
+/// Log target of the parsing steps, enabled with `--trace-parsing`.
+pub const PARSE_TRACE_TARGET: &str = "gdbloader::parse";
+
+/// Returns a reference to the static regex matching `target remote` failures.
+fn get_connect_error_regex() -> &'static Regex {
+    static REGEX_CONNECT_ERROR: OnceLock<Regex> = OnceLock::new();
+    REGEX_CONNECT_ERROR.get_or_init(|| {
+        Regex::new(r"(?i)connection refused|connection timed out|could not connect|remote communication error|remote connection closed|no route to host|unknown host").unwrap()
+    })
+}
+
+/// Returns a reference to the static regex matching the remote of a handshake, e.g. `Remote debugging using localhost:3333`.
+fn get_remote_debugging_regex() -> &'static Regex {
+    static REGEX_REMOTE_DEBUGGING: OnceLock<Regex> = OnceLock::new();
+    REGEX_REMOTE_DEBUGGING.get_or_init(|| {
+        Regex::new(r"^Remote debugging using (.+)$").unwrap()
+    })
+}
+
+/// Returns a reference to the static regex matching the frame the target stopped in, e.g. `0x08000100 in Reset_Handler ()`.
+fn get_frame_address_regex() -> &'static Regex {
+    static REGEX_FRAME_ADDRESS: OnceLock<Regex> = OnceLock::new();
+    REGEX_FRAME_ADDRESS.get_or_init(|| {
+        Regex::new(r"^0x([0-9a-fA-F]+) in ").unwrap()
+    })
+}
+
+/// Returns a reference to the static regex matching warnings of a handshake without the `warning:` prefix,
+/// e.g. `warning: Target not halted at reset` or `Target not examined yet`.
+fn get_connect_warning_regex() -> &'static Regex {
+    static REGEX_CONNECT_WARNING: OnceLock<Regex> = OnceLock::new();
+    REGEX_CONNECT_WARNING.get_or_init(|| {
+        Regex::new(r"(?i)^(?:warning:\s*(.+)|(.*\bnot (?:halted|examined)\b.*))$").unwrap()
+    })
+}
+
+/// Returns a reference to the static regex matching responses of a dropped connection,
+/// e.g. `Remote connection closed` or `"monitor" command not supported by this target.`.
+fn get_disconnected_regex() -> &'static Regex {
+    static REGEX_DISCONNECTED: OnceLock<Regex> = OnceLock::new();
+    REGEX_DISCONNECTED.get_or_init(|| {
+        Regex::new(r#"(?i)remote connection closed|remote communication error|"monitor" command not supported by this target|the program is not being run"#).unwrap()
+    })
+}
+
+/// Returns a reference to the static regex for value history entries, e.g. `$1 = 42`.
+fn get_value_history_regex() -> &'static Regex {
+    static REGEX_VALUE_HISTORY: OnceLock<Regex> = OnceLock::new();
+    REGEX_VALUE_HISTORY.get_or_init(|| {
+        Regex::new(r"^\$\d+ = (.*)$").unwrap()
+    })
+}
+
+/// Returns a reference to the static regex for missing symbol errors, e.g. `No symbol "foo" in current context.`.
+fn get_missing_symbol_regex() -> &'static Regex {
+    static REGEX_MISSING_SYMBOL: OnceLock<Regex> = OnceLock::new();
+    REGEX_MISSING_SYMBOL.get_or_init(|| {
+        Regex::new(r#"^No symbol "([^"]+)" in current context"#).unwrap()
+    })
+}
+
+/// Extracts the name of a missing symbol from a response line.
+///
+/// # Parameters
+/// - `line`: A response line.
+///
+/// # Returns
+/// An `Option` containing the symbol name if the line reports a missing symbol.
+fn extract_missing_symbol_from_response_line(line: &str) -> Option<String> {
+    get_missing_symbol_regex().captures(line)
+        .and_then(|captures| captures.get(1))
+        .map(|symbol| symbol.as_str().to_string())
+}
+
+/// Returns a reference to the static regex for hexadecimal addresses.
+fn get_hex_adress_regex() -> &'static Regex {
+    static REGEX_HEX_ADRESSES: OnceLock<Regex> = OnceLock::new();
+
+    REGEX_HEX_ADRESSES.get_or_init(|| {
+        Regex::new(r"0x([0-9a-fA-F]+)").unwrap()
+    })
+}
+
+/// Returns a reference to the static regex for hexadecimal addresses.
+fn get_hex_adresses_range_regex() -> &'static Regex {
+    static REGEX_ADRESSES_RANGE: OnceLock<Regex> = OnceLock::new();
+
+    REGEX_ADRESSES_RANGE.get_or_init(|| {
+        Regex::new(r"\(0x([0-9a-fA-F]+) to 0x([0-9a-fA-F]+)\)").unwrap()
+    })
+}
+
+/// Returns a reference to the static regex for a breakpoint hit line, e.g. `Breakpoint 1, foo ()`.
+fn get_breakpoint_hit_regex() -> &'static Regex {
+    static REGEX_BREAKPOINT_HIT: OnceLock<Regex> = OnceLock::new();
+
+    REGEX_BREAKPOINT_HIT.get_or_init(|| {
+        Regex::new(r"^(?:Temporary b|B)reakpoint (\d+), ").unwrap()
+    })
+}
+
+/// Returns a reference to the static regex for a set breakpoint line, e.g. `Breakpoint 1 at 0x8009bc8: file main.c, line 118.`.
+fn get_breakpoint_set_regex() -> &'static Regex {
+    static REGEX_BREAKPOINT_SET: OnceLock<Regex> = OnceLock::new();
+
+    REGEX_BREAKPOINT_SET.get_or_init(|| {
+        Regex::new(r"^(?:Temporary b|B)reakpoint (\d+) at (?:0x([0-9a-fA-F]+))?").unwrap()
+    })
+}
+
+/// Returns a reference to the static regex for a breakpoint location with its source line,
+/// e.g. `1       breakpoint     keep y   0x08009bc8 in Loader_Breakpoint at Core/Src/loader.c:42`.
+fn get_breakpoint_source_regex() -> &'static Regex {
+    static REGEX_BREAKPOINT_SOURCE: OnceLock<Regex> = OnceLock::new();
+
+    REGEX_BREAKPOINT_SOURCE.get_or_init(|| {
+        Regex::new(r"^(\d+)(?:\.\d+)?\s+(?:(?:hw )?breakpoint\s+\S+\s+)?[yn]-?\s+0x([0-9a-fA-F]+) in (\S+) at (\S+:\d+)$").unwrap()
+    })
+}
+
+/// Returns a reference to the static regex for the address of a source line, e.g. `Line 42 of "loader.c" starts at address 0x8009bc8`.
+fn get_line_address_regex() -> &'static Regex {
+    static REGEX_LINE_ADDRESS: OnceLock<Regex> = OnceLock::new();
+
+    REGEX_LINE_ADDRESS.get_or_init(|| {
+        Regex::new(r"^Line \d+ of .* (?:starts at|is at) address 0x([0-9a-fA-F]+)").unwrap()
+    })
+}
+
+/// Returns a reference to the static regex for the locations count of a breakpoint, e.g. `foo. (3 locations)`.
+fn get_breakpoint_locations_count_regex() -> &'static Regex {
+    static REGEX_BREAKPOINT_LOCATIONS_COUNT: OnceLock<Regex> = OnceLock::new();
+
+    REGEX_BREAKPOINT_LOCATIONS_COUNT.get_or_init(|| {
+        Regex::new(r"\((\d+) locations\)").unwrap()
+    })
 }
 
-/// Returns a reference to the static regex for hexadecimal addresses.
-fn get_hex_adresses_range_regex() -> &'static Regex {
-    static REGEX_ADRESSES_RANGE: OnceLock<Regex> = OnceLock::new();
+/// Returns a reference to the static regex for a breakpoint location line, e.g. `1.2   y   0x08000150 in foo at foo.h:10`.
+fn get_breakpoint_location_regex() -> &'static Regex {
+    static REGEX_BREAKPOINT_LOCATION: OnceLock<Regex> = OnceLock::new();
 
-    REGEX_ADRESSES_RANGE.get_or_init(|| {
-        Regex::new(r"\(0x([0-9a-fA-F]+) to 0x([0-9a-fA-F]+)\)").unwrap()
+    REGEX_BREAKPOINT_LOCATION.get_or_init(|| {
+        Regex::new(r"^(\d+)\.(\d+)\s+([yn])-?\s+0x([0-9a-fA-F]+)").unwrap()
+    })
+}
+
+/// Returns a reference to the static regex for a hit count line, e.g. `\tbreakpoint already hit 3 times`.
+fn get_hit_count_regex() -> &'static Regex {
+    static REGEX_HIT_COUNT: OnceLock<Regex> = OnceLock::new();
+
+    REGEX_HIT_COUNT.get_or_init(|| {
+        Regex::new(r"^\s*breakpoint already hit (\d+) times?").unwrap()
+    })
+}
+
+/// Extracts the breakpoint number from the response lines of [`Gdb::break_at`] or [`Gdb::break_at_address`].
+///
+/// # Parameters
+/// - `lines`: The response lines, one similar to `Breakpoint 1 at 0x8009bc8`.
+///
+/// # Returns
+/// An `Option` containing the breakpoint number.
+pub fn extract_breakpoint_number(lines: &[String]) -> Option<u32> {
+    lines.iter().find_map(|line| {
+        get_breakpoint_set_regex()
+            .captures(line)
+            .and_then(|captures| captures[1].parse().ok())
+    })
+}
+
+/// Extracts a set breakpoint from a response line of [`Gdb::break_at`].
+///
+/// # Parameters
+/// - `line`: A response line similar to `Breakpoint 1 at 0x8009bc8: file main.c, line 118.`
+///   or `Breakpoint 1 at 0x8000130: foo. (3 locations)`.
+///
+/// # Returns
+/// An `Option` with the breakpoint number, the address of its first location and its locations count.
+fn extract_breakpoint_from_response_line(line: &str) -> Option<(u32, Option<u32>, usize)> {
+    let captures = get_breakpoint_set_regex().captures(line)?;
+    let number = captures[1].parse().ok()?;
+    let address = captures.get(2).and_then(|address| u32::from_str_radix(address.as_str(), 16).ok());
+    let locations_count = get_breakpoint_locations_count_regex()
+        .captures(line)
+        .and_then(|captures| captures[1].parse().ok())
+        .unwrap_or(1);
+
+    log::trace!(target: PARSE_TRACE_TARGET, "Breakpoint line {line:?} parsed as number={number} address={address:?} locations={locations_count}");
+    Some((number, address, locations_count))
+}
+
+/// Extracts a location of a multi-location breakpoint from a response line of `info breakpoints`.
+///
+/// # Parameters
+/// - `line`: A response line similar to `1.2                         y   0x08000150 in foo at foo.h:10`.
+/// - `number`: The breakpoint number, lines of other breakpoints are ignored.
+///
+/// # Returns
+/// An `Option` containing the location.
+fn extract_breakpoint_location_from_response_line(line: &str, number: u32) -> Option<BreakpointLocation> {
+    let captures = get_breakpoint_location_regex().captures(line)?;
+    if captures[1].parse::<u32>().ok()? != number {
+        return None;
+    }
+
+    Some(BreakpointLocation {
+        index: captures[2].parse().ok()?,
+        enabled: &captures[3] == "y",
+        address: u32::from_str_radix(&captures[4], 16).ok()?,
+    })
+}
+
+/// Extracts the location of a breakpoint with its source line from a response line of `info breakpoints`.
+///
+/// # Parameters
+/// - `line`: A response line similar to
+///   `1       breakpoint     keep y   0x08009bc8 in Loader_Breakpoint at Core/Src/loader.c:42`
+///   or `1.2                         y   0x08000150 in foo at foo.h:10`.
+///
+/// # Returns
+/// An `Option` with the breakpoint number, the address, the function and the source line as `file:line`.
+fn extract_breakpoint_source_from_response_line(line: &str) -> Option<(u32, u32, String, String)> {
+    let captures = get_breakpoint_source_regex().captures(line)?;
+    Some((
+        captures[1].parse().ok()?,
+        u32::from_str_radix(&captures[2], 16).ok()?,
+        captures[3].to_string(),
+        captures[4].to_string(),
+    ))
+}
+
+/// Extracts the start address of a source line from a response line of `info line`.
+///
+/// # Parameters
+/// - `line`: A response line similar to `Line 42 of "loader.c" starts at address 0x8009bc8 <Loader_Breakpoint+4> and ends at 0x8009bcc.`
+///
+/// # Returns
+/// An `Option` containing the address.
+fn extract_line_address_from_response_line(line: &str) -> Option<u32> {
+    get_line_address_regex()
+        .captures(line)
+        .and_then(|captures| u32::from_str_radix(&captures[1], 16).ok())
+}
+
+/// Extracts the hit count from a response line.
+///
+/// # Parameters
+/// - `line`: A response line similar to `\tbreakpoint already hit 1 time`.
+///
+/// # Returns
+/// An `Option` containing the hit count.
+fn extract_hit_count_from_response_line(line: &str) -> Option<u32> {
+    get_hit_count_regex()
+        .captures(line)
+        .and_then(|captures| captures[1].parse().ok())
+}
+
+/// Returns a reference to the static regex for a signal line, e.g. `Program received signal SIGTRAP, Trace/breakpoint trap.`.
+fn get_signal_regex() -> &'static Regex {
+    static REGEX_SIGNAL: OnceLock<Regex> = OnceLock::new();
+
+    REGEX_SIGNAL.get_or_init(|| {
+        Regex::new(r"^Program (?:received|terminated with) signal (\w+)").unwrap()
+    })
+}
+
+/// Returns a reference to the static regex for an exit line, e.g. `[Inferior 1 (Remote target) exited with code 01]`.
+fn get_exited_regex() -> &'static Regex {
+    static REGEX_EXITED: OnceLock<Regex> = OnceLock::new();
+
+    REGEX_EXITED.get_or_init(|| {
+        Regex::new(r"^\[Inferior \d+ .*exited (?:normally|with code ([0-7]+))\]").unwrap()
+    })
+}
+
+/// Returns a reference to the static regex for the line starting a jump, e.g. `Continuing at 0x8000124.`.
+fn get_continuing_at_regex() -> &'static Regex {
+    static REGEX_CONTINUING_AT: OnceLock<Regex> = OnceLock::new();
+
+    REGEX_CONTINUING_AT.get_or_init(|| {
+        Regex::new(r"^Continuing at (0x[0-9a-fA-F]+)\.?").unwrap()
+    })
+}
+
+/// Returns a reference to the static regex for the source line ending a step, e.g. `119   HAL_Delay(10);`.
+fn get_step_end_regex() -> &'static Regex {
+    static REGEX_STEP_END: OnceLock<Regex> = OnceLock::new();
+
+    REGEX_STEP_END.get_or_init(|| {
+        Regex::new(r"^(?:\d+\s|0x[0-9a-fA-F]+ in )").unwrap()
+    })
+}
+
+/// Tells whether a line marks that the target stopped on a breakpoint, watchpoint, signal or exit.
+fn is_stop_line(line: &str) -> bool {
+    get_breakpoint_hit_regex().is_match(line)
+        || get_watchpoint_value_regex().is_match(line)
+        || get_signal_regex().is_match(line)
+        || get_exited_regex().is_match(line)
+}
+
+/// Extracts the reason of a stop from the lines returned after resuming the target.
+///
+/// # Parameters
+/// - `lines`: Response lines of a `continue` or `step` command.
+///
+/// # Returns
+/// The `StopReason` found in the last matching line, `StopReason::Unknown` if none matches.
+fn extract_stop_reason(lines: &[String]) -> StopReason {
+    for line in lines.iter().rev() {
+        if let Some(stop_reason) = extract_stop_reason_from_mi_line(line) {
+            return stop_reason;
+        }
+
+        if let Some(captures) = get_breakpoint_hit_regex().captures(line) {
+            if let Ok(number) = captures[1].parse() {
+                return StopReason::Breakpoint { number };
+            }
+        }
+
+        if let Some(number) = extract_watchpoint_number_from_response_line(line) {
+            return StopReason::Watchpoint { number };
+        }
+
+        if let Some(captures) = get_signal_regex().captures(line) {
+            return StopReason::Signal { name: captures[1].to_string() };
+        }
+
+        if let Some(captures) = get_exited_regex().captures(line) {
+            // GDB prints the exit code in octal.
+            let code = captures.get(1)
+                .and_then(|code| u32::from_str_radix(code.as_str(), 8).ok())
+                .unwrap_or(0);
+            return StopReason::Exited { code };
+        }
+    }
+
+    match lines.last() {
+        Some(line) if get_step_end_regex().is_match(line) => StopReason::Stepped,
+        _ => StopReason::Unknown,
+    }
+}
+
+/// Tells whether a line is an MI stop record, e.g. `*stopped,reason="breakpoint-hit",bkptno="1",...`.
+fn is_mi_stop_line(line: &str) -> bool {
+    line.starts_with("*stopped")
+}
+
+/// Extracts the reason of a stop from an MI stop record.
+///
+/// # Parameters
+/// - `line`: A line such as `*stopped,reason="signal-received",signal-name="SIGSEGV",...`.
+///
+/// # Returns
+/// The `StopReason`, `StopReason::Unknown` for an unknown reason, `None` if the line is not a stop record.
+fn extract_stop_reason_from_mi_line(line: &str) -> Option<StopReason> {
+    if !is_mi_stop_line(line) {
+        return None;
+    }
+
+    let field = |name: &str| {
+        get_mi_field_regex().captures_iter(line)
+            .find(|captures| &captures[1] == name)
+            .map(|captures| captures[2].to_string())
+    };
+    let number = |name: &str| field(name).and_then(|value| value.parse().ok());
+
+    let stop_reason = match field("reason").as_deref() {
+        Some("breakpoint-hit") => number("bkptno").map(|number| StopReason::Breakpoint { number }),
+        Some("watchpoint-trigger" | "read-watchpoint-trigger" | "access-watchpoint-trigger") => {
+            number("number").map(|number| StopReason::Watchpoint { number })
+        },
+        Some("signal-received") => field("signal-name").map(|name| StopReason::Signal { name }),
+        // MI prints the exit code in octal, like the CLI.
+        Some("exited") => Some(StopReason::Exited {
+            code: field("exit-code").and_then(|code| u32::from_str_radix(&code, 8).ok()).unwrap_or(0)
+        }),
+        Some("exited-normally") => Some(StopReason::Exited { code: 0 }),
+        Some("end-stepping-range") => Some(StopReason::Stepped),
+        _ => None,
+    };
+    log::trace!(target: PARSE_TRACE_TARGET, "MI stop record {line:?} parsed as {stop_reason:?}");
+
+    Some(stop_reason.unwrap_or(StopReason::Unknown))
+}
+
+/// Returns a reference to the static regex for a field of an MI record, e.g. `bkptno="1"`.
+fn get_mi_field_regex() -> &'static Regex {
+    static REGEX_MI_FIELD: OnceLock<Regex> = OnceLock::new();
+
+    REGEX_MI_FIELD.get_or_init(|| {
+        Regex::new(r#"([a-z-]+)="([^"]*)""#).unwrap()
+    })
+}
+
+/// Returns a reference to the static regex for a watchpoint line, e.g. `Hardware watchpoint 2: result`.
+fn get_watchpoint_regex() -> &'static Regex {
+    static REGEX_WATCHPOINT: OnceLock<Regex> = OnceLock::new();
+
+    REGEX_WATCHPOINT.get_or_init(|| {
+        Regex::new(r"^(?:Hardware )?(?:read |access \(read/write\) )?[Ww]atchpoint (\d+): ").unwrap()
+    })
+}
+
+/// Returns a reference to the static regex for the value report ending a watchpoint trigger.
+fn get_watchpoint_value_regex() -> &'static Regex {
+    static REGEX_WATCHPOINT_VALUE: OnceLock<Regex> = OnceLock::new();
+
+    REGEX_WATCHPOINT_VALUE.get_or_init(|| {
+        Regex::new(r"^(?:New value|Value) = ").unwrap()
+    })
+}
+
+/// Extracts the watchpoint number from a response line.
+///
+/// # Parameters
+/// - `line`: A response line similar to `Hardware watchpoint 2: result`.
+///
+/// # Returns
+/// An `Option` containing the watchpoint number.
+fn extract_watchpoint_number_from_response_line(line: &str) -> Option<u32> {
+    get_watchpoint_regex()
+        .captures(line)
+        .and_then(|captures| captures[1].parse().ok())
+}
+
+/// Returns a reference to the static regex for a memory examine line, e.g. `0x8001000 <foo+8>: 0x12 0x34`.
+fn get_memory_line_regex() -> &'static Regex {
+    static REGEX_MEMORY_LINE: OnceLock<Regex> = OnceLock::new();
+
+    REGEX_MEMORY_LINE.get_or_init(|| {
+        Regex::new(r"^0x[0-9a-fA-F]+(?: <[^>]*>)?:\s*(.*)$").unwrap()
+    })
+}
+
+/// Extracts the bytes from a line of `x/Nxb` output.
+///
+/// # Parameters
+/// - `line`: A response line containing an address followed by hexadecimal bytes.
+///
+/// # Returns
+/// An `Option` containing the extracted bytes.
+fn extract_memory_bytes_from_response_line(line: &str) -> Option<Vec<u8>> {
+    let captures = get_memory_line_regex().captures(line)?;
+
+    captures[1]
+        .split_whitespace()
+        .map(|byte| u8::from_str_radix(byte.strip_prefix("0x")?, 16).ok())
+        .collect()
+}
+
+/// Returns a reference to the static regex for benign stderr lines.
+fn get_benign_stderr_regex() -> &'static Regex {
+    static REGEX_BENIGN_STDERR: OnceLock<Regex> = OnceLock::new();
+
+    REGEX_BENIGN_STDERR.get_or_init(|| {
+        Regex::new(r"^(?:$|Resetting target|Sleep \d+ms|Halting target|Target halted|Reading all registers|Read \d+ bytes @ address|Target interface speed set to)").unwrap()
+    })
+}
+
+/// Returns a reference to the static regex for error stderr lines.
+fn get_error_stderr_regex() -> &'static Regex {
+    static REGEX_ERROR_STDERR: OnceLock<Regex> = OnceLock::new();
+
+    REGEX_ERROR_STDERR.get_or_init(|| {
+        Regex::new(r"(?i)^(?:error|no symbol|cannot |can't |could not|undefined command|remote connection closed|remote communication error|.*: no such file|.*not found|.*failed)").unwrap()
+    })
+}
+
+/// Returns a reference to the static regex for lines of a command GDB or the probe does not know,
+/// e.g. `Undefined command: "dump".  Try "help".`
+fn get_unsupported_command_regex() -> &'static Regex {
+    static REGEX_UNSUPPORTED_COMMAND: OnceLock<Regex> = OnceLock::new();
+
+    REGEX_UNSUPPORTED_COMMAND.get_or_init(|| {
+        Regex::new(r"(?i)undefined command|not supported").unwrap()
+    })
+}
+
+/// Returns a reference to the static regex for asynchronous output lines, e.g. `*stopped,reason=...`.
+fn get_async_line_regex() -> &'static Regex {
+    static REGEX_ASYNC_LINE: OnceLock<Regex> = OnceLock::new();
+
+    REGEX_ASYNC_LINE.get_or_init(|| {
+        Regex::new(r"^(?:[*=+][a-z-]+(?:,|$)|\[New Thread |\[Switching to |\[Thread .* exited\])").unwrap()
+    })
+}
+
+/// Tells whether GDB was terminated by `SIGTERM` or `SIGHUP`, which it may raise on itself when quitting.
+fn is_terminated_by_quit(status: &std::process::ExitStatus) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        matches!(status.signal(), Some(1) | Some(15))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        false
+    }
+}
+
+/// Tells whether a line is the echo of a sent command, possibly preceded by the `(gdb)` prompt.
+fn is_echo_line(line: &str, cmd: &str) -> bool {
+    let line = line.strip_prefix("(gdb)").unwrap_or(line).trim();
+    !line.is_empty() && line == cmd.trim()
+}
+
+/// Returns a reference to the static regex for GDB chatter not belonging to any command response.
+fn get_chatter_line_regex() -> &'static Regex {
+    static REGEX_CHATTER_LINE: OnceLock<Regex> = OnceLock::new();
+    REGEX_CHATTER_LINE.get_or_init(|| {
+        Regex::new(r"^(?:warning: Source file is more recent than executable\.|.* has changed; re-reading symbols\.|-+Type <(?:return|RET)>.*-+)$").unwrap()
+    })
+}
+
+/// Tells whether a line is known chatter, e.g. `warning: Source file is more recent than executable.`,
+/// `` `/path/fw.elf' has changed; re-reading symbols. `` or a pager prompt left by a pagination not turned off.
+fn is_chatter_line(line: &str) -> bool {
+    get_chatter_line_regex().is_match(line)
+}
+
+/// Tells whether a line printed during a call is target output rather than a GDB message,
+/// i.e. neither a value `$1 = ...` nor a known stderr line.
+fn is_target_output_line(line: &str) -> bool {
+    !get_value_history_regex().is_match(line)
+        && classify_stderr_line(line) == StderrClass::Unclassified
+        && extract_missing_symbol_from_response_line(line).is_none()
+}
+
+/// Tells whether an error reports the GDB process has exited.
+fn is_process_died(error: &io::Error) -> bool {
+    matches!(as_gdb_error(error), Some(GdbError::ProcessDied { .. }))
+}
+
+/// Tells whether a line is asynchronous output not belonging to any command response.
+fn is_async_line(line: &str) -> bool {
+    get_async_line_regex().is_match(line)
+}
+
+/// Classifies a stderr line as benign, error or unclassified.
+///
+/// # Parameters
+/// - `line`: A trimmed stderr line.
+///
+/// # Returns
+/// The `StderrClass` of the line.
+fn classify_stderr_line(line: &str) -> StderrClass {
+    if get_benign_stderr_regex().is_match(line) {
+        StderrClass::Benign
+    } else if get_error_stderr_regex().is_match(line) {
+        StderrClass::Error
+    } else {
+        StderrClass::Unclassified
+    }
+}
+
+/// Returns a reference to the static regex for a disassembly line, e.g. `=> 0x08000100 <main+0>:\t80 b5\tpush\t{r7, lr}`.
+fn get_instruction_regex() -> &'static Regex {
+    static REGEX_INSTRUCTION: OnceLock<Regex> = OnceLock::new();
+
+    REGEX_INSTRUCTION.get_or_init(|| {
+        Regex::new(r"^(=>\s*)?0x([0-9a-fA-F]+)(?: <[^>]*>)?:\s*(.*)$").unwrap()
+    })
+}
+
+/// Extracts an instruction from a line of `disassemble /r` output.
+///
+/// # Parameters
+/// - `line`: A response line with an address, raw bytes and the mnemonic separated by tabs.
+///
+/// # Returns
+/// An `Option` containing the parsed `Instruction`.
+fn extract_instruction_from_response_line(line: &str) -> Option<Instruction> {
+    let captures = get_instruction_regex().captures(line)?;
+    let address = u32::from_str_radix(&captures[2], 16).ok()?;
+    let (raw_bytes, mnemonic) = captures[3].split_once('\t')?;
+
+    let hex_digits = raw_bytes.split_whitespace().collect::<String>();
+    let bytes = (0..hex_digits.len())
+        .step_by(2)
+        .map(|idx| hex_digits.get(idx..idx + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect::<Option<Vec<u8>>>()?;
+
+    Some(Instruction {
+        address,
+        bytes,
+        mnemonic: mnemonic.split_whitespace().collect::<Vec<&str>>().join(" "),
+        is_current: captures.get(1).is_some(),
     })
 }
 
@@ -509,27 +3348,28 @@ fn get_hex_adresses_range_regex() -> &'static Regex {
 /// # Returns
 /// An `Option` containing a tuple of `(start_address, end_address)` if parsing succeeds.
 fn extract_adresses_from_response_line(line: &str) -> Option<(u32, u32)> {
-    if let Some(captures) = get_hex_adresses_range_regex().captures(line) {
-        log::trace!("{captures:?}");
+    let Some(captures) = get_hex_adresses_range_regex().captures(line) else {
+        log::trace!(target: PARSE_TRACE_TARGET, "Address range: line '{line}' did not match address range regex.");
+        return None;
+    };
 
-        let finds = get_hex_adress_regex()
-            .find_iter(line)
-            .map(|a| a.as_str().into())
-            .collect::<Vec<String>>();
+    let finds = get_hex_adress_regex()
+        .find_iter(line)
+        .map(|a| a.as_str().into())
+        .collect::<Vec<String>>();
 
-        if finds.len() == 2 {
-            let first_str = &finds[0];
-            let second_str = &finds[1];
-            log::trace!("From={}, to={}", first_str, second_str);
-
-            let start_address = u32::from_str_radix(&first_str[2..], 16).ok()?;
-            let end_address = u32::from_str_radix(&second_str[2..], 16).ok()?;
-            log::trace!("start_address={}, end_address={}", start_address, end_address);
-            return Some((start_address, end_address));
-        }
+    if finds.len() != 2 {
+        log::trace!(target: PARSE_TRACE_TARGET, "Address range: line '{line}' has {} addresses instead of 2, captured {captures:?}.", finds.len());
+        return None;
     }
 
-    None
+    let first_str = &finds[0];
+    let second_str = &finds[1];
+
+    let start_address = u32::from_str_radix(&first_str[2..], 16).ok();
+    let end_address = u32::from_str_radix(&second_str[2..], 16).ok();
+    log::trace!(target: PARSE_TRACE_TARGET, "Address range: line '{line}' parsed to start={start_address:?}, end={end_address:?}.");
+    Some((start_address?, end_address?))
 }
 
 /// Extracts a `u32` value from a response line.
@@ -540,9 +3380,62 @@ fn extract_adresses_from_response_line(line: &str) -> Option<(u32, u32)> {
 /// # Returns
 /// An `Option` containing the extracted `u32` value.
 fn extract_variable_value_from_response_line(line: &str) -> Option<u32> {
-    line.split(" ")
-        .last()
-        .and_then(|s| s.parse().ok())
+    let last_word = line.split(" ").last();
+    let value = last_word.and_then(|s| s.parse().ok());
+    match value {
+        Some(value) => log::trace!(target: PARSE_TRACE_TARGET, "Variable value: line '{line}' parsed to {value}."),
+        None => log::trace!(target: PARSE_TRACE_TARGET, "Variable value: last word {last_word:?} of line '{line}' is not a u32."),
+    }
+    value
+}
+
+/// Parses the value text of a `print/{fmt}` response into a `u32`.
+///
+/// # Parameters
+/// - `value_text`: The value text, the part after `$1 = `.
+/// - `fmt`: The print format the value was printed with.
+///
+/// # Returns
+/// An `Option` containing the value, `None` if it does not match the format or does not fit 32 bits.
+fn extract_formatted_u32(value_text: &str, fmt: char) -> Option<u32> {
+    match fmt {
+        // Octal and binary have no prefix GDB could be told apart from decimal by.
+        'o' => u32::from_str_radix(value_text, 8).ok(),
+        't' => u32::from_str_radix(value_text, 2).ok(),
+        _ => match GdbValue::parse(value_text) {
+            GdbValue::Int(value) => u32::try_from(value).ok()
+                .or_else(|| i32::try_from(value).ok().map(|value| value as u32)),
+            _ => None,
+        },
+    }
+}
+
+/// Parses the returned value of a call from its response line.
+///
+/// # Parameters
+/// - `line`: The call result line similar to `$3 = 118 'v'`, see [`extract_call_result`].
+/// - `has_return`: Whether a return value is expected.
+///
+/// # Returns
+/// The parsed value, [`TargetFunctionResult::Void`] if no return is expected
+/// and [`TargetFunctionResult::Raw`] with the whole line if it is not a value history entry.
+fn extract_target_function_result(line: &str, has_return: bool) -> TargetFunctionResult {
+    if !has_return {
+        return TargetFunctionResult::Void;
+    }
+
+    let result = match get_value_history_regex().captures(line) {
+        Some(captures) => TargetFunctionResult::parse(&captures[1]),
+        None => TargetFunctionResult::Raw(line.to_string()),
+    };
+    log::trace!(target: PARSE_TRACE_TARGET, "Function result: line '{line}' parsed to {result:?}.");
+    result
+}
+
+/// Converts a call result into a `u32`, see [`TargetFunctionResult::as_u32`].
+fn result_as_u32(result: &TargetFunctionResult) -> Result<u32, io::Error> {
+    result.as_u32()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Read format corrupted: {result:?}")))
 }
 
 /// Extracts the result of a call from the collected response lines.
@@ -556,11 +3449,246 @@ fn extract_variable_value_from_response_line(line: &str) -> Option<u32> {
 /// or an empty string otherwise. Returns an `io::Error` if no output is available when expected.
 fn extract_call_result(results: Vec<String>, has_return: bool) -> Result<String, io::Error> {
     if !has_return {
+        log::trace!(target: PARSE_TRACE_TARGET, "Call result: no return expected, ignored {results:?}.");
         Ok(String::new())
     } else {
+        log::trace!(target: PARSE_TRACE_TARGET, "Call result: first of {results:?} taken.");
         results
             .first()
             .cloned()
             .ok_or(io::Error::new(io::ErrorKind::InvalidData, "call with no output"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stand-in for GDB printing the echo of every command followed by a single response line.
+    #[cfg(unix)]
+    const ECHOING_GDB: &str = r#"while IFS= read -r line; do printf '%s\ndone %s\n' "$line" "$line"; done"#;
+
+    /// Writes a shell script standing in for GDB, it ignores its arguments and runs `body` on its stdin.
+    #[cfg(unix)]
+    fn mock_gdb(name: &str, body: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("gdbloader-mock-{}-{name}.sh", std::process::id()));
+        std::fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    /// Writes a PowerShell script standing in for GDB and a batch file running it, the batch file ignores
+    /// its arguments and the script runs `body` on its stdin.
+    #[cfg(windows)]
+    fn mock_gdb(name: &str, body: &str) -> PathBuf {
+        let script_path = std::env::temp_dir().join(format!("gdbloader-mock-{}-{name}.ps1", std::process::id()));
+        std::fs::write(&script_path, body).unwrap();
+        let path = script_path.with_extension("cmd");
+        std::fs::write(&path, format!("@powershell -NoProfile -ExecutionPolicy Bypass -File \"{}\"\r\n", script_path.display())).unwrap();
+        path
+    }
+
+    /// Starts the mock GDB and connects through it, with a short connect timeout as the mock never reports a target.
+    #[cfg(any(unix, windows))]
+    async fn connect_mock_gdb(mock_path: PathBuf, options: GdbOptions) -> Gdb {
+        let timeouts = Timeouts { connect: Duration::from_millis(100), ..options.timeouts.clone() };
+        Gdb::try_new(mock_path, PathBuf::from("fw.elf"), "localhost:3333".to_string(), GdbOptions { timeouts, ..options })
+            .await
+            .unwrap()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn echoed_command_is_not_returned_as_response() {
+        let mut gdb = connect_mock_gdb(mock_gdb("echo", ECHOING_GDB), GdbOptions::default()).await;
+        let lines = gdb.make_request_await_response("info line main", Some(1), Duration::from_millis(500)).await.unwrap();
+        assert_eq!(lines, ["done info line main"]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn long_output_is_captured_without_pager_prompt() {
+        // Pages after 100 lines and waits for a key, as GDB does, unless pagination and wrapping were turned off.
+        let stdin_log_path = std::env::temp_dir().join(format!("gdbloader-mock-{}-paging.log", std::process::id()));
+        let _ = std::fs::remove_file(&stdin_log_path);
+        let paging_gdb = format!(r#"pagination=on; width=80
+        while IFS= read -r line; do
+            printf '%s\n' "$line" >> '{}'
+            case "$line" in
+                "set pagination off") pagination=off ;;
+                "set width 0") width=0 ;;
+                "info functions")
+                    i=0
+                    while [ $i -lt 300 ]; do
+                        printf 'void func_%d(void);\n' $i
+                        if [ $i -eq 99 ] && {{ [ "$pagination" = on ] || [ "$width" != 0 ]; }}; then
+                            printf -- '--Type <RET> for more, q to quit, c to continue without paging--\n'
+                            break
+                        fi
+                        i=$((i + 1))
+                    done ;;
+            esac
+        done"#, stdin_log_path.display());
+        let mut gdb = connect_mock_gdb(mock_gdb("paging", &paging_gdb), GdbOptions::default()).await;
+        let lines = gdb.make_request_await_response("info functions", None, Duration::from_millis(300)).await.unwrap();
+        assert_eq!(lines.len(), 300);
+        assert_eq!(lines.last().map(String::as_str), Some("void func_299(void);"));
+        assert!(lines.iter().all(|line| !line.contains("Type <")));
+
+        let stdin_log = std::fs::read_to_string(&stdin_log_path).unwrap();
+        assert!(stdin_log.lines().any(|line| line == "set pagination off"));
+        assert!(stdin_log.lines().any(|line| line == "set width 0"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn crlf_is_written_and_stripped_from_responses() {
+        // The shell keeps the `\r` of a CRLF terminated line, the log shows the terminator sent.
+        let stdin_log_path = std::env::temp_dir().join(format!("gdbloader-mock-{}-crlf.log", std::process::id()));
+        let _ = std::fs::remove_file(&stdin_log_path);
+        let crlf_gdb = format!(
+            r#"while IFS= read -r line; do printf '%s\n' "$line" >> '{}'; printf '$1 = 5\r\n'; done"#,
+            stdin_log_path.display()
+        );
+        let options = GdbOptions { line_ending: LineEnding::Crlf, ..GdbOptions::default() };
+        let mut gdb = connect_mock_gdb(mock_gdb("crlf", &crlf_gdb), options).await;
+
+        let lines = gdb.make_request_await_response("print x", Some(1), Duration::from_millis(500)).await.unwrap();
+        assert_eq!(lines, ["$1 = 5"]);
+        let stdin_log = std::fs::read_to_string(&stdin_log_path).unwrap();
+        assert!(stdin_log.starts_with("set confirm off\r\n"));
+        assert!(stdin_log.ends_with("print x\r\n"));
+    }
+
+    #[cfg(windows)]
+    #[tokio::test]
+    async fn crlf_is_the_default_and_stripped_from_responses_on_windows() {
+        assert_eq!(LineEnding::default(), LineEnding::Crlf);
+
+        // Console output of PowerShell ends lines with `\r\n`, written explicitly to not depend on it.
+        let crlf_gdb = r#"while (($line = [Console]::In.ReadLine()) -ne $null) {
+            if ($line -eq 'print x') { [Console]::Out.Write("`$1 = 5`r`n"); [Console]::Out.Flush() }
+        }"#;
+        let mut gdb = connect_mock_gdb(mock_gdb("crlf", crlf_gdb), GdbOptions::default()).await;
+        let lines = gdb.make_request_await_response("print x", Some(1), Duration::from_secs(5)).await.unwrap();
+        assert_eq!(lines, ["$1 = 5"]);
+    }
+
+    #[test]
+    fn symbol_reload_warnings_are_chatter() {
+        assert!(is_chatter_line("warning: Source file is more recent than executable."));
+        assert!(is_chatter_line("`/home/user/fw/build/fw.elf' has changed; re-reading symbols."));
+        assert!(!is_chatter_line("Reading symbols from /home/user/fw/build/fw.elf..."));
+        assert!(!is_chatter_line("$1 = 0"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symbol_reload_warnings_are_not_counted_as_responses() {
+        let reloading_gdb = r#"while IFS= read -r line; do
+            [ "$line" = "print result" ] || continue
+            printf '%s\n' "\`/home/user/fw/build/fw.elf' has changed; re-reading symbols."
+            printf 'warning: Source file is more recent than executable.\n$1 = 7\n'
+        done"#;
+        let mut gdb = connect_mock_gdb(mock_gdb("reload", reloading_gdb), GdbOptions::default()).await;
+        let lines = gdb.make_request_await_response("print result", Some(1), Duration::from_millis(500)).await.unwrap();
+        assert_eq!(lines, ["$1 = 7"]);
+    }
+
+    #[test]
+    fn pager_prompts_are_chatter() {
+        assert!(is_chatter_line("---Type <return> to continue, or q <return> to quit---"));
+        assert!(is_chatter_line("--Type <RET> for more, q to quit, c to continue without paging--"));
+        assert!(!is_chatter_line("Type <return> is a struct"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn back_to_back_requests_skip_both_echoes() {
+        let mut gdb = connect_mock_gdb(mock_gdb("back-to-back", ECHOING_GDB), GdbOptions::default()).await;
+        gdb.make_request("print first").await.unwrap();
+        gdb.make_request("print second").await.unwrap();
+        assert_eq!(gdb.read_next_line().await.unwrap().as_deref(), Some("done print first"));
+        assert_eq!(gdb.read_next_line().await.unwrap().as_deref(), Some("done print second"));
+    }
+
+    #[test]
+    fn call_result_zero() {
+        let result = extract_target_function_result("$1 = 0", true);
+        assert_eq!(result, TargetFunctionResult::Int(0));
+        assert_eq!(result.as_u32(), Some(0));
+    }
+
+    #[test]
+    fn call_result_hex() {
+        let result = extract_target_function_result("$2 = 0x1234", true);
+        assert_eq!(result, TargetFunctionResult::Hex(0x1234));
+        assert_eq!(result.as_u32(), Some(0x1234));
+    }
+
+    #[test]
+    fn call_result_char() {
+        let result = extract_target_function_result("$3 = 118 'v'", true);
+        assert_eq!(result, TargetFunctionResult::Int(118));
+        assert_eq!(result.as_u32(), Some(118));
+    }
+
+    #[test]
+    fn call_result_void() {
+        let result = extract_target_function_result("$4 = 7", false);
+        assert_eq!(result, TargetFunctionResult::Void);
+        assert_eq!(result.as_u32(), None);
+    }
+
+    #[test]
+    fn multi_location_breakpoint_is_parsed() {
+        let (number, address, locations_count) =
+            extract_breakpoint_from_response_line("Breakpoint 1 at 0x8000130: foo. (3 locations)").unwrap();
+        assert_eq!((number, address, locations_count), (1, Some(0x0800_0130), 3));
+
+        let info_lines = [
+            "Num     Type           Disp Enb Address    What",
+            "1       breakpoint     keep y   <MULTIPLE>",
+            "1.1                         y   0x08000130 in foo at a.h:10",
+            "1.2                         y   0x08000150 in foo at a.h:10",
+            "1.3                         y-  0x08000172 in foo at a.h:10",
+            "2.1                         y   0x08000200 in bar at b.h:3",
+        ];
+        let locations: Vec<BreakpointLocation> = info_lines.iter()
+            .filter_map(|line| extract_breakpoint_location_from_response_line(line, number))
+            .collect();
+        assert_eq!(locations.len(), locations_count);
+        assert_eq!(locations.iter().map(|location| location.address).collect::<Vec<_>>(), [0x0800_0130, 0x0800_0150, 0x0800_0172]);
+        assert!(locations.iter().all(|location| location.enabled));
+    }
+
+    #[test]
+    fn single_location_breakpoint_is_parsed() {
+        let line = "Breakpoint 2 at 0x8009bc8: file main.c, line 118.";
+        assert_eq!(extract_breakpoint_from_response_line(line), Some((2, Some(0x0800_9bc8), 1)));
+    }
+
+    #[test]
+    fn short_restore_range_gives_restored_bytes() {
+        let line = "Restoring binary file /tmp/chunk_0.bin into memory (0x20000000 to 0x20000100)";
+        assert_eq!(extract_adresses_from_response_line(line), Some((0x2000_0000, 0x2000_0100)));
+    }
+
+    #[test]
+    fn restore_timeout_scales_with_measured_throughput() {
+        let timeouts = Timeouts::default();
+        assert_eq!(scaled_restore_timeout(&timeouts, None, 64 * 1024), timeouts.restore);
+        assert_eq!(scaled_restore_timeout(&timeouts, Some(10.0), 64 * 1024), Duration::from_millis(2560));
+        assert_eq!(scaled_restore_timeout(&timeouts, Some(10.0), 1024), RESTORE_TIMEOUT_FLOOR);
+        assert_eq!(scaled_restore_timeout(&timeouts, Some(10_000.0), 64 * 1024), RESTORE_TIMEOUT_CEILING);
+    }
+
+    #[test]
+    fn configured_restore_time_per_kb_overrides_measured_one() {
+        let timeouts = Timeouts { restore_per_kb: Some(Duration::from_millis(20)), ..Timeouts::default() };
+        assert_eq!(scaled_restore_timeout(&timeouts, None, 64 * 1024), Duration::from_millis(1280));
+        assert_eq!(scaled_restore_timeout(&timeouts, Some(10.0), 64 * 1024), Duration::from_millis(1280));
+    }
+}
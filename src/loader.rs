@@ -1,184 +1,2022 @@
+use std::collections::BTreeSet;
 use std::fmt::Debug;
+use std::ops::Range;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use std::time::Instant;
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
 
+use rayon::prelude::*;
+use regex::Regex;
 use tokio::io;
 use tokio::fs;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
-use crate::gdb::Gdb;
+use crate::error::{as_gdb_error, GdbError, ResultExt};
+use crate::gdb::{CallArg, Gdb};
 
-const TMP_WORKSPACE_DIR: &str = "tmp_bin_chunks";
+/// Name prefix of the per-run temporary workspace directories.
+const TMP_WORKSPACE_PREFIX: &str = "gdbloader_chunks";
 
-/// Returns the absolute path to the temporary workspace directory.
-fn get_abs_tmp_workspace_dir() -> PathBuf {
-    Path::new(env!("CARGO_MANIFEST_DIR")).join(TMP_WORKSPACE_DIR)
+/// Binary path meaning the image is read from the standard input.
+pub const STDIN_BINARY_PATH: &str = "-";
+
+/// Describes a flash memory which the firmware exposes a copy routine for.
+///
+/// The firmware must implement a copy function with the signature
+/// `uint32_t fn(uint32_t address, uint32_t bytes_count)` which copies `bytes_count`
+/// bytes from the RAM buffer to `address` and returns the Sum32 checksum of the copied data.
+/// The `address` passed is `address_base + flash offset`.
+///
+/// With [`UploadOptions::cumulative_checksum`] the copy function takes a third argument,
+/// `uint32_t fn(uint32_t address, uint32_t bytes_count, uint32_t seed)`, and returns
+/// `seed` plus the Sum32 checksum of the copied data, the running checksum of the image.
+///
+/// For `--verify-checksum-only` the firmware must also implement a checksum function with the
+/// same signature, `uint32_t fn(uint32_t address, uint32_t bytes_count)`, returning the Sum32
+/// checksum of `bytes_count` bytes of flash at `address` without modifying anything.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlashTarget {
+    /// Name of the target function copying RAM buffer content to flash.
+    pub copy_function_name: String,
+    /// Value added to every flash offset before passing it to the copy function.
+    pub address_base: u32,
+    /// Smallest erasable unit, chunk size and offsets should be its multiple.
+    pub sector_size: usize,
+    /// Total flash size, uploads exceeding it are rejected if known.
+    pub flash_size: Option<usize>,
+    /// Value returned by the copy function on failure, if it has one.
+    pub copy_error_value: Option<u32>,
+    /// Variable holding the copy status (0 = success), read only when a chunk checksum equals `copy_error_value`.
+    pub copy_status_variable: Option<String>,
+    /// Address of the copy function, it is called by address instead of by name if set.
+    pub copy_function_address: Option<u32>,
+    /// Type of the copy function used with `copy_function_address`.
+    pub copy_function_signature: String,
+    /// Name of the target function returning the checksum of a flash range.
+    pub checksum_function_name: String,
+    /// Width of the checksums returned by the copy and checksum functions.
+    pub checksum_width: ChecksumWidth,
+    /// Byte order of the checksums returned by the copy and checksum functions.
+    pub checksum_endianness: ChecksumEndianness,
+    /// Line the copy function prints after each copy, awaited after the call if set.
+    pub copy_confirmation: Option<CopyConfirmation>,
+    /// Address the flash is memory mapped at, flash is read back at `flash_base + flash offset`.
+    pub flash_base: Option<u32>,
+    /// What the copy function returns, with [`CopyReturns::Void`] chunks are not compared.
+    pub copy_returns: CopyReturns,
+}
+
+/// Confirmation line printed by the copy function through semihosting, e.g. `Wrote 65536 bytes at 0x200000`.
+///
+/// The named capture groups of the pattern are checked, both are optional, the values are decimal or `0x` hexadecimal:
+/// - `bytes`: The number of copied bytes, it must equal the requested count.
+/// - `checksum`: The checksum of the copied bytes, used instead of the value returned by the copy function.
+#[derive(Debug, Clone)]
+pub struct CopyConfirmation {
+    /// Pattern of the confirmation line.
+    pub pattern: Regex,
+    /// How long the line is awaited after the copy call returned.
+    pub timeout: Duration,
+}
+
+impl PartialEq for CopyConfirmation {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern.as_str() == other.pattern.as_str() && self.timeout == other.timeout
+    }
+}
+
+impl Eq for CopyConfirmation {}
+
+impl CopyConfirmation {
+    /// Tells whether the confirmation carries the checksum, so the value returned by the copy function is not used.
+    pub fn has_checksum(&self) -> bool {
+        self.pattern.capture_names().any(|name| name == Some("checksum"))
+    }
+
+    /// Extracts a named capture group value from a confirmation line.
+    ///
+    /// # Parameters
+    /// - `line`: The confirmation line matching the pattern.
+    /// - `group`: The name of the capture group.
+    ///
+    /// # Returns
+    /// An `io::Result` with the value, `None` if the group did not participate in the match,
+    /// or an error if it is not a number.
+    fn extract_value(&self, line: &str, group: &str) -> io::Result<Option<u32>> {
+        let Some(value_text) = self.pattern.captures(line).and_then(|captures| captures.name(group)) else {
+            return Ok(None);
+        };
+
+        let value_text = value_text.as_str();
+        match value_text.strip_prefix("0x").or_else(|| value_text.strip_prefix("0X")) {
+            Some(hex_digits) => u32::from_str_radix(hex_digits, 16),
+            None => value_text.parse(),
+        }
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Confirmation '{line}': invalid {group} '{value_text}': {e}")))
+    }
+}
+
+/// Width of the Sum32 checksum returned by the firmware, small copy routines may return
+/// a `uint16_t` or `uint8_t` sum, the host sum is truncated to the same width.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ChecksumWidth {
+    /// `uint8_t` checksum.
+    #[value(name = "8")]
+    W8,
+    /// `uint16_t` checksum.
+    #[value(name = "16")]
+    W16,
+    /// `uint32_t` checksum.
+    #[default]
+    #[value(name = "32")]
+    W32,
+}
+
+impl ChecksumWidth {
+    /// Truncates a checksum to the width, also dropping the sign extension of a negative value printed by GDB.
+    pub fn mask(self, checksum: u32) -> u32 {
+        match self {
+            ChecksumWidth::W8 => checksum & 0xFF,
+            ChecksumWidth::W16 => checksum & 0xFFFF,
+            ChecksumWidth::W32 => checksum,
+        }
+    }
+
+    /// Returns the width in bits.
+    pub fn bits(self) -> u32 {
+        match self {
+            ChecksumWidth::W8 => 8,
+            ChecksumWidth::W16 => 16,
+            ChecksumWidth::W32 => 32,
+        }
+    }
+}
+
+/// Byte order of the checksum returned by the firmware, independent of the data byte order.
+/// Some firmwares keep the sum in a big endian CRC register and return it as is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ChecksumEndianness {
+    /// The checksum is returned as a plain little endian value.
+    #[default]
+    Le,
+    /// The checksum bytes are swapped within the checksum width.
+    Be,
+}
+
+/// What the copy function returns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum CopyReturns {
+    /// The Sum32 checksum of the copied data, compared with the host one for every chunk.
+    #[default]
+    Checksum,
+    /// Nothing, the return value is not parsed and the chunks are verified only at the end, if at all.
+    Void,
+}
+
+/// Default type of the copy function called by address, `uint32_t` is unknown to GDB without debug info.
+pub const DEFAULT_COPY_FUNCTION_SIGNATURE: &str = "unsigned int (unsigned int, unsigned int)";
+
+impl FlashTarget {
+    /// External flash preset, the copy function receives offsets relative to the flash start.
+    pub fn external() -> Self {
+        Self {
+            copy_function_name: String::from("loader_copy_to_ext_flash"),
+            address_base: 0x0,
+            sector_size: 4 * 1024,
+            flash_size: None,
+            copy_error_value: None,
+            copy_status_variable: None,
+            copy_function_address: None,
+            copy_function_signature: String::from(DEFAULT_COPY_FUNCTION_SIGNATURE),
+            checksum_function_name: String::from("loader_checksum_ext_flash"),
+            checksum_width: ChecksumWidth::W32,
+            checksum_endianness: ChecksumEndianness::Le,
+            copy_confirmation: None,
+            flash_base: None,
+            copy_returns: CopyReturns::Checksum,
+        }
+    }
+
+    /// Internal flash preset, the copy function receives absolute addresses in the MCU flash bank.
+    pub fn internal() -> Self {
+        Self {
+            copy_function_name: String::from("loader_copy_to_int_flash"),
+            address_base: 0x0800_0000,
+            sector_size: 8 * 1024,
+            flash_size: None,
+            copy_error_value: None,
+            copy_status_variable: None,
+            copy_function_address: None,
+            copy_function_signature: String::from(DEFAULT_COPY_FUNCTION_SIGNATURE),
+            checksum_function_name: String::from("loader_checksum_int_flash"),
+            checksum_width: ChecksumWidth::W32,
+            checksum_endianness: ChecksumEndianness::Le,
+            copy_confirmation: None,
+            flash_base: Some(0x0800_0000),
+            copy_returns: CopyReturns::Checksum,
+        }
+    }
+
+    /// Calls the copy function, by address if known, otherwise by name.
+    ///
+    /// # Parameters
+    /// - `gdb`: A mutable reference to an active GDB connection.
+    /// - `flash_offset`: Offset counted from the beginning of the flash.
+    /// - `bytes_count`: The number of bytes to copy from the RAM buffer.
+    /// - `seed`: The running checksum passed as the third argument, for cumulative checksums only.
+    ///
+    /// # Returns
+    /// An `io::Result` with the checksum returned by the copy function, or printed in its confirmation,
+    /// `None` if the copy function returns void and the confirmation carries no checksum.
+    async fn call_copy(&self, gdb: &mut Gdb, flash_offset: usize, bytes_count: usize, seed: Option<u32>) -> io::Result<Option<u32>> {
+        let address = self.copy_address(flash_offset)?;
+        let mut args = vec![CallArg::U32(address), CallArg::U32(bytes_count as u32)];
+        args.extend(seed.map(CallArg::U32));
+
+        let confirmation_has_checksum = self.copy_confirmation.as_ref().is_some_and(CopyConfirmation::has_checksum);
+        let returns_checksum = self.copy_returns == CopyReturns::Checksum && !confirmation_has_checksum;
+        let checksum = match (self.copy_function_address, returns_checksum) {
+            (Some(copy_function_address), true) => gdb.call_at_address_resulting_u32(
+                copy_function_address,
+                &self.copy_function_signature,
+                &args
+            ).await
+            .with_context(|| format!("copy function at {copy_function_address:#x} call failed"))
+            .map(Some),
+            (None, true) => gdb.call_with_args_resulting_u32(&self.copy_function_name, &args).await
+                .with_context(|| format!("copy function '{}' call failed", self.copy_function_name))
+                .map(Some),
+            // The returned value, e.g. a status, is not the checksum, it comes with the confirmation if at all.
+            (Some(copy_function_address), false) => gdb.call_at_address(copy_function_address, &self.copy_function_signature, &args).await
+                .with_context(|| format!("copy function at {copy_function_address:#x} call failed"))
+                .map(|_| self.fallback_checksum()),
+            (None, false) => gdb.call_typed(&self.copy_function_name, &args, self.copy_returns == CopyReturns::Checksum).await
+                .with_context(|| format!("copy function '{}' call failed", self.copy_function_name))
+                .map(|_| self.fallback_checksum()),
+        }?;
+
+        let checksum = match &self.copy_confirmation {
+            Some(confirmation) => self.await_copy_confirmation(gdb, confirmation, bytes_count).await?.or(checksum),
+            None => checksum,
+        };
+
+        Ok(checksum.map(|checksum| self.decode_checksum(checksum)))
+    }
+
+    /// Returns the checksum assumed when the confirmation line lacks it, zero fails the comparison
+    /// unless the copy function returns void and nothing is compared.
+    fn fallback_checksum(&self) -> Option<u32> {
+        (self.copy_returns == CopyReturns::Checksum).then_some(0)
+    }
+
+    /// Awaits the confirmation line of a copy and checks the copied bytes count.
+    ///
+    /// # Parameters
+    /// - `gdb`: A mutable reference to an active GDB connection.
+    /// - `confirmation`: The confirmation pattern and timeout.
+    /// - `bytes_count`: The number of bytes passed to the copy function.
+    ///
+    /// # Returns
+    /// An `io::Result` with the confirmed checksum, `None` if the pattern has no `checksum` group.
+    async fn await_copy_confirmation(&self, gdb: &mut Gdb, confirmation: &CopyConfirmation, bytes_count: usize) -> io::Result<Option<u32>> {
+        let line = gdb.await_target_output(&confirmation.pattern, confirmation.timeout).await
+            .with_context(|| "copy confirmation missing")?;
+        log::debug!("Copy confirmed: {line}");
+
+        if let Some(confirmed_bytes) = confirmation.extract_value(&line, "bytes")? {
+            if confirmed_bytes as usize != bytes_count {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Copy confirmed {confirmed_bytes} B instead of {bytes_count} B: {line}")
+                ));
+            }
+        }
+
+        confirmation.extract_value(&line, "checksum")
+    }
+
+    /// Converts a checksum returned by the target to the host representation, truncated to
+    /// the checksum width and with its bytes swapped for [`ChecksumEndianness::Be`].
+    ///
+    /// # Parameters
+    /// - `checksum`: The checksum as printed by GDB.
+    ///
+    /// # Returns
+    /// The checksum comparable with the truncated host Sum32.
+    fn decode_checksum(&self, checksum: u32) -> u32 {
+        let checksum = self.checksum_width.mask(checksum);
+        match (self.checksum_endianness, self.checksum_width) {
+            (ChecksumEndianness::Le, _) | (ChecksumEndianness::Be, ChecksumWidth::W8) => checksum,
+            (ChecksumEndianness::Be, ChecksumWidth::W16) => (checksum as u16).swap_bytes() as u32,
+            (ChecksumEndianness::Be, ChecksumWidth::W32) => checksum.swap_bytes(),
+        }
+    }
+
+    /// Calls the checksum function over a flash range.
+    ///
+    /// # Parameters
+    /// - `gdb`: A mutable reference to an active GDB connection.
+    /// - `flash_offset`: Offset counted from the beginning of the flash.
+    /// - `bytes_count`: The number of bytes to checksum.
+    ///
+    /// # Returns
+    /// An `io::Result<u32>` with the checksum returned by the checksum function.
+    async fn call_checksum(&self, gdb: &mut Gdb, flash_offset: usize, bytes_count: usize) -> io::Result<u32> {
+        let address = self.copy_address(flash_offset)?;
+        gdb.call_with_u32_u32_resulting_u32(&self.checksum_function_name, address, bytes_count as u32, true).await
+            .with_context(|| format!("checksum function '{}' call failed", self.checksum_function_name))
+            .map(|checksum| self.decode_checksum(checksum))
+    }
+
+    /// Translates a flash offset into the address passed to the copy function.
+    ///
+    /// # Parameters
+    /// - `flash_offset`: Offset counted from the beginning of the flash.
+    ///
+    /// # Returns
+    /// An `io::Result<u32>` with the address or an error if it does not fit 32 bits.
+    pub(crate) fn copy_address(&self, flash_offset: usize) -> io::Result<u32> {
+        u32::try_from(flash_offset)
+            .ok()
+            .and_then(|offset| self.address_base.checked_add(offset))
+            .ok_or_else(|| io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Flash offset {flash_offset:#x} out of 32-bit address range")
+            ))
+    }
+
+    /// Translates a flash offset into the memory mapped address the flash is read back at,
+    /// which differs from the copy address if the copy function takes offsets, e.g. for QSPI flash.
+    ///
+    /// # Parameters
+    /// - `flash_offset`: Offset counted from the beginning of the flash.
+    ///
+    /// # Returns
+    /// An `io::Result<u32>` with the address, or an error if the flash base is unknown or the address does not fit 32 bits.
+    pub(crate) fn readback_address(&self, flash_offset: usize) -> io::Result<u32> {
+        let flash_base = self.flash_base.ok_or_else(|| io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Reading flash back needs its memory mapped address, pass --flash-base"
+        ))?;
+
+        u32::try_from(flash_offset)
+            .ok()
+            .and_then(|offset| flash_base.checked_add(offset))
+            .ok_or_else(|| io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Flash offset {flash_offset:#x} out of 32-bit address range")
+            ))
+    }
+}
+
+/// RAM buffer on the target the chunks are written into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RamBuffer {
+    /// Buffer resolved by its symbol name, e.g. `loader_ram_buffer`.
+    Symbol(String),
+    /// Buffer at a fixed address, for firmwares without symbols.
+    Address(u32),
+}
+
+impl RamBuffer {
+    /// Returns the GDB expression of the buffer start address.
+    fn location(&self) -> String {
+        match self {
+            RamBuffer::Symbol(name) => format!("&{name}"),
+            RamBuffer::Address(address) => format!("{address:#x}"),
+        }
+    }
+
+    /// Queries the buffer size from the target, only possible for buffers with a symbol.
+    async fn query_size(&self, gdb: &mut Gdb) -> io::Result<usize> {
+        match self {
+            RamBuffer::Symbol(name) => {
+                let size = gdb.read_variable_u32(&format!("sizeof({name})")).await? as usize;
+                if size == 0 {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, format!("RAM buffer '{name}' has zero size")));
+                }
+                Ok(size)
+            },
+            RamBuffer::Address(address) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("Size of RAM buffer at {address:#x} unknown without a symbol")
+            )),
+        }
+    }
+}
+
+/// Calculates the Sum32 checksum, the wrapping sum of all bytes, as the firmware copy function does.
+pub(crate) fn checksum_sum32(data: &[u8]) -> u32 {
+    data.iter().fold(0u32, |acc, &v| acc.wrapping_add(v as u32))
+}
+
+/// Size of the data from which chunk checksums are computed up front in parallel, smaller data gains nothing.
+const PARALLEL_CHECKSUM_THRESHOLD: usize = 4 * 1024 * 1024;
+
+/// Computes the Sum32 checksum of every planned chunk in parallel, so the upload loop only waits for GDB.
+///
+/// # Parameters
+/// - `plan`: The upload plan with the chunk ranges.
+/// - `file_data`: The (padded) data the plan was made for.
+///
+/// # Returns
+/// The checksums in the order of `plan.chunks`.
+fn precompute_chunk_checksums(plan: &UploadPlan, file_data: &[u8]) -> Vec<u32> {
+    plan.chunks.par_iter()
+        .map(|chunk| checksum_sum32(&file_data[chunk.file_range.clone()]))
+        .collect()
+}
+
+/// Queries the flash geometry from the target using firmware getter functions.
+///
+/// The firmware may implement `uint32_t fn(void)` getters returning the sector size
+/// and the total flash size in bytes. Values which cannot be read keep the ones
+/// already present in `flash_target`.
+///
+/// # Parameters
+/// - `gdb`: A mutable reference to an active GDB connection, the target must be halted.
+/// - `sector_size_function_name`: The name of the sector size getter.
+/// - `flash_size_function_name`: The name of the flash size getter.
+/// - `flash_target`: The flash description updated with the queried values.
+pub async fn query_flash_geometry(
+    gdb: &mut Gdb,
+    sector_size_function_name: &str,
+    flash_size_function_name: &str,
+    flash_target: &mut FlashTarget
+) {
+    match gdb.call_resulting_u32(sector_size_function_name).await {
+        Ok(sector_size) if sector_size > 0 => {
+            log::info!("Target reported sector size {sector_size} B.");
+            flash_target.sector_size = sector_size as usize;
+        },
+        Ok(_) => log::warn!("Target reported zero sector size, keeping {} B.", flash_target.sector_size),
+        Err(e) => log::warn!("Sector size query with '{sector_size_function_name}' failed: {e}, keeping {} B.", flash_target.sector_size),
+    }
+
+    match gdb.call_resulting_u32(flash_size_function_name).await {
+        Ok(flash_size) if flash_size > 0 => {
+            log::info!("Target reported flash size {flash_size} B.");
+            flash_target.flash_size = Some(flash_size as usize);
+        },
+        Ok(_) => log::warn!("Target reported zero flash size, keeping {:?}.", flash_target.flash_size),
+        Err(e) => log::warn!("Flash size query with '{flash_size_function_name}' failed: {e}, keeping {:?}.", flash_target.flash_size),
+    }
+}
+
+/// Checks a flash range against a known checksum, without the source image and without reading it back.
+///
+/// # Parameters
+/// - `gdb`: A mutable reference to an active GDB connection, the target must be halted.
+/// - `flash_target`: The flash description with the checksum function and address base.
+/// - `flash_offset`: Offset of the range counted from the beginning of the flash.
+/// - `length`: The number of bytes of the range.
+/// - `expected_checksum`: The previously recorded checksum of the range.
+///
+/// # Returns
+/// An `io::Result<u32>` with the target checksum, or a checksum mismatch error if it differs from the expected one.
+pub async fn verify_flash_checksum(
+    gdb: &mut Gdb,
+    flash_target: &FlashTarget,
+    flash_offset: usize,
+    length: usize,
+    expected_checksum: u32
+) -> io::Result<u32> {
+    if let Some(flash_size) = flash_target.flash_size {
+        if flash_offset + length > flash_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Range {flash_offset:#x}+{length:#x} exceeds flash size {flash_size:#x}")
+            ));
+        }
+    }
+
+    let expected_checksum = flash_target.checksum_width.mask(expected_checksum);
+    let target_checksum = flash_target.call_checksum(gdb, flash_offset, length).await?;
+    log::info!("Got target_checksum={target_checksum:#x}, expected_checksum={expected_checksum:#x}.");
+
+    if target_checksum != expected_checksum {
+        return Err(GdbError::ChecksumMismatch { host_checksum: expected_checksum, target_checksum }.into());
+    }
+
+    Ok(target_checksum)
+}
+
+/// Verifies an image written to a staging region and activates it with the firmware commit function,
+/// the last step of a two-phase upload.
+///
+/// The firmware must implement `uint32_t fn(uint32_t staging_address, uint32_t target_address, uint32_t bytes_count)`
+/// which activates the staged image atomically, e.g. by copying it over the target region or by switching
+/// the active partition, and returns 0 on success. The addresses are built as for the copy function.
+/// The commit function is not called unless the checksum of the whole staged image matches.
+///
+/// # Parameters
+/// - `gdb`: A mutable reference to an active GDB connection.
+/// - `flash_target`: The flash description with the checksum function and address base.
+/// - `staging_offset`: The flash offset the image was written to.
+/// - `target_offset`: The flash offset the image is activated at.
+/// - `data`: The staged image.
+/// - `commit_function_name`: The name of the commit function.
+///
+/// # Returns
+/// An `io::Result<()>` with an `InvalidInput` error if the regions overlap or the target region exceeds the flash,
+/// a checksum mismatch error if the staged image differs, or an error if the commit function fails.
+pub async fn commit_staged_image(
+    gdb: &mut Gdb,
+    flash_target: &FlashTarget,
+    staging_offset: usize,
+    target_offset: usize,
+    data: &[u8],
+    commit_function_name: &str
+) -> io::Result<()> {
+    ensure_staging_fits(flash_target, staging_offset, target_offset, data.len())?;
+
+    log::info!("Verifying staged image of {} B at flash offset {staging_offset:#x}...", data.len());
+    verify_flash_checksum(gdb, flash_target, staging_offset, data.len(), checksum_sum32(data)).await
+        .with_context(|| format!("staged image at flash offset {staging_offset:#x}"))?;
+
+    log::info!("Committing staged image to flash offset {target_offset:#x}...");
+    let args = [
+        CallArg::U32(flash_target.copy_address(staging_offset)?),
+        CallArg::U32(flash_target.copy_address(target_offset)?),
+        CallArg::U32(data.len() as u32),
+    ];
+    let status = gdb.call_with_args_resulting_u32(commit_function_name, &args).await
+        .with_context(|| format!("commit function '{commit_function_name}' call failed"))?;
+    if status != 0 {
+        return Err(io::Error::other(format!("Commit function '{commit_function_name}' reported status {status}")));
+    }
+
+    Ok(())
+}
+
+/// Checks the regions of a two-phase upload before anything is written, the image must not be staged
+/// over the region it is committed to and the target region must fit the flash.
+///
+/// # Parameters
+/// - `flash_target`: The flash description with the flash size.
+/// - `staging_offset`: The flash offset the image is written to.
+/// - `target_offset`: The flash offset the image is activated at.
+/// - `length`: The image size in bytes.
+///
+/// # Returns
+/// An `io::Result<()>` with an `InvalidInput` error if the regions overlap or the target region exceeds the flash.
+pub fn ensure_staging_fits(flash_target: &FlashTarget, staging_offset: usize, target_offset: usize, length: usize) -> io::Result<()> {
+    let staging_end = staging_offset + length;
+    let target_end = target_offset + length;
+    if staging_offset < target_end && target_offset < staging_end {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Staging region {staging_offset:#x}..{staging_end:#x} overlaps the target region {target_offset:#x}..{target_end:#x}")
+        ));
+    }
+
+    if let Some(flash_size) = flash_target.flash_size {
+        if target_end > flash_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Target region {target_offset:#x}..{target_end:#x} exceeds flash size {flash_size} B")
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Data extended to whole sectors by read-modify-write, see [`read_modify_write`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadModifyWrite {
+    /// Flash offset of the extended data, the original offset aligned down to a sector boundary.
+    pub flash_start_offset: usize,
+    /// The new data surrounded by the flash content kept in the partial sectors.
+    pub data: Vec<u8>,
+    /// Flash offsets of the partially written sectors, the first and the last one.
+    pub partial_sectors: Vec<usize>,
+}
+
+impl ReadModifyWrite {
+    /// Checks the merged sectors in flash against the checksum of the merged data.
+    ///
+    /// # Parameters
+    /// - `gdb`: A mutable reference to an active GDB connection.
+    /// - `flash_target`: The flash description with the checksum function and sector size.
+    ///
+    /// # Returns
+    /// An `io::Result<()>` with a checksum mismatch error if a merged sector differs.
+    pub async fn verify(&self, gdb: &mut Gdb, flash_target: &FlashTarget) -> io::Result<()> {
+        for &sector_offset in &self.partial_sectors {
+            let data_start = sector_offset - self.flash_start_offset;
+            let expected_checksum = checksum_sum32(&self.data[data_start..data_start + flash_target.sector_size]);
+            log::info!("Verifying merged sector at flash offset {sector_offset:#x}...");
+            verify_flash_checksum(gdb, flash_target, sector_offset, flash_target.sector_size, expected_checksum).await
+                .with_context(|| format!("merged sector at flash offset {sector_offset:#x}"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Extends the data to whole sectors with the current flash content, so writing it leaves
+/// the bytes around it intact.
+///
+/// The head of the first and the tail of the last touched sector are read back from flash at its
+/// memory mapped address, see [`FlashTarget::readback_address`], so the flash base must be known
+/// with `--flash-base`, and the data is written as whole sectors afterwards. Data already aligned
+/// to sectors is returned unchanged.
+///
+/// # Parameters
+/// - `gdb`: A mutable reference to an active GDB connection, the target must be halted.
+/// - `flash_target`: The flash description with the sector size and address base.
+/// - `flash_start_offset`: The flash offset the data is meant to be written to.
+/// - `data`: The new data.
+///
+/// # Returns
+/// An `io::Result` with the extended data and its aligned offset.
+pub async fn read_modify_write(
+    gdb: &mut Gdb,
+    flash_target: &FlashTarget,
+    flash_start_offset: usize,
+    data: &[u8]
+) -> io::Result<ReadModifyWrite> {
+    let sector_size = flash_target.sector_size;
+    let data_end = flash_start_offset + data.len();
+    let aligned_start = flash_start_offset - flash_start_offset % sector_size;
+    let aligned_end = data_end.next_multiple_of(sector_size);
+
+    let head_size = flash_start_offset - aligned_start;
+    let tail_size = aligned_end - data_end;
+
+    let mut merged = Vec::with_capacity(aligned_end - aligned_start);
+    if head_size > 0 {
+        log::info!("Reading {head_size} B of flash before offset {flash_start_offset:#x}...");
+        merged.extend(gdb.read_memory(flash_target.readback_address(aligned_start)?, head_size).await
+            .with_context(|| format!("reading flash at offset {aligned_start:#x} failed"))?);
+    }
+    merged.extend_from_slice(data);
+    if tail_size > 0 {
+        log::info!("Reading {tail_size} B of flash after offset {data_end:#x}...");
+        merged.extend(gdb.read_memory(flash_target.readback_address(data_end)?, tail_size).await
+            .with_context(|| format!("reading flash at offset {data_end:#x} failed"))?);
+    }
+
+    if merged.len() != aligned_end - aligned_start {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Flash read back is shorter than requested"));
+    }
+
+    let mut partial_sectors = Vec::new();
+    if head_size > 0 {
+        partial_sectors.push(aligned_start);
+    }
+    if tail_size > 0 && !partial_sectors.contains(&(aligned_end - sector_size)) {
+        partial_sectors.push(aligned_end - sector_size);
+    }
+
+    Ok(ReadModifyWrite { flash_start_offset: aligned_start, data: merged, partial_sectors })
+}
+
+/// Flash region reserved for non-destructive tests, uploads must not write into it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScratchRegion {
+    /// Offset of the region counted from the beginning of the flash.
+    pub offset: usize,
+    /// Size of the region in bytes.
+    pub size: usize,
+}
+
+impl ScratchRegion {
+    /// Fails if the range `flash_offset..flash_offset + length` overlaps the region.
+    ///
+    /// # Parameters
+    /// - `flash_offset`: Offset of the range counted from the beginning of the flash.
+    /// - `length`: The number of bytes of the range.
+    ///
+    /// # Returns
+    /// An `io::Result<()>` with an error of kind `InvalidInput` on overlap.
+    pub fn ensure_no_overlap(&self, flash_offset: usize, length: usize) -> io::Result<()> {
+        let overlaps = flash_offset < self.offset + self.size && self.offset < flash_offset + length;
+        if overlaps {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Range {flash_offset:#x}..{:#x} overlaps the scratch region {:#x}..{:#x}",
+                    flash_offset + length, self.offset, self.offset + self.size
+                )
+            ));
+        }
+        Ok(())
+    }
+
+    /// Fails if `length` bytes do not fit the region.
+    fn ensure_fits(&self, length: usize) -> io::Result<()> {
+        if length > self.size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{length} B do not fit the scratch region of {} B", self.size)
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Describes how failed chunks are retried.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Number of additional attempts after the first failed one.
+    pub max_retries: usize,
+    /// Delay before the first retry, doubled on every following attempt.
+    pub backoff: Duration,
+    /// Upper bound of the delay between attempts.
+    pub max_backoff: Duration,
+    /// Issue `monitor halt` before retrying to resynchronize the core state.
+    pub halt_before_retry: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_millis(2000),
+            halt_before_retry: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the delay before retry number `attempt` (counted from 0).
+    fn backoff_for(&self, attempt: usize) -> Duration {
+        let factor = 1u32.checked_shl(attempt as u32).unwrap_or(u32::MAX);
+        self.backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+/// Way chunks are written into the RAM buffer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum RamWriteMethod {
+    /// Saves every chunk to a temporary file and loads it with `restore`.
+    #[default]
+    Restore,
+    /// Sends the chunk bytes with the GDB/MI `-data-write-memory-bytes` command,
+    /// falls back to `restore` if GDB lacks the MI interpreter.
+    Mi,
+}
+
+/// Part of a chunk written into the RAM buffer and copied to flash at once.
+struct ChunkPart<'a> {
+    /// Offset of the part within the chunk.
+    offset: usize,
+    /// Bytes of the part.
+    bytes: &'a [u8],
+    /// Temporary file with the part, `None` if written with [`RamWriteMethod::Mi`].
+    file_path: Option<PathBuf>,
+}
+
+/// Action taken when a chunk checksum does not match.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum MismatchAction {
+    /// Only report the checksums.
+    #[default]
+    Fail,
+    /// Read the flash region back and save both sides to files for inspection.
+    Dump,
+}
+
+/// Bounds of adaptive chunk sizing, sizes are kept multiples of the sector size.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AdaptiveChunking {
+    /// Smallest chunk size, the sector size if `None`.
+    pub min_chunk: Option<usize>,
+    /// Largest chunk size, the RAM buffer size if `None`.
+    pub max_chunk: Option<usize>,
+}
+
+/// Number of consecutive successful chunks after which adaptive sizing doubles the chunk size.
+const ADAPTIVE_GROW_AFTER: usize = 4;
+
+/// Sampled verification of written chunks with the flash checksum function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifySample {
+    /// Share of chunks verified in percent, the first and last chunk are verified always.
+    pub percent: f64,
+    /// Seed of the chunk selection, the same seed selects the same chunks.
+    pub seed: u64,
+}
+
+/// SplitMix64 generator, enough for reproducible sampling without a dependency.
+struct SampleRng(u64);
+
+impl SampleRng {
+    /// Returns a uniformly distributed number in `0.0..1.0`.
+    fn next_f64(&mut self) -> f64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Selects the chunks verified by sampling: the first, the last and a random share of the others.
+///
+/// # Parameters
+/// - `chunks_count`: The number of written chunks.
+/// - `sample`: The share and seed of the selection.
+///
+/// # Returns
+/// The ascending indices of the selected chunks.
+fn select_sampled_chunks(chunks_count: usize, sample: &VerifySample) -> Vec<usize> {
+    let mut rng = SampleRng(sample.seed);
+    (0..chunks_count)
+        .filter(|&chunk_idx| {
+            // Draw for every chunk, so the selection depends on the seed and the count only.
+            let drawn = rng.next_f64() * 100.0 < sample.percent;
+            chunk_idx == 0 || chunk_idx + 1 == chunks_count || drawn
+        })
+        .collect()
+}
+
+/// Words of the written data read back one by one after the upload, see [`UploadOptions::spot_check`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpotCheck {
+    /// Number of words read back.
+    pub count: usize,
+    /// Seed of the word selection, the same seed selects the same words.
+    pub seed: u64,
+}
+
+/// Size of a word read back by the spot check.
+const SPOT_CHECK_WORD_SIZE: usize = 4;
+
+/// Selects the offsets of the spot checked words, all of them if there are fewer than requested.
+///
+/// # Parameters
+/// - `length`: The number of written bytes.
+/// - `spot_check`: The number of words and the seed of the selection.
+///
+/// # Returns
+/// The ascending, word aligned offsets within the written data.
+fn select_spot_check_offsets(length: usize, spot_check: &SpotCheck) -> Vec<usize> {
+    let words_count = length.div_ceil(SPOT_CHECK_WORD_SIZE);
+    if spot_check.count >= words_count {
+        return (0..words_count).map(|word_idx| word_idx * SPOT_CHECK_WORD_SIZE).collect();
+    }
+
+    let mut rng = SampleRng(spot_check.seed);
+    let mut offsets = BTreeSet::new();
+    while offsets.len() < spot_check.count {
+        let word_idx = ((rng.next_f64() * words_count as f64) as usize).min(words_count - 1);
+        offsets.insert(word_idx * SPOT_CHECK_WORD_SIZE);
+    }
+    offsets.into_iter().collect()
+}
+
+/// Reads randomly selected words of the written data back from flash one by one and compares them with the host data.
+///
+/// Sum32 does not depend on the byte order, so a copy function writing to a wrong address still returns
+/// matching checksums, a read of the word at its expected address catches that.
+///
+/// # Parameters
+/// - `gdb`: A mutable reference to an active GDB connection.
+/// - `flash_target`: The flash description with the flash base.
+/// - `flash_start_offset`: The flash offset the data was written to.
+/// - `data`: The written data.
+/// - `spot_check`: The number of words and the seed of the selection.
+///
+/// # Returns
+/// An `io::Result` with the number of checked words, or an error listing the address of every mismatching word.
+async fn spot_check_words(
+    gdb: &mut Gdb,
+    flash_target: &FlashTarget,
+    flash_start_offset: usize,
+    data: &[u8],
+    spot_check: &SpotCheck
+) -> io::Result<usize> {
+    let offsets = select_spot_check_offsets(data.len(), spot_check);
+    log::info!("Spot checking {} words, seed {}.", offsets.len(), spot_check.seed);
+
+    let mut mismatches = Vec::new();
+    for &offset in &offsets {
+        let host_bytes = &data[offset..(offset + SPOT_CHECK_WORD_SIZE).min(data.len())];
+        let target_bytes = read_flash(gdb, flash_target, flash_start_offset + offset, host_bytes.len(), None).await
+            .with_context(|| format!("spot check at flash offset {:#x}", flash_start_offset + offset))?;
+        if target_bytes != host_bytes {
+            let address = flash_target.readback_address(flash_start_offset + offset)?;
+            mismatches.push(format!("{address:#x}: host={host_bytes:02x?} target={target_bytes:02x?}"));
+        }
+    }
+
+    if !mismatches.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Spot check mismatched {} of {} words, {}", mismatches.len(), offsets.len(), mismatches.join(", "))
+        ));
+    }
+
+    Ok(offsets.len())
+}
+
+/// Options tuning the upload procedure.
+#[derive(Debug, Clone, Default)]
+pub struct UploadOptions {
+    /// Retry policy applied to every chunk.
+    pub retry: RetryPolicy,
+    /// Pause between chunks, trades throughput for reliability on finicky hardware.
+    pub chunk_delay: Duration,
+    /// Byte used to pad the data so it ends at a sector boundary, no padding if `None`.
+    pub pad_last: Option<u8>,
+    /// Way chunks are written into the RAM buffer.
+    pub ram_write: RamWriteMethod,
+    /// Action taken when a chunk checksum does not match.
+    pub on_mismatch: MismatchAction,
+    /// Split chunks larger than the RAM buffer into buffer sized parts, verified with a single checksum.
+    pub auto_split: bool,
+    /// Idle interval after which a keep-alive packet is sent while waiting between chunks, disabled if `None`.
+    pub keep_alive: Option<Duration>,
+    /// Halve the chunk size on failures and double it after a streak of successes, disabled if `None`.
+    pub adaptive: Option<AdaptiveChunking>,
+    /// The copy function is seeded with the running checksum of the previous chunks and returns the updated one.
+    pub cumulative_checksum: bool,
+    /// Verify a sample of the chunks with the flash checksum function after writing, disabled if `None`.
+    pub verify_sample: Option<VerifySample>,
+    /// Region the upload must not write into, unchecked if `None`.
+    pub scratch: Option<ScratchRegion>,
+    /// Compare every chunk with the flash checksum function first and write only the differing ones.
+    pub resume_verify: bool,
+    /// Program page size of the flash, no copy call crosses a page boundary if set.
+    pub program_page_size: Option<usize>,
+    /// Time budget of the upload, once exceeded it stops at the next chunk boundary, unlimited if `None`.
+    pub max_duration: Option<Duration>,
+    /// Skip chunks of only `0xFF` bytes, valid only if the flash is erased.
+    pub skip_blank: bool,
+    /// Read the RAM buffer back after every write and compare it with the part before the copy function runs.
+    pub verify_ram: bool,
+    /// Size of the reads when flash is read back for verification, independent of the chunk size,
+    /// e.g. larger for memory mapped QSPI flash, a whole chunk per read if `None`.
+    pub verify_read_chunk_size: Option<usize>,
+    /// Log a chunk still mismatching after its retries and go on with the next one, the upload fails at the end
+    /// with all mismatching chunks listed.
+    pub continue_on_mismatch: bool,
+    /// Read random words of the written data back from flash after the upload, disabled if `None`.
+    pub spot_check: Option<SpotCheck>,
+}
+
+/// Summary of a finished upload.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UploadStats {
+    /// Number of chunks written.
+    pub chunks_count: usize,
+    /// Number of bytes written, including padding.
+    pub bytes_transferred: usize,
+    /// Number of chunk retries.
+    pub retries: usize,
+    /// Chunk size in use at the end, differs from the requested one with adaptive sizing.
+    pub chunk_size: usize,
+    /// Warnings emitted during the upload, they are logged as well.
+    pub warnings: Vec<String>,
+    /// Indices of the chunks verified by sampling.
+    pub verified_chunks: Vec<usize>,
+    /// Seed the verified chunks and the spot checked words were selected with, `None` without sampling.
+    pub verify_seed: Option<u64>,
+    /// Number of chunks skipped as flash already held them, see [`UploadOptions::resume_verify`].
+    pub skipped_chunks: usize,
+    /// Whether the upload stopped early as [`UploadOptions::max_duration`] ran out, `chunks_count` chunks were written.
+    pub incomplete: bool,
+    /// Number of chunks planned, the last estimate with adaptive sizing.
+    pub planned_chunks_count: usize,
+    /// Number of blank chunks skipped, see [`UploadOptions::skip_blank`].
+    pub blank_chunks: usize,
+    /// Duration of the upload, from the first chunk until the verification ended.
+    pub elapsed: Duration,
+    /// Number of chunks written without a checksum to compare, see [`CopyReturns::Void`].
+    pub unverified_chunks: usize,
+    /// Number of words read back by the spot check, see [`UploadOptions::spot_check`].
+    pub spot_checked_words: usize,
+}
+
+impl UploadStats {
+    /// Logs a warning and records it.
+    fn warn(&mut self, message: String) {
+        log::warn!("{message}");
+        self.warnings.push(message);
+    }
+}
+
+/// Outcome of a single chunk, passed to the per-chunk handler of [`execute_plan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkResult {
+    /// Index of the chunk.
+    pub index: usize,
+    /// Offset in flash the chunk was written to.
+    pub flash_offset: usize,
+    /// Size of the chunk in bytes.
+    pub size: usize,
+    /// Checksum computed on the host, truncated to the checksum width.
+    pub host_checksum: u32,
+    /// Checksum reported by the target, `None` for a skipped blank chunk.
+    pub target_checksum: Option<u32>,
+    /// Number of retries of the chunk.
+    pub retries: usize,
+    /// Time spent on the chunk, including retries.
+    pub duration: Duration,
+    /// Whether the chunk was skipped as blank, see [`UploadOptions::skip_blank`].
+    pub skipped_blank: bool,
+    /// Whether the chunk was skipped as flash already held it, see [`UploadOptions::resume_verify`].
+    pub skipped_written: bool,
+}
+
+/// Totals of the upload after a chunk, passed to the per-chunk handler of [`execute_plan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadProgress {
+    /// Number of chunks in total, an estimate with adaptive sizing.
+    pub chunks_count: usize,
+    /// Number of bytes done, including skipped chunks.
+    pub bytes_done: usize,
+    /// Number of bytes in total.
+    pub total_bytes: usize,
+    /// Start time of the upload in milliseconds since the Unix epoch.
+    pub start_timestamp_ms: u128,
+}
+
+/// Adapts a handler taking the chunk index, chunks count, bytes done, total bytes and the start timestamp,
+/// the per-chunk handler signature before [`ChunkResult`], to the current one.
+///
+/// # Parameters
+/// - `handler`: The handler with scalar arguments.
+///
+/// # Returns
+/// A per-chunk handler for [`execute_plan`].
+pub fn legacy_chunk_handler<H>(handler: H) -> impl Fn(&ChunkResult, &UploadProgress) + 'static
+where
+    H: Fn(usize, usize, usize, usize, u128) + 'static
+{
+    move |chunk, progress| handler(
+        chunk.index,
+        progress.chunks_count,
+        progress.bytes_done,
+        progress.total_bytes,
+        progress.start_timestamp_ms
+    )
+}
+
+/// Waits on the host, keeping the link alive meanwhile if enabled.
+///
+/// # Parameters
+/// - `gdb`: A mutable reference to an active GDB connection.
+/// - `duration`: The time to wait.
+/// - `keep_alive`: The keep-alive idle interval, plain sleep if `None`.
+///
+/// # Returns
+/// An `io::Result<()>` with an error if a keep-alive packet fails.
+async fn wait_keeping_alive(gdb: &mut Gdb, duration: Duration, keep_alive: Option<Duration>) -> io::Result<()> {
+    let Some(idle_interval) = keep_alive else {
+        tokio::time::sleep(duration).await;
+        return Ok(());
+    };
+
+    let deadline = tokio::time::Instant::now() + duration;
+    loop {
+        gdb.keep_alive(idle_interval).await?;
+        let now = tokio::time::Instant::now();
+        if now >= deadline {
+            return Ok(());
+        }
+        tokio::time::sleep((deadline - now).min(idle_interval)).await;
+    }
+}
+
+/// Temporary directory of a single run holding the chunk files loaded with `restore`.
+///
+/// The directory and file names carry a per-run token made of the process id and a counter,
+/// so concurrent runs, e.g. in a parallel CI matrix, never share files. The directory is
+/// removed when the workspace is dropped, leaving files of other runs untouched.
+struct TmpWorkspace {
+    /// Absolute path to the directory.
+    dir: PathBuf,
+    /// Token unique to this run.
+    token: String,
+}
+
+impl TmpWorkspace {
+    /// Creates the directory of a new workspace under the system temporary directory.
+    ///
+    /// # Returns
+    /// An `io::Result<TmpWorkspace>` with an error if the directory cannot be created.
+    async fn create() -> io::Result<Self> {
+        static RUN_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let token = format!("{}_{}", std::process::id(), RUN_COUNTER.fetch_add(1, Ordering::Relaxed));
+        let dir = std::env::temp_dir().join(format!("{TMP_WORKSPACE_PREFIX}_{token}"));
+        log::debug!("Preparing tmp workspace at absolute path: {:?}", dir);
+
+        // A leftover of a crashed run with a reused process id is ours to replace.
+        if fs::try_exists(&dir).await? {
+            log::debug!("Preparing tmp workspace - erasing stale directory...");
+            fs::remove_dir_all(&dir).await?;
+        }
+
+        fs::create_dir_all(&dir).await
+            .with_context(|| format!("creating tmp workspace {dir:?} failed"))?;
+        log::debug!("Preparing tmp workspace done!");
+
+        Ok(Self { dir, token })
+    }
+
+    /// Saves a data chunk to a temporary file within the workspace directory.
+    ///
+    /// # Parameters
+    /// - `chunk_idx`: The index of the chunk (used in the file name).
+    /// - `part_idx`: The index of the part within the chunk (used in the file name).
+    /// - `data_slice`: The data slice to be saved.
+    ///
+    /// # Returns
+    /// An `io::Result<PathBuf>` containing the absolute path of the created file.
+    async fn save_chunk(&self, chunk_idx: usize, part_idx: usize, data_slice: &[u8]) -> io::Result<PathBuf> {
+        let tmp_file_abs_path = self.dir.join(format!("chunk_{}_{}_{}.bin", chunk_idx, part_idx, self.token));
+        log::debug!("Saving tmp chunk: {:?} with {} B...",
+            &tmp_file_abs_path, data_slice.len()
+        );
+
+        // A retried chunk replaces the file of its previous attempt.
+        match fs::remove_file(&tmp_file_abs_path).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {},
+        }
+
+        async {
+            let mut chunk_file = fs::File::create_new(&tmp_file_abs_path).await?;
+            chunk_file.write_all(data_slice).await?;
+            chunk_file.flush().await
+        }
+        .await
+        .with_context(|| format!("saving tmp chunk {tmp_file_abs_path:?} failed"))?;
+
+        log::debug!("Saving tmp chunk done!");
+        Ok(tmp_file_abs_path)
+    }
+}
+
+impl Drop for TmpWorkspace {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.dir) {
+            log::warn!("Removing tmp workspace {:?} failed: {e}", self.dir);
+        }
+    }
+}
+
+/// Reads the whole binary image, either from a file or from stdin if the path is [`STDIN_BINARY_PATH`].
+///
+/// The stdin stream is read until EOF before anything is uploaded, so the total size
+/// is known up front and chunking works exactly as for a file.
+///
+/// # Parameters
+/// - `binary_filepath`: The path to the binary file or `-`.
+///
+/// # Returns
+/// An `io::Result<Vec<u8>>` containing the image data.
+pub async fn read_binary_input<P>(binary_filepath: P) -> io::Result<Vec<u8>>
+where
+    P: AsRef<Path>
+{
+    if binary_filepath.as_ref() == Path::new(STDIN_BINARY_PATH) {
+        log::debug!("Reading binary from stdin...");
+        let mut data = Vec::new();
+        io::stdin().read_to_end(&mut data).await?;
+        Ok(data)
+    } else {
+        fs::read(binary_filepath).await
+    }
+}
+
+/// Splits a chunk into parts of at most `part_size` bytes, saving them to temporary files if needed.
+/// With a program page size the parts are also cut at every page boundary, so a part starting
+/// in the middle of a page ends at its end.
+///
+/// # Parameters
+/// - `workspace`: The temporary workspace of the run.
+/// - `chunk_idx`: The index of the chunk (used in the file names).
+/// - `data_slice`: The chunk data.
+/// - `flash_offset`: The offset in flash memory the chunk is written to.
+/// - `part_size`: The maximal part size, usually the RAM buffer size.
+/// - `program_page_size`: The flash program page size, parts are not page aligned if `None`.
+/// - `ram_write`: The way the parts are written into the RAM buffer.
+///
+/// # Returns
+/// An `io::Result` with the parts in order.
+async fn prepare_chunk_parts<'a>(
+    workspace: &TmpWorkspace,
+    chunk_idx: usize,
+    data_slice: &'a [u8],
+    flash_offset: usize,
+    part_size: usize,
+    program_page_size: Option<usize>,
+    ram_write: RamWriteMethod
+) -> io::Result<Vec<ChunkPart<'a>>> {
+    let mut parts = Vec::new();
+    let mut offset = 0;
+
+    while offset < data_slice.len() {
+        let mut part_end = data_slice.len().min(offset + part_size);
+        if let Some(page_size) = program_page_size {
+            let page_end = ((flash_offset + offset) / page_size + 1) * page_size - flash_offset;
+            part_end = part_end.min(page_end);
+        }
+
+        let bytes = &data_slice[offset..part_end];
+        let file_path = match ram_write {
+            RamWriteMethod::Restore => Some(workspace.save_chunk(chunk_idx, parts.len(), bytes).await?),
+            RamWriteMethod::Mi => None,
+        };
+        parts.push(ChunkPart { offset, bytes, file_path });
+        offset = part_end;
+    }
+
+    Ok(parts)
+}
+
+/// Reads a flash range back in reads of up to `read_chunk_size` bytes.
+///
+/// The flash must be memory mapped at the flash base.
+///
+/// # Parameters
+/// - `gdb`: A mutable reference to an active GDB connection.
+/// - `flash_target`: The flash description with the flash base.
+/// - `flash_offset`: Offset of the range counted from the beginning of the flash.
+/// - `length`: The number of bytes to read.
+/// - `read_chunk_size`: The maximum size of a single read, the whole range at once if `None`.
+///
+/// # Returns
+/// An `io::Result` with the flash content, in one piece regardless of the read size.
+pub(crate) async fn read_flash(
+    gdb: &mut Gdb,
+    flash_target: &FlashTarget,
+    flash_offset: usize,
+    length: usize,
+    read_chunk_size: Option<usize>
+) -> io::Result<Vec<u8>> {
+    let read_chunk_size = read_chunk_size.unwrap_or(length).max(1);
+    let mut bytes = Vec::with_capacity(length);
+
+    while bytes.len() < length {
+        let read_bytes = (length - bytes.len()).min(read_chunk_size);
+        let flash_address = flash_target.readback_address(flash_offset + bytes.len())?;
+        bytes.extend(gdb.read_memory(flash_address, read_bytes).await
+            .with_context(|| format!("reading {read_bytes} B of flash at {flash_address:#x} failed"))?);
+    }
+
+    Ok(bytes)
+}
+
+/// Saves the host chunk and the flash content read back from the target for a mismatching chunk,
+/// the files are named `mismatch_chunk_<chunk_idx>_host.bin` and `mismatch_chunk_<chunk_idx>_target.bin`.
+///
+/// # Parameters
+/// - `gdb`: A mutable reference to an active GDB connection.
+/// - `chunk_idx`: The index of the chunk (used in the file names).
+/// - `flash_target`: The flash description with the address base.
+/// - `flash_offset`: The offset in flash memory the chunk was written to.
+/// - `data_slice`: The chunk data on the host.
+/// - `read_chunk_size`: The maximum size of a single read, see [`read_flash`].
+///
+/// # Returns
+/// An `io::Result<()>` with an error if reading back or saving fails.
+async fn dump_mismatching_chunk(
+    gdb: &mut Gdb,
+    chunk_idx: usize,
+    flash_target: &FlashTarget,
+    flash_offset: usize,
+    data_slice: &[u8],
+    read_chunk_size: Option<usize>
+) -> io::Result<()> {
+    let flash_address = flash_target.readback_address(flash_offset)?;
+    log::info!("Reading back {} B of flash at {flash_address:#x}...", data_slice.len());
+    let target_bytes = read_flash(gdb, flash_target, flash_offset, data_slice.len(), read_chunk_size).await?;
+
+    let host_file_path = PathBuf::from(format!("mismatch_chunk_{chunk_idx}_host.bin"));
+    let target_file_path = PathBuf::from(format!("mismatch_chunk_{chunk_idx}_target.bin"));
+    fs::write(&host_file_path, data_slice).await?;
+    fs::write(&target_file_path, &target_bytes).await?;
+
+    let differing_bytes_count = data_slice.iter().zip(&target_bytes).filter(|(host, target)| host != target).count();
+    match data_slice.iter().zip(&target_bytes).position(|(host, target)| host != target) {
+        Some(first_offset) => log::error!(
+            "Chunk {chunk_idx}: {differing_bytes_count} B differ, first at offset {first_offset:#x} (flash {:#x}): host={:#04x} target={:#04x}. Saved {host_file_path:?} and {target_file_path:?}.",
+            flash_address as usize + first_offset, data_slice[first_offset], target_bytes[first_offset]
+        ),
+        None => log::error!("Chunk {chunk_idx}: flash content matches host, the copy function checksum is wrong. Saved {host_file_path:?} and {target_file_path:?}."),
+    }
+
+    Ok(())
+}
+
+/// Checks that a restore wrote the whole file into the RAM buffer.
+///
+/// # Parameters
+/// - `restored_bytes`: The number of bytes GDB reported as restored.
+/// - `expected_bytes`: The size of the restored file.
+///
+/// # Returns
+/// An `io::Result<()>`, an `InvalidData` error with both counts if they differ.
+fn check_restored_bytes(restored_bytes: u32, expected_bytes: usize) -> io::Result<()> {
+    if restored_bytes as usize != expected_bytes {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("RAM write incomplete, restored {restored_bytes} B, expected {expected_bytes} B")
+        ));
+    }
+
+    Ok(())
+}
+
+/// Transfers a single chunk: writes its parts to the RAM buffer, triggers the copy function for each
+/// and compares the checksum of the whole chunk.
+///
+/// Every part is copied to `flash_offset` plus its offset, the copy function gets its own address
+/// and byte count. As Sum32 is additive, the part checksums add up to the checksum of the chunk.
+/// With a `seed` every part is seeded with the running checksum returned for the previous one,
+/// the last returned value is the running checksum after the chunk.
+///
+/// # Parameters
+/// - `gdb`: A mutable reference to an active GDB connection.
+/// - `parts`: The chunk parts, a single one unless the chunk exceeds the RAM buffer or crosses program pages.
+/// - `ram_buffer`: The RAM buffer on the target device.
+/// - `flash_target`: The flash description with the copy function and address base.
+/// - `flash_offset`: The offset in flash memory the chunk is written to.
+/// - `host_checksum`: The checksum of the chunk computed on the host, including the seed.
+/// - `seed`: The running checksum before the chunk, for cumulative checksums only.
+/// - `verify_ram`: Read every part back from the RAM buffer before copying it, so a corrupted RAM write
+///   is told apart from a corrupted flash write.
+///
+/// # Returns
+/// An `io::Result` with the target checksum of the chunk, `None` if the copy function returns void and nothing
+/// was compared, or an error if any step fails or checksums do not match.
+#[allow(clippy::too_many_arguments)]
+async fn transfer_chunk(
+    gdb: &mut Gdb,
+    parts: &[ChunkPart<'_>],
+    ram_buffer: &RamBuffer,
+    flash_target: &FlashTarget,
+    flash_offset: usize,
+    host_checksum: u32,
+    seed: Option<u32>,
+    verify_ram: bool
+) -> io::Result<Option<u32>> {
+    let checksum_width = flash_target.checksum_width;
+    let host_checksum = checksum_width.mask(host_checksum);
+    let mut target_checksum = seed.unwrap_or(0);
+    let mut verified = true;
+
+    for part in parts {
+        // Upload the part to the target's RAM.
+        match &part.file_path {
+            Some(part_abs_file_path) => {
+                let restored_bytes = gdb.write_binary_file_to_mem(&ram_buffer.location(), part_abs_file_path).await
+                    .with_context(|| format!("restoring part at offset {:#x} into RAM buffer failed", part.offset))?;
+                log::info!("Got RAM writing results: {restored_bytes:?}");
+
+                // A truncated restore would otherwise copy stale RAM buffer content to flash.
+                check_restored_bytes(restored_bytes, part.bytes.len())
+                    .with_context(|| format!("part at offset {:#x}", part.offset))?;
+            },
+            None => {
+                gdb.write_memory_bytes_at(&ram_buffer.location(), part.bytes).await
+                    .with_context(|| format!("writing part at offset {:#x} into RAM buffer failed", part.offset))?;
+                log::info!("Got RAM writing results: {} B", part.bytes.len());
+            },
+        }
+
+        if verify_ram {
+            let ram_bytes = gdb.read_memory_at(&ram_buffer.location(), part.bytes.len()).await
+                .with_context(|| format!("reading part at offset {:#x} back from RAM buffer failed", part.offset))?;
+            if ram_bytes.len() != part.bytes.len() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "RAM buffer read back is shorter than the part"));
+            }
+            if let Some(ram_offset) = ram_bytes.iter().zip(part.bytes).position(|(target, host)| target != host) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("RAM write corrupted, part at offset {:#x} differs at RAM buffer offset {ram_offset:#x}: host={:#04x} target={:#04x}",
+                        part.offset, part.bytes[ram_offset], ram_bytes[ram_offset]
+                    )
+                ));
+            }
+        }
+
+        // Trigger the copying function to move the data from RAM to flash.
+        let part_seed = seed.map(|_| target_checksum);
+        let part_host_checksum = checksum_width.mask(part_seed.unwrap_or(0).wrapping_add(checksum_sum32(part.bytes)));
+        let Some(part_target_checksum) = flash_target.call_copy(gdb, flash_offset + part.offset, part.bytes.len(), part_seed).await? else {
+            log::info!("Copied part at offset {:#x}, copy function returns no checksum.", part.offset);
+            verified = false;
+            continue;
+        };
+
+        log::info!("Got target_checksum={part_target_checksum}, host_checksum={part_host_checksum}, matches={}", 
+            part_target_checksum == part_host_checksum
+        );
+
+        if flash_target.copy_error_value == Some(part_target_checksum) {
+            if part_host_checksum != part_target_checksum {
+                return Err(io::Error::other(format!("Copy function reported error value {part_target_checksum:#x}")));
+            }
+
+            // The data legitimately checksums to the error value, only the status can tell success from failure.
+            match &flash_target.copy_status_variable {
+                Some(copy_status_variable) => {
+                    let status = gdb.read_variable_u32(copy_status_variable).await?;
+                    if status != 0 {
+                        return Err(io::Error::other(format!("Copy function reported status {status}")));
+                    }
+                },
+                None => log::warn!("Chunk checksum equals copy error value {part_target_checksum:#x}, success cannot be confirmed."),
+            }
+        }
+
+        target_checksum = match part_seed {
+            Some(_) => part_target_checksum,
+            None => checksum_width.mask(target_checksum.wrapping_add(part_target_checksum)),
+        };
+    }
+
+    if !verified {
+        return Ok(None);
+    }
+
+    // Compare the computed checksum with the target's checksum.
+    if host_checksum != target_checksum {
+        log::error!("Compare with host checksum={host_checksum}...");
+        return Err(GdbError::ChecksumMismatch { host_checksum, target_checksum }.into());
+    }
+
+    Ok(Some(target_checksum))
+}
+
+/// Uploads a binary file to external flash memory in chunks.
+///
+/// Kept for compatibility, it is [`upload_binary_file`] with the [`FlashTarget::external`] preset
+/// using `coping_function_name` as the copy function.
+#[deprecated(note = "use `upload_binary_file` with `FlashTarget::external()`")]
+#[allow(unused)]
+pub async fn upload_binary_file_to_external_flash<P, F>(
+    gdb: &mut Gdb,
+    binary_filepath: P,
+    ram_buffer_name: &str,
+    chunk_size: usize,
+    flash_start_offset: usize,
+    coping_function_name: &str,
+    per_chunk_handler: Option<F>
+) -> io::Result<()> 
+where
+    P: AsRef<Path> + Debug,
+    F: Fn(usize, usize, usize, usize, u128) + 'static
+{
+    let flash_target = FlashTarget {
+        copy_function_name: coping_function_name.to_string(),
+        ..FlashTarget::external()
+    };
+
+    upload_binary_file(
+        gdb,
+        binary_filepath,
+        &RamBuffer::Symbol(ram_buffer_name.to_string()),
+        chunk_size,
+        flash_start_offset,
+        &flash_target,
+        &UploadOptions::default(),
+        per_chunk_handler.map(legacy_chunk_handler)
+    ).await
+    .map(|_| ())
+}
+
+/// A chunk of a planned upload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedChunk {
+    /// Index of the chunk.
+    pub index: usize,
+    /// Byte range of the chunk in the (padded) input data.
+    pub file_range: Range<usize>,
+    /// Offset in flash the chunk is written to.
+    pub flash_offset: usize,
+    /// Size of the chunk in bytes.
+    pub size: usize,
+    /// Whether all bytes are `0xFF`, the content of erased flash.
+    pub blank: bool,
+}
+
+/// Chunks of an upload, computed without touching the target.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UploadPlan {
+    /// The chunks in upload order.
+    pub chunks: Vec<PlannedChunk>,
+    /// Requested chunk size in bytes.
+    pub chunk_size: usize,
+    /// Flash offset of the first chunk.
+    pub flash_start_offset: usize,
+    /// Size of the data in bytes, including padding.
+    pub total_size: usize,
+    /// Problems found while planning, reported as warnings when the plan is executed.
+    pub warnings: Vec<String>,
+}
+
+impl UploadPlan {
+    /// Renders the plan as a text table, one row per chunk.
+    pub fn render_table(&self) -> String {
+        let mut table = format!("{:>5}  {:>21}  {:>10}  {:>8}  {}\n", "chunk", "file range", "flash", "size", "blank");
+        for chunk in &self.chunks {
+            table.push_str(&format!("{:>5}  {:>21}  {:>#10x}  {:>8}  {}\n",
+                chunk.index,
+                format!("{:#x}..{:#x}", chunk.file_range.start, chunk.file_range.end),
+                chunk.flash_offset,
+                chunk.size,
+                if chunk.blank { "yes" } else { "no" }
+            ));
+        }
+        table.push_str(&format!("{} chunks, {} B at flash offset {:#x}\n", self.chunks.len(), self.total_size, self.flash_start_offset));
+        for warning in &self.warnings {
+            table.push_str(&format!("warning: {warning}\n"));
+        }
+        table
+    }
+
+    /// Renders the plan as JSON.
+    pub fn render_json(&self) -> String {
+        let chunks = self.chunks.iter()
+            .map(|chunk| format!(
+                "{{\"index\":{},\"file_start\":{},\"file_end\":{},\"flash_offset\":{},\"size\":{},\"blank\":{}}}",
+                chunk.index, chunk.file_range.start, chunk.file_range.end, chunk.flash_offset, chunk.size, chunk.blank
+            ))
+            .collect::<Vec<_>>()
+            .join(",");
+        let warnings = self.warnings.iter()
+            .map(|warning| format!("{warning:?}"))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        format!("{{\"chunk_size\":{},\"flash_start_offset\":{},\"total_size\":{},\"chunks\":[{chunks}],\"warnings\":[{warnings}]}}",
+            self.chunk_size, self.flash_start_offset, self.total_size
+        )
+    }
 }
 
-/// Prepares the temporary workspace directory by removing any existing directory
-/// and then re-creating it.
+/// Rejects a flash offset inside a sector if the copy function erases whole sectors,
+/// as the data of the sector below the offset would be erased too.
+///
+/// # Parameters
+/// - `flash_start_offset`: The starting offset in flash memory for data writing.
+/// - `sector_size`: The flash sector size in bytes.
 ///
 /// # Returns
-/// An `io::Result<()>` indicating whether the directory was successfully prepared.
+/// An `io::Result<()>` with an error suggesting the nearest aligned offset if the offset is misaligned.
+pub fn ensure_erase_aligned(flash_start_offset: usize, sector_size: usize) -> io::Result<()> {
+    let misalignment = flash_start_offset % sector_size;
+    if misalignment == 0 {
+        return Ok(());
+    }
+
+    let aligned_below = flash_start_offset - misalignment;
+    let aligned_above = aligned_below + sector_size;
+    let nearest_aligned = match misalignment <= sector_size / 2 {
+        true => aligned_below,
+        false => aligned_above,
+    };
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("Flash offset {flash_start_offset:#x} is inside the sector at {aligned_below:#x} of {sector_size} B, \
+            erasing it would destroy {misalignment} B below the offset. \
+            Use the nearest aligned offset {nearest_aligned:#x} or --rmw to keep the sector content")
+    ))
+}
+
+/// Splits the data into chunks and validates it against the flash, without touching the target.
+///
+/// # Parameters
+/// - `data`: The data to upload, already padded.
+/// - `chunk_size`: The maximum size (in bytes) of each chunk.
+/// - `flash_start_offset`: The starting offset in flash memory for data writing.
+/// - `flash_target`: The flash description with the sector and flash size.
+/// - `options`: The upload options, the scratch region is checked.
 ///
-/// # Notes
-/// This function uses synchronous calls (e.g., `exists()`) to check for the directory.
-/// For fully asynchronous behavior, consider using `tokio::fs::metadata`.
-async fn prepare_tmp_workspace_dir() -> io::Result<()> {
-    let absolute_path = get_abs_tmp_workspace_dir();
-    log::debug!("Preparing tmp workspace at absolute path: {:?}", absolute_path);
+/// # Returns
+/// An `io::Result<UploadPlan>` with an error if the data does not fit the flash or overlaps the scratch region.
+pub fn plan_upload(
+    data: &[u8],
+    chunk_size: usize,
+    flash_start_offset: usize,
+    flash_target: &FlashTarget,
+    options: &UploadOptions
+) -> io::Result<UploadPlan> {
+    let total_data_size = data.len();
+
+    if chunk_size == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Chunk size must not be zero"));
+    }
+
+    if let Some(flash_size) = flash_target.flash_size {
+        if flash_start_offset + total_data_size > flash_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Data of {total_data_size} B at offset {flash_start_offset:#x} exceeds flash size {flash_size} B")
+            ));
+        }
+    }
+
+    if let Some(scratch) = &options.scratch {
+        scratch.ensure_no_overlap(flash_start_offset, total_data_size)?;
+    }
 
-    // Good enough method in this case
-    if absolute_path.exists() {
-        log::debug!("Preparing tmp workspace - erasing existing file...");
-        fs::remove_dir_all(&absolute_path).await?;
+    let mut warnings = Vec::new();
+    if !chunk_size.is_multiple_of(flash_target.sector_size) || !flash_start_offset.is_multiple_of(flash_target.sector_size) {
+        warnings.push(format!("Chunk size {} B or flash offset {:#x} is not a multiple of sector size {} B.",
+            chunk_size, flash_start_offset, flash_target.sector_size
+        ));
     }
 
-    fs::create_dir_all(&absolute_path).await?;
-    log::debug!("Preparing tmp workspace done!");
+    let chunks = (0..total_data_size).step_by(chunk_size)
+        .enumerate()
+        .map(|(index, start)| {
+            let file_range = start..(start + chunk_size).min(total_data_size);
+            PlannedChunk {
+                index,
+                flash_offset: flash_start_offset + start,
+                size: file_range.len(),
+                blank: data[file_range.clone()].iter().all(|&byte| byte == 0xFF),
+                file_range,
+            }
+        })
+        .collect();
 
-    Ok(())
+    Ok(UploadPlan { chunks, chunk_size, flash_start_offset, total_size: total_data_size, warnings })
 }
 
-/// Saves a data chunk to a temporary file within the workspace directory.
+/// Reads the input, pads it if requested and plans the upload.
 ///
 /// # Parameters
-/// - `chunk_idx`: The index of the chunk (used in the file name).
-/// - `data_slice`: The data slice to be saved.
+/// - `binary_filepath`: The path to the binary file to be uploaded, `-` reads it from stdin.
+/// - `chunk_size`: The maximum size (in bytes) of each chunk.
+/// - `flash_start_offset`: The starting offset in flash memory for data writing.
+/// - `flash_target`: The flash description with the sector and flash size.
+/// - `options`: The upload options, e.g. the padding.
 ///
 /// # Returns
-/// An `io::Result<PathBuf>` containing the absolute path of the created file.
-async fn save_chunk_tmp_file(
-    chunk_idx: usize,
-    data_slice: &[u8]
-) -> io::Result<PathBuf> {
-    let tmp_file_name = format!("chunk_{}_.bin", chunk_idx);
-
-    let tmp_dir_abs_path = get_abs_tmp_workspace_dir();
-    let tmp_file_abs_path = tmp_dir_abs_path.clone().join(tmp_file_name);
-    log::debug!("Saving tmp chunk: {:?} with {} B...",
-        &tmp_file_abs_path, data_slice.len()
-    );
+/// An `io::Result` with the data to upload and its plan.
+pub async fn prepare_upload<P>(
+    binary_filepath: P,
+    chunk_size: usize,
+    flash_start_offset: usize,
+    flash_target: &FlashTarget,
+    options: &UploadOptions
+) -> io::Result<(Vec<u8>, UploadPlan)>
+where
+    P: AsRef<Path> + Debug
+{
+    let mut file_data = read_binary_input(&binary_filepath).await
+        .with_context(|| format!("reading binary {binary_filepath:?} failed"))?;
 
-    let mut chunk_file = fs::File::create_new(&tmp_file_abs_path).await?;
-    chunk_file.write_all(data_slice).await?;
-    chunk_file.flush().await?;
+    pad_last_sector(&mut file_data, flash_start_offset, flash_target, options);
+
+    let plan = plan_upload(&file_data, chunk_size, flash_start_offset, flash_target, options)?;
+    log::info!("Loaded file {:?}, got {} B. Packets to upload: {} up to {} B each.", 
+        binary_filepath, plan.total_size, plan.chunks.len(), chunk_size
+    );
 
-    log::debug!("Saving tmp chunk done!");
-    Ok(tmp_file_abs_path)
+    Ok((file_data, plan))
 }
 
-/// Uploads a binary file to external flash memory in chunks.
+/// Pads the data with [`UploadOptions::pad_last`] so the written region ends at a sector boundary,
+/// the padding is part of the data, so it is included in the checksum too.
 ///
-/// The upload process includes:
-/// 1. Reading the entire binary file into memory.
-/// 2. Splitting the file into chunks of size `chunk_size`.
-/// 3. For each chunk:
-///    - Saving the chunk to a temporary file.
-///    - Uploading the file to a RAM buffer using the GDB interface.
-///    - Triggering a copying function on the target to transfer data from RAM to flash.
-///    - Calculating a checksum for the chunk and comparing it with the target's checksum.
+/// # Parameters
+/// - `data`: The data to pad, unchanged without the pad option.
+/// - `flash_start_offset`: The flash offset the data is written to.
+/// - `flash_target`: The flash description with the sector size.
+/// - `options`: The upload options with the pad byte.
+pub fn pad_last_sector(data: &mut Vec<u8>, flash_start_offset: usize, flash_target: &FlashTarget, options: &UploadOptions) {
+    if let Some(pad_byte) = options.pad_last {
+        let data_end = flash_start_offset + data.len();
+        let pad_bytes_count = data_end.next_multiple_of(flash_target.sector_size) - data_end;
+        data.resize(data.len() + pad_bytes_count, pad_byte);
+        log::info!("Padded last chunk with {pad_bytes_count} B of {pad_byte:#04x}.");
+    }
+}
+
+/// Uploads a binary file to flash memory in chunks, see [`prepare_upload`] and [`execute_plan`].
 ///
 /// # Parameters
 /// - `gdb`: A mutable reference to an active GDB connection.
-/// - `binary_filepath`: The path to the binary file to be uploaded.
-/// - `ram_buffer_name`: The name of the RAM buffer on the target device.
+/// - `binary_filepath`: The path to the binary file to be uploaded, `-` reads it from stdin.
+/// - `ram_buffer`: The RAM buffer on the target device.
 /// - `chunk_size`: The maximum size (in bytes) of each chunk.
-/// - `flash_start_offset`: The starting offset in external flash memory for data writing.
-/// - `coping_function_name`: The name of the function that triggers copying from RAM to flash.
-///    *Note: Consider renaming this parameter (e.g., to `copying_function_name`).
+/// - `flash_start_offset`: The starting offset in flash memory for data writing.
+/// - `flash_target`: The flash description with the copy function and address base.
+/// - `options`: Additional options such as the retry policy.
 ///
 /// # Returns
-/// - `Ok(())` if the upload is successful and all checksums match.
+/// - `Ok(UploadStats)` if the upload is successful and all checksums match.
 /// - `Err(io::Error)` if an I/O error occurs or if a checksum mismatch is detected.
-pub async fn upload_binary_file_to_external_flash<P, F>(
+#[allow(clippy::too_many_arguments)]
+pub async fn upload_binary_file<P, F>(
     gdb: &mut Gdb,
     binary_filepath: P,
-    ram_buffer_name: &str,
+    ram_buffer: &RamBuffer,
     chunk_size: usize,
     flash_start_offset: usize,
-    coping_function_name: &str,
+    flash_target: &FlashTarget,
+    options: &UploadOptions,
     per_chunk_handler: Option<F>
-) -> io::Result<()> 
+) -> io::Result<UploadStats> 
 where
     P: AsRef<Path> + Debug,
-    F: Fn(usize, usize, usize, usize, u128) + 'static
+    F: Fn(&ChunkResult, &UploadProgress) + 'static
 {
-    let file_data = fs::read(&binary_filepath).await?;
-    let total_data_size = file_data.len();
-    let chunks_count = (total_data_size / chunk_size) + if total_data_size % chunk_size != 0 { 1 } else { 0 };
-    log::info!("Loaded file {:?}, got {} B. Packets to upload: {} up to {} B each.", 
-        binary_filepath, total_data_size, chunks_count, chunk_size
-    );
+    let (file_data, plan) = prepare_upload(binary_filepath, chunk_size, flash_start_offset, flash_target, options).await?;
+    execute_plan(gdb, &plan, &file_data, ram_buffer, flash_target, options, per_chunk_handler).await
+}
+
+/// Executes a planned upload.
+///
+/// The process includes, for each chunk:
+/// - Splitting it into RAM buffer sized parts if `options.auto_split` is set and it does not fit.
+/// - Saving the parts to temporary files, unless written with [`RamWriteMethod::Mi`].
+/// - Uploading each part to a RAM buffer using the GDB interface.
+/// - Triggering the copy function of `flash_target` to transfer each part from RAM to flash.
+/// - Calculating a checksum for the chunk and comparing it with the target's checksum.
+///
+/// The chunks follow the plan, unless adaptive sizing changes the chunk size on the way.
+///
+/// # Parameters
+/// - `gdb`: A mutable reference to an active GDB connection.
+/// - `plan`: The plan of the upload made by [`plan_upload`].
+/// - `file_data`: The data the plan was made for.
+/// - `ram_buffer`: The RAM buffer on the target device.
+/// - `flash_target`: The flash description with the copy function and address base.
+/// - `options`: Additional options such as the retry policy.
+/// - `per_chunk_handler`: Called after every chunk, also a skipped one, with its result and the upload totals.
+///
+/// # Returns
+/// - `Ok(UploadStats)` if the upload is successful and all checksums match.
+/// - `Err(io::Error)` if an I/O error occurs or if a checksum mismatch is detected.
+pub async fn execute_plan<F>(
+    gdb: &mut Gdb,
+    plan: &UploadPlan,
+    file_data: &[u8],
+    ram_buffer: &RamBuffer,
+    flash_target: &FlashTarget,
+    options: &UploadOptions,
+    per_chunk_handler: Option<F>
+) -> io::Result<UploadStats> 
+where
+    F: Fn(&ChunkResult, &UploadProgress) + 'static
+{
+    let mut stats = UploadStats::default();
+    for warning in &plan.warnings {
+        stats.warn(warning.clone());
+    }
 
-    // Create or recreate temp files directory
+    let total_data_size = plan.total_size;
+    let chunk_size = plan.chunk_size;
+    let flash_start_offset = plan.flash_start_offset;
+    let chunks_count = plan.chunks.len();
+
+    // Create temp files directory of this run, removed when the upload ends.
     // It will be used to store files to be transfered 
     // via GDB to target MCU RAM buffer.
-    prepare_tmp_workspace_dir().await?;
+    let workspace = TmpWorkspace::create().await?;
 
     let mut remaining_bytes = total_data_size;
     let mut data_offset = 0;
     let mut chunk_idx: usize = 0;
     let mut flash_offset: usize = flash_start_offset;
     let mut bytes_trasfered = 0;
+    let mut ram_write = options.ram_write;
+    // Flash offset and data range of every written chunk, their sizes differ with adaptive sizing.
+    let mut written_chunks = Vec::new();
+    let mut mismatched_chunks = Vec::new();
+    // Stays 0 unless the checksum is cumulative, a retry is seeded again with the value of the last verified chunk.
+    let mut running_checksum = 0u32;
+
+    // Parts are as large as the chunk, unless split to fit the RAM buffer.
+    let part_size = if options.auto_split {
+        let ram_buffer_size = ram_buffer.query_size(gdb).await?;
+        if chunk_size > ram_buffer_size {
+            log::info!("Chunks of {chunk_size} B are split into parts of {ram_buffer_size} B to fit the RAM buffer.");
+        }
+        chunk_size.min(ram_buffer_size)
+    } else {
+        chunk_size
+    };
+
+    // Resolve bounds of adaptive sizing, aligned to sectors.
+    let sector_size = flash_target.sector_size;
+    let adaptive_bounds = match &options.adaptive {
+        Some(adaptive) => {
+            let max_chunk = match adaptive.max_chunk {
+                Some(max_chunk) => max_chunk,
+                None => match ram_buffer.query_size(gdb).await {
+                    Ok(ram_buffer_size) => ram_buffer_size,
+                    Err(_) => {
+                        stats.warn(format!("RAM buffer size unknown, adaptive chunk size limited to {chunk_size} B."));
+                        chunk_size
+                    }
+                },
+            };
+            let min_chunk = adaptive.min_chunk.unwrap_or(sector_size).next_multiple_of(sector_size);
+            let max_chunk = (max_chunk - max_chunk % sector_size).max(min_chunk);
+            log::debug!("Adaptive chunk size bounds: {min_chunk}..={max_chunk} B.");
+            Some((min_chunk, max_chunk))
+        },
+        None => None,
+    };
+
+    let mut current_chunk_size = match adaptive_bounds {
+        Some((min_chunk, max_chunk)) => (chunk_size - chunk_size % sector_size).clamp(min_chunk, max_chunk),
+        None => chunk_size,
+    };
+    let mut chunk_size_trajectory = vec![current_chunk_size];
+    let mut successes_in_row = 0;
+    let mut chunks_count = chunks_count;
+
+    // Chunks of the plan are checksummed up front, chunks resized by adaptive sizing on the fly.
+    let precomputed_checksums = match total_data_size >= PARALLEL_CHECKSUM_THRESHOLD {
+        true => {
+            let precompute_start = Instant::now();
+            let checksums = precompute_chunk_checksums(plan, file_data);
+            log::debug!("Checksums of {} chunks computed in {} ms.", checksums.len(), precompute_start.elapsed().as_millis());
+            checksums
+        },
+        false => Vec::new(),
+    };
+    let chunk_checksum = |chunk_idx: usize, data_range: Range<usize>| {
+        plan.chunks.get(chunk_idx)
+            .zip(precomputed_checksums.get(chunk_idx))
+            .filter(|(chunk, _)| chunk.file_range == data_range)
+            .map_or_else(|| checksum_sum32(&file_data[data_range.clone()]), |(_, &checksum)| checksum)
+    };
 
     let system_time_start = SystemTime::now();
+    let upload_start = Instant::now();
 
     while remaining_bytes > 0 {
         // Determine the number of bytes for the current chunk.
-        let chunk_bytes = if remaining_bytes > chunk_size { chunk_size } else { remaining_bytes };
+        let mut chunk_bytes = remaining_bytes.min(current_chunk_size);
         log::info!("Preparing chunk_idx={chunk_idx}/{chunks_count}, chunk_size={chunk_bytes} B, remaining={remaining_bytes} B.");
 
         let data_slice_start = data_offset;
-        let data_slice_end = data_slice_start + chunk_bytes;
-        let data_slice = &file_data[data_slice_start..data_slice_end];
-
-        // Calculate the checksum for the current chunk.
-        let data_slice_checksum = data_slice.iter().fold(0u32, |acc, &v| acc.wrapping_add(v as u32));
-
-        // Save the chunk to a temporary file.
-        let chunk_abs_file_path = save_chunk_tmp_file(
-            chunk_idx,
-            data_slice
-        ).await?;
-
-        // Upload the temporary file to the target's RAM.
-        let result = gdb.write_binary_file_to_mem(ram_buffer_name, &chunk_abs_file_path).await?;
-        log::info!("Got RAM writing results: {result:?}");
-
-        // Trigger the copying function to move the data from RAM to external flash.
-        let target_checksum = gdb.call_with_u32_u32_resulting_u32(
-            coping_function_name, 
-            flash_offset as u32, 
-            chunk_bytes as u32,
-            true        
-        ).await?;
-
-        log::info!("Got target_checksum={target_checksum}, host_checksum={data_slice_checksum}, matches={}", 
-            target_checksum == data_slice_checksum
-        );
+        let mut data_slice = &file_data[data_slice_start..data_slice_start + chunk_bytes];
 
-        // Compare the computed checksum with the target's checksum.
-        if data_slice_checksum != target_checksum {
-            log::error!("Compare with host checksum={data_slice_checksum}...");
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData, 
-                format!("Checksum not match host={data_slice_checksum} target={target_checksum}")
-            ));
+        // Calculate the checksum for the current chunk, a cumulative one continues from the verified chunks.
+        let seed = options.cumulative_checksum.then_some(running_checksum);
+        let data_slice_sum = chunk_checksum(chunk_idx, data_slice_start..data_slice_start + chunk_bytes);
+        let mut data_slice_checksum = running_checksum.wrapping_add(data_slice_sum);
+
+        let chunk_start = Instant::now();
+        let retries_before = stats.retries;
+
+        // Skip the chunk if flash already holds it, e.g. written by an interrupted run.
+        let is_blank = options.skip_blank && data_slice.iter().all(|&byte| byte == 0xFF);
+        let flash_checksum = match options.resume_verify && !is_blank {
+            true => Some(flash_target.call_checksum(gdb, flash_offset, chunk_bytes).await
+                .with_context(|| format!("chunk {chunk_idx} at flash offset {flash_offset:#x}: resume check failed"))?),
+            false => None,
+        };
+        let already_written = flash_checksum == Some(flash_target.checksum_width.mask(data_slice_sum));
+        let mut target_checksum = flash_checksum.filter(|_| already_written);
+
+        if is_blank {
+            log::info!("Chunk {chunk_idx} is blank, skipped.");
+            stats.blank_chunks += 1;
+        } else if already_written {
+            log::info!("Chunk {chunk_idx} already in flash, skipped.");
+            stats.skipped_chunks += 1;
+        } else {
+            // Split the chunk into parts and save them to temporary files if needed.
+            let mut parts = prepare_chunk_parts(&workspace, chunk_idx, data_slice, flash_offset, part_size, options.program_page_size, ram_write).await?;
+
+            // Transfer the chunk, retrying with an increasing backoff on failure.
+            let mut attempt = 0;
+            loop {
+                let result = transfer_chunk(
+                    gdb,
+                    &parts,
+                    ram_buffer,
+                    flash_target,
+                    flash_offset,
+                    data_slice_checksum,
+                    seed,
+                    options.verify_ram
+                ).await;
+
+                match result {
+                    Ok(checksum) => {
+                        if checksum.is_none() {
+                            stats.unverified_chunks += 1;
+                        }
+                        target_checksum = checksum;
+                        break;
+                    },
+                    Err(e) if e.kind() == io::ErrorKind::Unsupported && ram_write == RamWriteMethod::Mi => {
+                        stats.warn(format!("{e}, falling back to restore for the remaining chunks."));
+                        ram_write = RamWriteMethod::Restore;
+                        parts = prepare_chunk_parts(&workspace, chunk_idx, data_slice, flash_offset, part_size, options.program_page_size, ram_write).await?;
+                    },
+                    Err(e) if adaptive_bounds.is_some_and(|(min_chunk, _)| chunk_bytes > min_chunk) => {
+                        // Retry the same data with a smaller chunk, the retry budget is kept for the minimal size.
+                        let min_chunk = adaptive_bounds.map_or(sector_size, |(min_chunk, _)| min_chunk);
+                        current_chunk_size = ((chunk_bytes / 2) - (chunk_bytes / 2) % sector_size).max(min_chunk);
+                        chunk_size_trajectory.push(current_chunk_size);
+                        successes_in_row = 0;
+                        stats.retries += 1;
+                        stats.warn(format!("Chunk {chunk_idx} failed: {e}. Retry with chunk size {current_chunk_size} B..."));
+
+                        chunk_bytes = remaining_bytes.min(current_chunk_size);
+                        data_slice = &file_data[data_slice_start..data_slice_start + chunk_bytes];
+                        data_slice_checksum = running_checksum.wrapping_add(checksum_sum32(data_slice));
+                        parts = prepare_chunk_parts(&workspace, chunk_idx, data_slice, flash_offset, part_size, options.program_page_size, ram_write).await?;
+                        chunks_count = chunk_idx + remaining_bytes.div_ceil(current_chunk_size);
+                    },
+                    Err(e) if attempt < options.retry.max_retries => {
+                        let backoff = options.retry.backoff_for(attempt);
+                        attempt += 1;
+                        stats.retries += 1;
+                        stats.warn(format!("Chunk {chunk_idx} failed: {e}. Retry {attempt}/{} in {} ms...",
+                            options.retry.max_retries, backoff.as_millis()
+                        ));
+                        wait_keeping_alive(gdb, backoff, options.keep_alive).await?;
+
+                        if options.retry.halt_before_retry {
+                            gdb.monitor_halt().await?;
+                        }
+                    },
+                    Err(e) if options.continue_on_mismatch && matches!(as_gdb_error(&e), Some(GdbError::ChecksumMismatch { .. })) => {
+                        // The retries are exhausted, the mismatch is final.
+                        if options.on_mismatch == MismatchAction::Dump {
+                            if let Err(dump_error) = dump_mismatching_chunk(gdb, chunk_idx, flash_target, flash_offset, data_slice, options.verify_read_chunk_size).await {
+                                stats.warn(format!("Dumping mismatching chunk {chunk_idx} failed: {dump_error}"));
+                            }
+                        }
+                        if let Some(&GdbError::ChecksumMismatch { host_checksum, target_checksum: checksum }) = as_gdb_error(&e) {
+                            target_checksum = Some(checksum);
+                            mismatched_chunks.push(MismatchedChunk {
+                                index: chunk_idx,
+                                flash_range: flash_offset..flash_offset + chunk_bytes,
+                                host_checksum,
+                                target_checksum: checksum,
+                            });
+                        }
+                        stats.warn(format!("Chunk {chunk_idx} at flash offset {flash_offset:#x} failed: {e}. Continuing with the next chunk..."));
+                        break;
+                    },
+                    Err(e) => {
+                        let is_mismatch = matches!(as_gdb_error(&e), Some(GdbError::ChecksumMismatch { .. }));
+                        if is_mismatch && options.on_mismatch == MismatchAction::Dump {
+                            if let Err(dump_error) = dump_mismatching_chunk(gdb, chunk_idx, flash_target, flash_offset, data_slice, options.verify_read_chunk_size).await {
+                                stats.warn(format!("Dumping mismatching chunk {chunk_idx} failed: {dump_error}"));
+                            }
+                        }
+                        return Err(e).with_context(|| format!("chunk {chunk_idx} at flash offset {flash_offset:#x}"));
+                    },
+                }
+            }
+        }
+
+        if options.cumulative_checksum {
+            running_checksum = data_slice_checksum;
+        }
+        written_chunks.push((flash_offset, data_slice_start..data_slice_start + chunk_bytes));
+
+        // Grow the chunk again after a streak of successes.
+        if let Some((_, max_chunk)) = adaptive_bounds {
+            successes_in_row += 1;
+            if successes_in_row >= ADAPTIVE_GROW_AFTER && current_chunk_size < max_chunk {
+                current_chunk_size = (current_chunk_size * 2).min(max_chunk);
+                chunk_size_trajectory.push(current_chunk_size);
+                successes_in_row = 0;
+                log::debug!("Chunk size increased to {current_chunk_size} B.");
+            }
+            chunks_count = chunk_idx + 1 + (remaining_bytes - chunk_bytes).div_ceil(current_chunk_size);
         }
 
         bytes_trasfered += chunk_bytes;
         if let Some(chunk_handle) = per_chunk_handler.as_ref() {
             let time_since_start = system_time_start.duration_since(UNIX_EPOCH).expect("Time went backwards");
-            chunk_handle(
-                chunk_idx, 
-                chunks_count, 
-                bytes_trasfered,
-                total_data_size,
-                time_since_start.as_millis()
-            )
+            let chunk_result = ChunkResult {
+                index: chunk_idx,
+                flash_offset,
+                size: chunk_bytes,
+                host_checksum: flash_target.checksum_width.mask(data_slice_checksum),
+                target_checksum,
+                retries: stats.retries - retries_before,
+                duration: chunk_start.elapsed(),
+                skipped_blank: is_blank,
+                skipped_written: already_written,
+            };
+            let progress = UploadProgress {
+                chunks_count,
+                bytes_done: bytes_trasfered,
+                total_bytes: total_data_size,
+                start_timestamp_ms: time_since_start.as_millis(),
+            };
+            chunk_handle(&chunk_result, &progress);
         }
 
         // Update indices and offsets for the next iteration.
@@ -186,7 +2024,437 @@ where
         flash_offset += chunk_bytes;
         data_offset += chunk_bytes;
         remaining_bytes -= chunk_bytes;
+
+        // Give slow targets time to settle before the next write.
+        if remaining_bytes > 0 && !options.chunk_delay.is_zero() {
+            log::debug!("Waiting {} ms before next chunk...", options.chunk_delay.as_millis());
+            wait_keeping_alive(gdb, options.chunk_delay, options.keep_alive).await?;
+        }
+
+        // Stop at the chunk boundary once the time budget is spent, the written chunks stay valid.
+        if let Some(max_duration) = options.max_duration {
+            if remaining_bytes > 0 && upload_start.elapsed() >= max_duration {
+                stats.incomplete = true;
+                stats.warn(format!("Upload stopped after {chunk_idx} of {chunks_count} chunks, time budget of {} ms spent.",
+                    max_duration.as_millis()
+                ));
+                break;
+            }
+        }
     }
 
-    Ok(())
-}
\ No newline at end of file
+    if adaptive_bounds.is_some() {
+        log::info!("Adaptive chunk size settled at {current_chunk_size} B.");
+        log::debug!("Chunk size trajectory: {chunk_size_trajectory:?}");
+    }
+
+    if options.cumulative_checksum {
+        log::info!("Running checksum of the image: {running_checksum:#010x}.");
+    }
+
+    if stats.unverified_chunks > 0 && options.verify_sample.is_none() {
+        stats.warn(format!("{} chunks written without verification, the copy function returns no checksum. Use --verify-sample.",
+            stats.unverified_chunks
+        ));
+    }
+
+    if let Some(sample) = &options.verify_sample {
+        stats.verified_chunks = select_sampled_chunks(written_chunks.len(), sample);
+        stats.verify_seed = Some(sample.seed);
+        log::info!("Verifying {} of {} chunks, seed {}: {:?}.",
+            stats.verified_chunks.len(), written_chunks.len(), sample.seed, stats.verified_chunks
+        );
+
+        for &verified_chunk_idx in &stats.verified_chunks {
+            let (chunk_flash_offset, data_range) = written_chunks[verified_chunk_idx].clone();
+            let host_checksum = checksum_sum32(&file_data[data_range.clone()]);
+            verify_flash_checksum(gdb, flash_target, chunk_flash_offset, data_range.len(), host_checksum).await
+                .with_context(|| format!("verification of chunk {verified_chunk_idx} at flash offset {chunk_flash_offset:#x}"))?;
+        }
+    }
+
+    if let Some(spot_check) = &options.spot_check {
+        stats.verify_seed = Some(spot_check.seed);
+        stats.spot_checked_words = spot_check_words(gdb, flash_target, flash_start_offset, &file_data[..bytes_trasfered], spot_check).await?;
+    }
+
+    stats.chunks_count = chunk_idx;
+    stats.planned_chunks_count = chunks_count;
+    stats.bytes_transferred = bytes_trasfered;
+    stats.chunk_size = current_chunk_size;
+    stats.elapsed = upload_start.elapsed();
+
+    check_mismatched_chunks(&mismatched_chunks, chunk_idx)?;
+    Ok(stats)
+}
+
+/// Chunk still mismatching after its retries, collected with [`UploadOptions::continue_on_mismatch`].
+struct MismatchedChunk {
+    index: usize,
+    flash_range: Range<usize>,
+    host_checksum: u32,
+    target_checksum: u32,
+}
+
+/// Fails an upload with [`UploadOptions::continue_on_mismatch`] if any chunk mismatched, the flash ranges
+/// of adjacent mismatching chunks are merged.
+///
+/// # Parameters
+/// - `mismatched_chunks`: The mismatching chunks in upload order.
+/// - `chunks_count`: The number of chunks of the upload.
+///
+/// # Returns
+/// An `io::Result<()>`, a checksum mismatch error with the checksums of the first mismatching chunk, described e.g. as
+/// `3 of 40 chunks mismatched (2, 3, 17) at flash offsets 0x2000..0x4000, 0x11000..0x12000`.
+fn check_mismatched_chunks(mismatched_chunks: &[MismatchedChunk], chunks_count: usize) -> io::Result<()> {
+    let Some(first) = mismatched_chunks.first() else {
+        return Ok(());
+    };
+
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for chunk in mismatched_chunks {
+        match ranges.last_mut() {
+            Some(last_range) if last_range.end == chunk.flash_range.start => last_range.end = chunk.flash_range.end,
+            _ => ranges.push(chunk.flash_range.clone()),
+        }
+    }
+
+    let indices = mismatched_chunks.iter().map(|chunk| chunk.index.to_string()).collect::<Vec<_>>().join(", ");
+    let ranges = ranges.iter().map(|range| format!("{:#x}..{:#x}", range.start, range.end)).collect::<Vec<_>>().join(", ");
+
+    Err(io::Error::from(GdbError::ChecksumMismatch { host_checksum: first.host_checksum, target_checksum: first.target_checksum }))
+        .with_context(|| format!("{} of {chunks_count} chunks mismatched ({indices}) at flash offsets {ranges}, first", mismatched_chunks.len()))
+}
+
+/// Outcome of a single self test step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfTestStep {
+    /// Human readable step name.
+    pub name: &'static str,
+    /// Whether the step passed.
+    pub passed: bool,
+    /// Details of the outcome, e.g. the failure reason.
+    pub details: String,
+}
+
+impl SelfTestStep {
+    fn new(name: &'static str, result: io::Result<String>) -> Self {
+        match result {
+            Ok(details) => Self { name, passed: true, details },
+            Err(e) => Self { name, passed: false, details: e.to_string() },
+        }
+    }
+}
+
+/// Validates the firmware loader contract with a small known pattern.
+///
+/// The steps are:
+/// 1. RAM write - the pattern is restored into the RAM buffer and read back.
+/// 2. Copy - the copy function copies the pattern to `scratch_offset`.
+/// 3. Checksum - the returned checksum is compared with the host one.
+/// 4. Readback - optionally, the flash is read back and compared with the pattern,
+///    only possible if the flash is memory mapped.
+///
+/// Steps following a failed one are not run. Everything happens within the scratch region,
+/// its start gets overwritten.
+///
+/// # Parameters
+/// - `gdb`: A mutable reference to an active GDB connection, the target must be halted.
+/// - `ram_buffer`: The RAM buffer on the target device.
+/// - `flash_target`: The flash description with the copy function and address base.
+/// - `scratch`: The flash region which may be overwritten by the test.
+/// - `pattern_size`: The number of bytes of the test pattern.
+/// - `readback`: Whether the readback step is run.
+///
+/// # Returns
+/// An `io::Result` with the outcome of every run step, or an error if the pattern does not fit
+/// the scratch region or the workspace cannot be prepared.
+pub async fn self_test(
+    gdb: &mut Gdb,
+    ram_buffer: &RamBuffer,
+    flash_target: &FlashTarget,
+    scratch: &ScratchRegion,
+    pattern_size: usize,
+    readback: bool
+) -> io::Result<Vec<SelfTestStep>> {
+    scratch.ensure_fits(pattern_size)?;
+    let scratch_offset = scratch.offset;
+
+    let pattern = (0..pattern_size).map(|idx| (idx as u8) ^ 0xA5).collect::<Vec<u8>>();
+    let host_checksum = flash_target.checksum_width.mask(checksum_sum32(&pattern));
+
+    let workspace = TmpWorkspace::create().await?;
+    let pattern_abs_file_path = workspace.save_chunk(0, 0, &pattern).await?;
+
+    let mut steps = Vec::new();
+
+    let ram_write_result = async {
+        let restored_bytes = gdb.write_binary_file_to_mem(&ram_buffer.location(), &pattern_abs_file_path).await?;
+        check_restored_bytes(restored_bytes, pattern_size)?;
+        let readback = gdb.read_memory_at(&ram_buffer.location(), pattern_size).await?;
+        if readback != pattern {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "RAM buffer content differs from the pattern"));
+        }
+        Ok(format!("{restored_bytes} B restored and read back"))
+    }.await;
+    steps.push(SelfTestStep::new("RAM write", ram_write_result));
+    if steps.iter().any(|step| !step.passed) {
+        return Ok(steps);
+    }
+
+    let copy_result = async {
+        let target_checksum = flash_target.call_copy(gdb, scratch_offset, pattern_size, None).await?;
+        if let Some(error_value) = target_checksum.filter(|&checksum| flash_target.copy_error_value == Some(checksum) && checksum != host_checksum) {
+            return Err(io::Error::other(format!("Copy function reported error value {error_value:#x}")));
+        }
+        Ok(target_checksum)
+    }.await;
+    let target_checksum = copy_result.as_ref().ok().copied().flatten();
+    steps.push(SelfTestStep::new("Copy", copy_result.map(|checksum| match checksum {
+        Some(checksum) => format!("returned {checksum:#x}"),
+        None => String::from("returned void"),
+    })));
+
+    if let Some(target_checksum) = target_checksum {
+        let checksum_result = if target_checksum == host_checksum {
+            Ok(format!("host={host_checksum:#x} target={target_checksum:#x}"))
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Checksum not match host={host_checksum:#x} target={target_checksum:#x}")
+            ))
+        };
+        steps.push(SelfTestStep::new("Checksum", checksum_result));
+    }
+
+    if readback && steps.iter().all(|step| step.passed) {
+        let readback_result = async {
+            let flash_address = flash_target.readback_address(scratch_offset)?;
+            let flash_bytes = gdb.read_memory(flash_address, pattern_size).await?;
+            match flash_bytes.iter().zip(&pattern).position(|(target, host)| target != host) {
+                Some(first_offset) => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Flash differs from the pattern at offset {first_offset:#x}")
+                )),
+                None => Ok(format!("{pattern_size} B at {flash_address:#x} match")),
+            }
+        }.await;
+        steps.push(SelfTestStep::new("Readback", readback_result));
+    }
+
+    Ok(steps)
+}
+
+/// Test pattern written across a flash region to check its health.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TestPattern {
+    /// Alternating `0xAA` and `0x55` bytes, neighbouring bits differ.
+    Aa55,
+    /// Walking ones, `0x01`, `0x02`, ... `0x80` repeated.
+    Walking,
+    /// Incrementing bytes, wrapping at `0xFF`, a misplaced write shows as a shifted value.
+    Counter,
+    /// All bits cleared.
+    Zero,
+    /// All bits set, the content of erased flash.
+    Ff,
+}
+
+impl TestPattern {
+    /// Generates `length` bytes of the pattern.
+    pub fn generate(self, length: usize) -> Vec<u8> {
+        (0..length)
+            .map(|idx| match self {
+                TestPattern::Aa55 => if idx % 2 == 0 { 0xAA } else { 0x55 },
+                TestPattern::Walking => 1 << (idx % 8),
+                TestPattern::Counter => idx as u8,
+                TestPattern::Zero => 0x00,
+                TestPattern::Ff => 0xFF,
+            })
+            .collect()
+    }
+}
+
+/// Checks a written region chunk by chunk with the flash checksum function and locates the bad flash addresses.
+///
+/// A mismatching chunk is read back and compared byte by byte if `readback` is set,
+/// otherwise the whole chunk is reported.
+///
+/// # Parameters
+/// - `gdb`: A mutable reference to an active GDB connection.
+/// - `flash_target`: The flash description with the checksum function and the flash base the addresses are reported at.
+/// - `plan`: The plan the data was written with.
+/// - `data`: The written data.
+/// - `readback`: Whether mismatching chunks are read back, the flash must be memory mapped.
+/// - `read_chunk_size`: The maximum size of a single read back, see [`read_flash`].
+///
+/// # Returns
+/// An `io::Result` with the ranges of bad flash addresses in ascending order, empty if all chunks match.
+pub async fn find_bad_regions(
+    gdb: &mut Gdb,
+    flash_target: &FlashTarget,
+    plan: &UploadPlan,
+    data: &[u8],
+    readback: bool,
+    read_chunk_size: Option<usize>
+) -> io::Result<Vec<Range<u32>>> {
+    let mut bad_regions: Vec<Range<u32>> = Vec::new();
+
+    for chunk in &plan.chunks {
+        let host_bytes = &data[chunk.file_range.clone()];
+        let host_checksum = flash_target.checksum_width.mask(checksum_sum32(host_bytes));
+        let target_checksum = flash_target.call_checksum(gdb, chunk.flash_offset, chunk.size).await
+            .with_context(|| format!("checking chunk {} at flash offset {:#x} failed", chunk.index, chunk.flash_offset))?;
+        if target_checksum == host_checksum {
+            continue;
+        }
+
+        // Labeled with the memory mapped address, the copy address is a bare offset for flash written by offsets.
+        let flash_address = match flash_target.flash_base {
+            Some(_) => flash_target.readback_address(chunk.flash_offset)?,
+            None => flash_target.copy_address(chunk.flash_offset)?,
+        };
+        log::warn!("Chunk {} at {flash_address:#x} differs from the pattern, host={host_checksum:#x} target={target_checksum:#x}.", chunk.index);
+        if !readback {
+            push_bad_region(&mut bad_regions, flash_address..flash_address + chunk.size as u32);
+            continue;
+        }
+
+        let flash_bytes = read_flash(gdb, flash_target, chunk.flash_offset, chunk.size, read_chunk_size).await?;
+        for (idx, (target, host)) in flash_bytes.iter().zip(host_bytes).enumerate() {
+            if target != host {
+                let address = flash_address + idx as u32;
+                push_bad_region(&mut bad_regions, address..address + 1);
+            }
+        }
+    }
+
+    Ok(bad_regions)
+}
+
+/// Appends a bad region, merging it with the last one if they are adjacent.
+fn push_bad_region(bad_regions: &mut Vec<Range<u32>>, region: Range<u32>) {
+    match bad_regions.last_mut() {
+        Some(last_region) if last_region.end == region.start => last_region.end = region.end,
+        _ => bad_regions.push(region),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_restore_is_rejected_with_both_counts() {
+        let error = check_restored_bytes(0x100, 0x400).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "RAM write incomplete, restored 256 B, expected 1024 B");
+
+        assert!(check_restored_bytes(0x400, 0x400).is_ok());
+    }
+
+    /// External flash of 64 KiB with 4 KiB sectors.
+    fn small_flash() -> FlashTarget {
+        FlashTarget { sector_size: 4 * 1024, flash_size: Some(64 * 1024), ..FlashTarget::external() }
+    }
+
+    #[test]
+    fn last_chunk_of_plan_is_partial() {
+        let data = vec![0u8; 10 * 1024];
+        let plan = plan_upload(&data, 4 * 1024, 0x1000, &small_flash(), &UploadOptions::default()).unwrap();
+
+        assert_eq!(plan.chunks.len(), 3);
+        let last_chunk = plan.chunks.last().unwrap();
+        assert_eq!((last_chunk.index, last_chunk.flash_offset, last_chunk.size), (2, 0x3000, 2 * 1024));
+        assert_eq!(last_chunk.file_range, 8 * 1024..10 * 1024);
+        assert_eq!(plan.total_size, data.len());
+        assert!(plan.warnings.is_empty());
+    }
+
+    #[test]
+    fn erased_chunks_are_planned_blank() {
+        let mut data = vec![0xFFu8; 8 * 1024];
+        data[4 * 1024] = 0x00;
+        let plan = plan_upload(&data, 4 * 1024, 0, &small_flash(), &UploadOptions::default()).unwrap();
+
+        assert_eq!(plan.chunks.iter().map(|chunk| chunk.blank).collect::<Vec<_>>(), [true, false]);
+    }
+
+    #[test]
+    fn data_beyond_flash_size_is_rejected() {
+        let data = vec![0u8; 8 * 1024];
+        let error = plan_upload(&data, 4 * 1024, 60 * 1024, &small_flash(), &UploadOptions::default()).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(error.to_string(), "Data of 8192 B at offset 0xf000 exceeds flash size 65536 B");
+    }
+
+    #[test]
+    fn data_over_scratch_region_is_rejected() {
+        let data = vec![0u8; 8 * 1024];
+        let options = UploadOptions { scratch: Some(ScratchRegion { offset: 0xE000, size: 0x2000 }), ..UploadOptions::default() };
+
+        let error = plan_upload(&data, 4 * 1024, 0xD000, &small_flash(), &options).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(error.to_string(), "Range 0xd000..0xf000 overlaps the scratch region 0xe000..0x10000");
+
+        // Ending right at the region does not overlap it.
+        assert!(plan_upload(&data, 4 * 1024, 0xC000, &small_flash(), &options).is_ok());
+    }
+
+    #[test]
+    fn misaligned_plan_is_warned_about() {
+        let data = vec![0u8; 8 * 1024];
+        let misaligned_offset = plan_upload(&data, 4 * 1024, 0x1100, &small_flash(), &UploadOptions::default()).unwrap();
+        assert_eq!(misaligned_offset.warnings, ["Chunk size 4096 B or flash offset 0x1100 is not a multiple of sector size 4096 B."]);
+
+        let misaligned_chunk = plan_upload(&data, 3 * 1024, 0x1000, &small_flash(), &UploadOptions::default()).unwrap();
+        assert_eq!(misaligned_chunk.warnings.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn concurrent_workspaces_do_not_share_files() {
+        let first = TmpWorkspace::create().await.unwrap();
+        let second = TmpWorkspace::create().await.unwrap();
+        assert_ne!(first.dir, second.dir);
+
+        let first_chunk = first.save_chunk(0, 0, &[1, 2, 3]).await.unwrap();
+        let second_chunk = second.save_chunk(0, 0, &[4, 5, 6]).await.unwrap();
+        assert_ne!(first_chunk.file_name(), second_chunk.file_name());
+
+        let second_dir = second.dir.clone();
+        drop(first);
+        assert!(!first_chunk.exists());
+        assert_eq!(std::fs::read(&second_chunk).unwrap(), [4, 5, 6]);
+
+        drop(second);
+        assert!(!second_dir.exists());
+    }
+
+    #[test]
+    fn checksum_is_masked_to_its_width() {
+        // GDB prints a negative `int` sign extended.
+        assert_eq!(ChecksumWidth::W8.mask(0xFFFF_FF80), 0x80);
+        assert_eq!(ChecksumWidth::W16.mask(0xFFFF_8001), 0x8001);
+        assert_eq!(ChecksumWidth::W32.mask(0xFFFF_8001), 0xFFFF_8001);
+    }
+
+    #[test]
+    fn big_endian_checksum_is_swapped_within_its_width() {
+        let target = |checksum_width| FlashTarget {
+            checksum_width,
+            checksum_endianness: ChecksumEndianness::Be,
+            ..FlashTarget::external()
+        };
+        assert_eq!(target(ChecksumWidth::W32).decode_checksum(0x7856_3412), 0x1234_5678);
+        assert_eq!(target(ChecksumWidth::W16).decode_checksum(0xFFFF_3412), 0x1234);
+        assert_eq!(target(ChecksumWidth::W8).decode_checksum(0xFFFF_FF12), 0x12);
+    }
+
+    #[test]
+    fn little_endian_checksum_is_only_masked() {
+        let target = FlashTarget { checksum_width: ChecksumWidth::W16, ..FlashTarget::external() };
+        assert_eq!(target.checksum_endianness, ChecksumEndianness::Le);
+        assert_eq!(target.decode_checksum(0xFFFF_3412), 0x3412);
+    }
+}
@@ -0,0 +1,65 @@
+use std::sync::{Mutex, OnceLock};
+
+use crate::gdb::PARSE_TRACE_TARGET;
+
+/// Logger forwarding records to `env_logger` and recording every warning,
+/// so the run can be failed on warnings with `--fail-on-warning`.
+struct RecordingLogger {
+    inner: env_logger::Logger,
+    warnings: Mutex<Vec<String>>,
+}
+
+impl log::Log for RecordingLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if record.level() == log::Level::Warn {
+            if let Ok(mut warnings) = self.warnings.lock() {
+                warnings.push(record.args().to_string());
+            }
+        }
+        self.inner.log(record);
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+static LOGGER: OnceLock<RecordingLogger> = OnceLock::new();
+
+/// Installs the logger, warnings are always enabled.
+///
+/// # Parameters
+/// - `debug`: Whether debug records are printed too.
+/// - `trace_parsing`: Whether the parsing steps logged to [`PARSE_TRACE_TARGET`] are printed.
+/// - `write_style`: Whether the level names are colored.
+pub fn init(debug: bool, trace_parsing: bool, write_style: env_logger::WriteStyle) {
+    let mut builder = env_logger::builder();
+    if trace_parsing {
+        builder.filter_module(PARSE_TRACE_TARGET, log::LevelFilter::Trace);
+    }
+
+    let inner = builder
+        .filter_level(if debug { log::LevelFilter::Debug } else { log::LevelFilter::Warn })
+        .write_style(write_style)
+        .format_timestamp_millis()
+        .format_file(true)
+        .format_line_number(true)
+        .build();
+    let max_level = inner.filter();
+
+    let logger = LOGGER.get_or_init(|| RecordingLogger { inner, warnings: Mutex::new(vec![]) });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+/// Returns all warnings logged so far.
+pub fn warnings() -> Vec<String> {
+    LOGGER.get()
+        .and_then(|logger| logger.warnings.lock().ok().map(|warnings| warnings.clone()))
+        .unwrap_or_default()
+}
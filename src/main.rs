@@ -1,19 +1,48 @@
+mod color;
+mod config;
+mod diagnostics;
+mod elf;
+mod error;
 mod gdb;
 mod loader;
+mod logger;
+mod manifest;
+mod progress;
+mod server;
+mod session;
+mod shared_gdb;
+mod summary;
+mod value;
 
-use std::path::PathBuf;
-use clap::Parser;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use clap::{CommandFactory, Parser, Subcommand};
+use regex::Regex;
 use tokio::io;
 
-use gdb::Gdb;
-use loader::upload_binary_file_to_external_flash;
+use color::ColorChoice;
+use config::{load_commands_file, load_config, Config, TimeoutsConfig};
+use manifest::{check_overlaps, load_manifest, LoadedImage};
+use error::{GdbError, ResultExt};
+use gdb::{extract_breakpoint_number, BreakpointLocations, Gdb, GdbOptions, LineEnding, MonitorCommands, Probe, ResponseEncoding, ResponseLimits, StopReason};
+use session::{Phase, Session, SessionConfig, Staging, UploadSteps};
+use loader::{checksum_sum32, ensure_erase_aligned, execute_plan, find_bad_regions, pad_last_sector, plan_upload, prepare_upload, query_flash_geometry, self_test, verify_flash_checksum, AdaptiveChunking, ChecksumEndianness, ChecksumWidth, ChunkResult, CopyConfirmation, CopyReturns, FlashTarget, DEFAULT_COPY_FUNCTION_SIGNATURE, MismatchAction, RamBuffer, RamWriteMethod, RetryPolicy, ScratchRegion, SpotCheck, TestPattern, UploadOptions, UploadPlan, UploadProgress, UploadStats, VerifySample};
 
 #[derive(Debug, Parser)]
 #[command(version, about = "Image dithering and palette extraction tool", long_about = None)]
+#[command(group(clap::ArgGroup::new("erased_flash").args(["assume_erased", "erase"])))]
+#[command(group(clap::ArgGroup::new("sampling").args(["verify_sample_percent", "spot_check_count"]).multiple(true)))]
 struct Cli {
-    /// Input binary file path (required).
-    #[arg(short = 'b', long = "binary", value_name = "BINARY_PATH", required = true)]
-    binary_path: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Input binary file path (required unless a subcommand is given), `-` reads the image from stdin.
+    #[arg(short = 'b', long = "binary", value_name = "BINARY_PATH")]
+    binary_path: Option<PathBuf>,
+
+    /// TOML manifest of images flashed one after another in a single session, instead of --binary.
+    #[arg(long = "manifest", value_name = "MANIFEST_PATH", conflicts_with_all = ["binary_path", "flash_save_offset", "offset_symbol", "two_phase", "rmw"])]
+    manifest_path: Option<PathBuf>,
     
     /// arm-none-eabi-gdb executive path (required).
     #[arg(short = 'g', long = "gdb", value_name = "GDB_PATH", required = true)]
@@ -23,103 +52,1522 @@ struct Cli {
     #[arg(short = 'e', long = "elf", value_name = "ELF_PATH", required = true)]
     elf_path: PathBuf,
 
+    /// Additional ELF whose symbols are loaded after connecting, as `<path>[@text_address]`, can be repeated.
+    /// Useful if e.g. the copy function lives in a bootloader image.
+    #[arg(long = "extra-elf", value_name = "PATH[@ADDR]", value_parser = parse_extra_elf)]
+    extra_elfs: Vec<ExtraElf>,
+
+    /// Symbol which must exist in the firmware, checked before anything is done on the target, can be repeated.
+    #[arg(long = "require-symbol", value_name = "NAME")]
+    required_symbols: Vec<String>,
+
     /// Name of target function at which program should break before uploading.
     #[arg(short = 'B', long = "break", value_name = "BREAK_FUN", default_value_t = String::from("Loader_Breakpoint"))]
     break_function_name: String,
 
+    /// Reset into halt, so the breakpoint is set before any user code runs, for targets booting past it
+    /// before it is set.
+    #[arg(long = "halt-before-break", default_value_t = false, conflicts_with = "break_entry")]
+    halt_before_break: bool,
+
+    /// Read the PC twice after the halt and fail if it moved, for probes reporting a halt of a core which keeps running.
+    #[arg(long = "verify-halt", default_value_t = false)]
+    verify_halt: bool,
+
+    /// Flash the ELF with `load` after the reset, before the breakpoint is set, e.g. to update the firmware
+    /// with the loader functions in internal flash.
+    #[arg(long = "load", default_value_t = false)]
+    load_elf: bool,
+
+    /// Location of a break function with several locations, e.g. inlined, to stop at: `all` or its index from 1,
+    /// the other locations are disabled.
+    #[arg(long = "break-location", value_name = "all|INDEX", default_value = "all", value_parser = parse_break_location, conflicts_with_all = ["break_address", "break_entry"])]
+    break_location: BreakpointLocations,
+
+    /// Code address at which program should break before uploading, for targets without symbols.
+    #[arg(long = "break-address", value_name = "ADDRESS", value_parser = parse_code_address, conflicts_with = "break_function_name")]
+    break_address: Option<u32>,
+
+    /// Break at the ELF entry point, right after reset before main runs, with a temporary breakpoint.
+    #[arg(long = "break-entry", default_value_t = false, conflicts_with_all = ["break_function_name", "break_address", "breakpoint_hit_count"])]
+    break_entry: bool,
+
+    /// Stop at the Nth hit of the breakpoint instead of the first, for break functions called
+    /// periodically, e.g. from an init loop. Earlier hits are ignored by GDB.
+    #[arg(long = "breakpoint-hit-count", value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    breakpoint_hit_count: u32,
+
     /// Target RAM buffer name.
     #[arg(short = 'r', long = "rambuf", value_name = "RAM_BUFFER", default_value_t = String::from("loader_ram_buffer"))]
     ram_buffer_name: String,
 
-    /// Target copy function name.
-    #[arg(short = 'c', long = "copy", value_name = "COPY_FUN", default_value_t = String::from("loader_copy_to_ext_flash"))]
-    copy_function_name: String,
+    /// Target RAM buffer address, for firmwares without symbols.
+    #[arg(long = "rambuf-address", value_name = "ADDRESS", value_parser = parse_ram_address, conflicts_with = "ram_buffer_name")]
+    ram_buffer_address: Option<u32>,
+
+    /// Flash memory to upload to, selects the default copy function and address base.
+    #[arg(short = 'f', long = "flash", value_name = "FLASH", value_enum, default_value_t = FlashKind::External)]
+    flash_kind: FlashKind,
+
+    /// Target copy function name, overrides the one of the selected flash preset.
+    #[arg(short = 'c', long = "copy", value_name = "COPY_FUN")]
+    copy_function_name: Option<String>,
+
+    /// Target copy function address, calls it by address instead of by name, for firmwares without symbols.
+    #[arg(long = "copy-address", value_name = "ADDRESS", value_parser = parse_u32, conflicts_with = "copy_function_name")]
+    copy_function_address: Option<u32>,
+
+    /// Type of the copy function called with --copy-address.
+    #[arg(long = "copy-signature", value_name = "SIGNATURE", default_value_t = String::from(DEFAULT_COPY_FUNCTION_SIGNATURE))]
+    copy_function_signature: String,
+
+    /// What the copy function returns, with void the return value is not parsed and chunks are not compared,
+    /// use --verify-sample to verify the written data at the end.
+    #[arg(long = "copy-returns", value_enum, default_value_t = CopyReturns::Checksum)]
+    copy_returns: CopyReturns,
+
+    /// Fast mode for a copy function returning void, shorthand of --copy-returns void.
+    #[arg(long = "write-only", default_value_t = false, conflicts_with_all = ["copy_returns", "cumulative_checksum", "copy_error_value"])]
+    write_only: bool,
 
     /// GDB server address.
     #[arg(short = 's', long = "server", value_name = "SERVER-ADDRESS", default_value_t = String::from("localhost:61234"))]
     server_address: String,
 
+    /// GDB server command started before connecting and stopped at the end, e.g. "openocd -f board/stm32u5.cfg".
+    #[arg(long = "server-launch", value_name = "CMD")]
+    server_launch: Option<String>,
+
+    /// Pattern of the line the launched server prints once it accepts connections [default: per --probe].
+    #[arg(long = "server-ready-regex", value_name = "REGEX", requires = "server_launch")]
+    server_ready_regex: Option<Regex>,
+
+    /// Time in milliseconds to wait for the readiness line of the launched server, connection is retried afterwards.
+    #[arg(long = "server-ready-timeout", value_name = "MILLIS", default_value_t = 10_000, requires = "server_launch")]
+    server_ready_timeout_ms: u64,
+
+    /// Symbol holding a build ID or version, its value in the ELF must match the one on the target.
+    #[arg(long = "build-id-symbol", value_name = "SYMBOL")]
+    build_id_symbol: Option<String>,
+
+    /// Target function returning the loader protocol version of the firmware, checked against
+    /// --expected-protocol-version before anything is written.
+    #[arg(long = "protocol-version-fn", value_name = "FUN", requires = "expected_protocol_version")]
+    protocol_version_function_name: Option<String>,
+
+    /// Loader protocol version the firmware must report, see --protocol-version-fn.
+    #[arg(long = "expected-protocol-version", value_name = "VERSION", value_parser = parse_u32, requires = "protocol_version_function_name")]
+    expected_protocol_version: Option<u32>,
+
+    /// Target function called once before uploading, e.g. to unlock flash.
+    #[arg(long = "pre-session-call", value_name = "FUN")]
+    pre_session_function_name: Option<String>,
+
+    /// Target function called once after uploading, also if upload failed, e.g. to lock flash.
+    #[arg(long = "post-session-call", value_name = "FUN")]
+    post_session_function_name: Option<String>,
+
+    /// Value the copy function returns on failure, distinguished from a checksum match.
+    #[arg(long = "copy-error-value", value_name = "VALUE", value_parser = parse_u32)]
+    copy_error_value: Option<u32>,
+
+    /// Target variable with the copy status (0 = success), read when a chunk checksum equals the error value.
+    #[arg(long = "copy-status-var", value_name = "VARIABLE", requires = "copy_error_value")]
+    copy_status_variable: Option<String>,
+
+    /// Flash sector size in bytes, overrides the one of the selected flash preset.
+    #[arg(long = "sector-size", value_name = "BYTES")]
+    sector_size: Option<usize>,
+
+    /// Address the flash is memory mapped at, flash is read back at this base plus the offset
+    /// [default: none for external, 0x8000000 for internal flash].
+    #[arg(long = "flash-base", value_name = "ADDRESS", value_parser = parse_u32)]
+    flash_base: Option<u32>,
+
+    /// Total flash size in bytes, uploads exceeding it are rejected.
+    #[arg(long = "flash-size", value_name = "BYTES")]
+    flash_size: Option<usize>,
+
+    /// Query sector and flash size from the target, falling back to CLI values if unavailable.
+    #[arg(long = "query-geometry", default_value_t = false)]
+    query_geometry: bool,
+
+    /// Target function returning the flash sector size, used with --query-geometry.
+    #[arg(long = "sector-size-fn", value_name = "FUN", default_value_t = String::from("loader_get_sector_size"))]
+    sector_size_function_name: String,
+
+    /// Target function returning the total flash size, used with --query-geometry.
+    #[arg(long = "flash-size-fn", value_name = "FUN", default_value_t = String::from("loader_get_flash_size"))]
+    flash_size_function_name: String,
+
     /// Chunk size, should be multiple of FLASH memory unit size.
     #[arg(short = 'C', long = "chunk", value_name = "CHUNK_SIZE", default_value_t = 64 * 1024)]
     chunk_size_bytes: usize,
 
+    /// Split chunks larger than the RAM buffer into buffer sized writes, each copied separately
+    /// but verified with a single checksum of the whole chunk.
+    #[arg(long = "auto-split", default_value_t = false)]
+    auto_split: bool,
+
+    /// Program page size of the flash, e.g. 256 for NOR flashes, copy calls are split so none crosses a page boundary,
+    /// the chunk is still verified with a single checksum.
+    #[arg(long = "program-page-size", value_name = "BYTES", value_parser = clap::value_parser!(u32).range(1..))]
+    program_page_size: Option<u32>,
+
+    /// Halve the chunk size when a chunk fails and double it after a streak of successful chunks.
+    #[arg(long = "adaptive-chunk", default_value_t = false)]
+    adaptive_chunk: bool,
+
+    /// Smallest chunk size of --adaptive-chunk [default: sector size].
+    #[arg(long = "min-chunk", value_name = "BYTES", requires = "adaptive_chunk")]
+    min_chunk: Option<usize>,
+
+    /// Largest chunk size of --adaptive-chunk [default: RAM buffer size].
+    #[arg(long = "max-chunk", value_name = "BYTES", requires = "adaptive_chunk")]
+    max_chunk: Option<usize>,
+
+    /// Seed the copy function with the running checksum of the previous chunks as its third argument,
+    /// for firmwares checksumming the image cumulatively.
+    #[arg(long = "cumulative-checksum", default_value_t = false)]
+    cumulative_checksum: bool,
+
+    /// Verify this percentage of chunks, plus the first and last one, with the flash checksum function after writing.
+    #[arg(long = "verify-sample", value_name = "PERCENT", value_parser = parse_percent)]
+    verify_sample_percent: Option<f64>,
+
+    /// Seed selecting the chunks of --verify-sample and the words of --spot-check, random if not given.
+    #[arg(long = "verify-seed", value_name = "SEED", requires = "sampling")]
+    verify_seed: Option<u64>,
+
+    /// Read this many random words of the written data back from flash after writing and compare them with the file,
+    /// catching address errors the order-independent Sum32 checksums miss. Needs memory mapped flash, see --flash-base.
+    #[arg(long = "spot-check", value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    spot_check_count: Option<u32>,
+
+    /// Compare every chunk with the flash checksum function first and write only the chunks which differ,
+    /// to recover from an interrupted upload or to update an image incrementally.
+    #[arg(long = "resume-verify", default_value_t = false)]
+    resume_verify: bool,
+
+    /// Skip chunks of only 0xFF bytes. Valid only on erased flash, so it requires --assume-erased or --erase.
+    #[arg(long = "skip-blank", default_value_t = false, requires = "erased_flash")]
+    skip_blank: bool,
+
+    /// Read the RAM buffer back after every write, so a corrupted RAM write is reported instead of a flash checksum mismatch.
+    #[arg(long = "verify-ram", default_value_t = false)]
+    verify_ram: bool,
+
+    /// Size of the reads when flash is read back, e.g. for --on-mismatch dump or a pattern readback, independent
+    /// of --chunk. Large reads are much faster on memory mapped QSPI flash [default: chunk size].
+    #[arg(long = "verify-read-chunk-size", value_name = "BYTES", value_parser = clap::value_parser!(u32).range(1..))]
+    verify_read_chunk_size: Option<u32>,
+
+    /// Assert that the flash region is erased, for --skip-blank.
+    #[arg(long = "assume-erased", default_value_t = false)]
+    assume_erased: bool,
+
+    /// Erase the flash region with the probe erase command before the upload. Probes without
+    /// a ranged erase command erase the whole flash.
+    #[arg(long = "erase", default_value_t = false)]
+    erase: bool,
+
+    /// Time budget of the upload in milliseconds, once spent the upload stops at the next chunk boundary
+    /// and the run exits with code 9 after reporting the progress.
+    #[arg(long = "max-upload-duration", value_name = "MILLIS", conflicts_with_all = ["two_phase", "rmw"])]
+    max_upload_duration_ms: Option<u64>,
+
+    /// Flash offset of the scratch region used by selftest, uploads must not overlap it if --scratch-size is given.
+    #[arg(long = "scratch-offset", value_name = "FLASH_OFFSET", value_parser = parse_u32, global = true)]
+    scratch_offset: Option<u32>,
+
+    /// Size of the scratch region in bytes [default for selftest: pattern size].
+    #[arg(long = "scratch-size", value_name = "BYTES", requires = "scratch_offset", global = true)]
+    scratch_size: Option<usize>,
+
     /// Offset at which saving will start, should be multiple of FLASH memory unit size.
-    #[arg(short = 'o', long = "offset", value_name = "FLASH_OFFSET", default_value_t = 0x0)]
+    #[arg(short = 'o', long = "offset", value_name = "FLASH_OFFSET", default_value_t = 0x0, global = true)]
     flash_save_offset: usize,
+
+    /// ELF symbol whose value is used as the flash offset instead of --offset, e.g. `__ext_flash_data_start`.
+    /// Values at or above the flash address base are treated as absolute addresses.
+    #[arg(long = "offset-symbol", value_name = "SYMBOL", conflicts_with = "flash_save_offset")]
+    offset_symbol: Option<String>,
+
+    /// Write the image to the staging region at --staging-offset, verify it as a whole and only then
+    /// activate it at --offset with the commit function.
+    #[arg(long = "two-phase", default_value_t = false, requires = "staging_offset", conflicts_with = "rmw")]
+    two_phase: bool,
+
+    /// Flash offset of the staging region of --two-phase.
+    #[arg(long = "staging-offset", value_name = "FLASH_OFFSET", value_parser = parse_u32, requires = "two_phase")]
+    staging_offset: Option<u32>,
+
+    /// Target function activating the staged image, used with --two-phase.
+    #[arg(long = "commit-fn", value_name = "FUN", default_value_t = String::from("loader_commit"), requires = "two_phase")]
+    commit_function_name: String,
+
+    /// File of GDB commands, one per line, `#` starts a comment, run after halting the target and before the upload.
+    #[arg(long = "commands-file", value_name = "PATH")]
+    commands_file: Option<PathBuf>,
+
+    /// GDB script run at startup with `-x`, e.g. probe setup or custom commands, before the tool connects.
+    #[arg(long = "gdb-script", value_name = "PATH")]
+    gdb_script: Option<PathBuf>,
+
+    /// Keep running the commands file after a command failed, only logging the error.
+    #[arg(long = "continue-on-error", default_value_t = false, requires = "commands_file")]
+    continue_on_error: bool,
+
+    /// Print the chunk plan (index, file range, flash offset, size, blank) before uploading.
+    #[arg(long = "show-plan", value_name = "FORMAT", value_enum, num_args = 0..=1, default_missing_value = "table")]
+    show_plan: Option<PlanFormat>,
+
+    /// Only print the plan of --show-plan, neither GDB is started nor anything is written.
+    #[arg(long = "dry-run", default_value_t = false, requires = "show_plan")]
+    dry_run: bool,
+
+    /// Configuration file (TOML), command line values take precedence over it.
+    #[arg(long = "config", value_name = "CONFIG_PATH")]
+    config_path: Option<PathBuf>,
+
+    #[command(flatten)]
+    timeouts: TimeoutsConfig,
+
+    /// Number of retries of a failed chunk.
+    #[arg(long = "retries", value_name = "RETRIES", default_value_t = 0)]
+    retries: usize,
+
+    /// Delay in milliseconds before the first chunk retry, doubled on every next attempt.
+    #[arg(long = "retry-backoff-ms", value_name = "MILLIS", default_value_t = 50)]
+    retry_backoff_ms: u64,
+
+    /// Maximal delay in milliseconds between chunk retries.
+    #[arg(long = "retry-backoff-max-ms", value_name = "MILLIS", default_value_t = 2000)]
+    retry_backoff_max_ms: u64,
+
+    /// Halt the target before retrying a chunk to resynchronize the core state.
+    #[arg(long = "retry-halt", default_value_t = false)]
+    retry_halt: bool,
+
+    /// Unix socket of a front-end to stream NDJSON progress events to, next to the progress printed on stdout.
+    #[arg(long = "progress-socket", value_name = "PATH")]
+    progress_socket: Option<PathBuf>,
+
+    /// Local TCP port of a front-end to stream NDJSON progress events to, see --progress-socket.
+    #[arg(long = "progress-port", value_name = "PORT", conflicts_with = "progress_socket")]
+    progress_port: Option<u16>,
+
+    /// Print a table summarizing the run at its end, also if it failed.
+    #[arg(long = "summary", default_value_t = false)]
+    summary: bool,
+
+    /// Exit with an error if any warning was logged, even if the upload succeeded.
+    #[arg(long = "fail-on-warning", default_value_t = false)]
+    fail_on_warning: bool,
+
+    /// Only compare the checksum of the flash range at --offset of --length bytes with this expected value,
+    /// computed by the target checksum function, no binary is needed.
+    #[arg(long = "verify-checksum-only", value_name = "EXPECTED", value_parser = parse_u32, requires = "length")]
+    verify_checksum_only: Option<u32>,
+
+    /// Length in bytes of the flash range checked by --verify-checksum-only.
+    #[arg(long = "length", value_name = "BYTES", requires = "verify_checksum_only")]
+    length: Option<usize>,
+
+    /// Target checksum function name, overrides the one of the selected flash preset.
+    #[arg(long = "checksum-fn", value_name = "FUN")]
+    checksum_function_name: Option<String>,
+
+    /// Enable semihosting and print target output, e.g. prints of the copy function, prefixed with `[target]`.
+    #[arg(long = "show-target-output", default_value_t = false)]
+    show_target_output: bool,
+
+    /// Pattern of the line the copy function prints after each copy, awaited after the call. Its named groups
+    /// `bytes` and `checksum` are checked against the requested count and used as the chunk checksum.
+    #[arg(long = "confirm-regex", value_name = "REGEX")]
+    confirm_regex: Option<Regex>,
+
+    /// Time in milliseconds the --confirm-regex line is awaited after the copy call returned.
+    #[arg(long = "target-output-timeout", value_name = "MILLIS", default_value_t = 1000, requires = "confirm_regex")]
+    target_output_timeout_ms: u64,
+
+    /// Directory a diagnostic bundle (registers, backtrace, stop reason, GDB transcript) is written to on failure.
+    #[arg(long = "dump-on-failure", value_name = "DIR")]
+    dump_on_failure_dir: Option<PathBuf>,
+
+    /// Only connect, print GDB version and target status and quit, to check the setup without flashing.
+    #[arg(long = "connect-only", default_value_t = false)]
+    connect_only: bool,
+
+    /// Treat GDB stderr lines which are neither known benign nor known errors as hard errors.
+    #[arg(long = "log-gdb-stderr-as-error", default_value_t = false)]
+    unclassified_stderr_as_error: bool,
+
+    /// Pad the last chunk up to the sector size, so the written region ends at a sector boundary.
+    #[arg(long = "pad-last", default_value_t = false)]
+    pad_last: bool,
+
+    /// Width in bits of the checksums returned by the copy and checksum functions, the host sum is truncated to it.
+    #[arg(long = "checksum-width", value_name = "BITS", value_enum, default_value_t = ChecksumWidth::W32)]
+    checksum_width: ChecksumWidth,
+
+    /// Byte order of the checksums returned by the copy and checksum functions, `be` swaps them before the comparison.
+    #[arg(long = "checksum-endianness", value_name = "ORDER", value_enum, default_value_t = ChecksumEndianness::Le)]
+    checksum_endianness: ChecksumEndianness,
+
+    /// The copy function erases every sector it writes to, an offset inside a sector is rejected unless --rmw is given.
+    #[arg(long = "copy-erases-sectors", default_value_t = false)]
+    copy_erases_sectors: bool,
+
+    /// Read-modify-write the partially written first and last sector, so flash content around the data is kept.
+    #[arg(long = "rmw", visible_alias = "preserve-flash-region", default_value_t = false, requires = "sector_size", conflicts_with = "pad_last")]
+    rmw: bool,
+
+    /// Byte used by --pad-last.
+    #[arg(long = "pad-byte", value_name = "BYTE", value_parser = parse_u8, default_value = "0xFF")]
+    pad_byte: u8,
+
+    /// Way chunks are written into the RAM buffer, `mi` avoids temporary files.
+    #[arg(long = "ram-write", value_enum, default_value_t = RamWriteMethod::Restore)]
+    ram_write: RamWriteMethod,
+
+    /// Action on a chunk checksum mismatch, `dump` saves the host chunk and the flash read back to files.
+    #[arg(long = "on-mismatch", value_enum, default_value_t = MismatchAction::Fail)]
+    on_mismatch: MismatchAction,
+
+    /// Keep going after a chunk still mismatching after its retries and fail at the end with all mismatching chunks,
+    /// to see the whole failure pattern of a board in a single run.
+    #[arg(long = "continue-on-mismatch", default_value_t = false)]
+    continue_on_mismatch: bool,
+
+    /// Delay in milliseconds between chunks, trades throughput for reliability on finicky hardware.
+    #[arg(long = "chunk-delay-ms", value_name = "MILLIS", default_value_t = 0)]
+    chunk_delay_ms: u64,
+
+    /// Debug probe family, selects the monitor command dialect.
+    #[arg(long = "probe", value_enum, default_value_t = Probe::Openocd)]
+    probe: Probe,
+
+    /// Overrides the probe reset command, e.g. "monitor reset halt".
+    #[arg(long = "monitor-reset", value_name = "CMD")]
+    monitor_reset: Option<String>,
+
+    /// Overrides the probe reset halt command used with --halt-before-break.
+    #[arg(long = "monitor-reset-halt", value_name = "CMD")]
+    monitor_reset_halt: Option<String>,
+
+    /// Overrides the probe halt command.
+    #[arg(long = "monitor-halt", value_name = "CMD")]
+    monitor_halt: Option<String>,
+
+    /// Overrides the probe sleep command, `{millis}` is substituted.
+    #[arg(long = "monitor-sleep", value_name = "CMD")]
+    monitor_sleep: Option<String>,
+
+    /// Overrides the probe flash erase command, `{address}` and `{length}` are substituted.
+    #[arg(long = "monitor-erase", value_name = "CMD")]
+    monitor_erase: Option<String>,
+
+    /// Send a keep-alive packet after this many idle milliseconds while waiting between chunks or before retries.
+    #[arg(long = "keep-alive-ms", value_name = "MILLIS")]
+    keep_alive_ms: Option<u64>,
+
+    /// Maximal length of a single GDB output line in bytes, longer lines are split.
+    #[arg(long = "max-line-length", value_name = "BYTES", default_value_t = ResponseLimits::default().max_line_length)]
+    max_line_length: usize,
+
+    /// Maximal number of GDB output lines collected for one command.
+    #[arg(long = "max-response-lines", value_name = "LINES", default_value_t = ResponseLimits::default().max_lines)]
+    max_response_lines: usize,
+
+    /// Maximal total size of GDB output collected for one command in bytes.
+    #[arg(long = "max-response-bytes", value_name = "BYTES", default_value_t = ResponseLimits::default().max_total_bytes)]
+    max_response_bytes: usize,
+
+    /// Decoding of GDB output, lossy UTF-8 never fails on a stray byte.
+    #[arg(long = "encoding", value_name = "ENCODING", value_enum, default_value_t = ResponseEncoding::Utf8Lossy)]
+    response_encoding: ResponseEncoding,
+
+    /// Terminator of commands sent to GDB [default: crlf on Windows, lf elsewhere].
+    #[arg(long = "line-ending", value_name = "LINE_ENDING", value_enum)]
+    line_ending: Option<LineEnding>,
     
+    /// Logs every parsing step of GDB responses with the input line and the outcome.
+    #[arg(long = "trace-parsing", hide = true, default_value_t = false)]
+    trace_parsing: bool,
+
+    /// Colors chunk results, warnings and the summary, `auto` only on a terminal without `NO_COLOR` set.
+    #[arg(long = "color", value_name = "WHEN", value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Disables colors, same as `--color never`.
+    #[arg(long = "no-color", default_value_t = false, conflicts_with = "color")]
+    no_color: bool,
+
     /// Additional information about execution process (optional)
     #[arg(short = 'd', long = "debug", value_name = "DEBUG_ENABLED", default_value_t = false)]
     debug: bool  
 }
 
+/// Operations other than the default upload.
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Validates the firmware loader contract: RAM write, copy and checksum of a small pattern.
+    Selftest {
+        /// Size of the test pattern in bytes.
+        #[arg(long = "pattern-size", value_name = "BYTES", default_value_t = 256)]
+        pattern_size: usize,
+
+        /// Also read the scratch region back and compare it with the pattern, the flash must be memory mapped.
+        #[arg(long = "readback", default_value_t = false)]
+        readback: bool,
+    },
+    /// Fills the flash region at --offset with a test pattern, verifies it and reports bad addresses.
+    Pattern {
+        /// Pattern written across the region.
+        #[arg(long = "pattern", value_enum)]
+        pattern: TestPattern,
+
+        /// Length of the region in bytes.
+        #[arg(long = "length", value_name = "BYTES", value_parser = parse_u32)]
+        length: u32,
+
+        /// Read mismatching chunks back to locate the bad bytes, the flash must be memory mapped.
+        /// Without it whole chunks are reported.
+        #[arg(long = "readback", default_value_t = false)]
+        readback: bool,
+    },
+    /// Compares the binary with the flash at --offset using the checksum function, nothing is written.
+    Verify,
+    /// Reads the flash region at --offset back into a file, the flash must be memory mapped.
+    Dump {
+        /// File the region is saved to, overwritten if it exists.
+        #[arg(long = "output", value_name = "PATH")]
+        output: PathBuf,
+
+        /// Length of the region in bytes.
+        #[arg(long = "length", value_name = "BYTES", value_parser = parse_u32)]
+        length: u32,
+    },
+    /// Prints the Sum32 of the input file as the upload computes it, without GDB or a target.
+    Checksum {
+        /// Also print the checksum of every chunk of the --chunk size, as compared with the copy function result.
+        #[arg(long = "per-chunk", default_value_t = false)]
+        per_chunk: bool,
+    },
+}
+
+/// Output formats of the upload plan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum PlanFormat {
+    /// Human readable table.
+    Table,
+    /// JSON object, for scripts.
+    Json,
+}
+
+impl PlanFormat {
+    /// Renders the plan in the format.
+    fn render(&self, plan: &UploadPlan) -> String {
+        match self {
+            PlanFormat::Table => plan.render_table(),
+            PlanFormat::Json => plan.render_json(),
+        }
+    }
+}
+
+/// Flash presets selectable from the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum FlashKind {
+    /// External flash, see [`FlashTarget::external`].
+    External,
+    /// Internal MCU flash, see [`FlashTarget::internal`].
+    Internal,
+}
+
+/// Parses a `u32` given either as hexadecimal with `0x` prefix or as decimal.
+fn parse_u32(value: &str) -> Result<u32, String> {
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+    .map_err(|e| format!("invalid number '{value}': {e}"))
+}
+
+/// Parses an address which could hold a RAM buffer: inside the Cortex-M SRAM region
+/// or the external RAM regions.
+fn parse_ram_address(value: &str) -> Result<u32, String> {
+    let address = parse_u32(value)?;
+
+    let in_ram_region = (0x2000_0000..0x4000_0000).contains(&address) || (0x6000_0000..0xA000_0000).contains(&address);
+    if !in_ram_region {
+        return Err(format!("{address:#010x} is not a plausible RAM address"));
+    }
+
+    Ok(address)
+}
+
+/// Additional symbol file loaded after connecting.
+#[derive(Debug, Clone)]
+struct ExtraElf {
+    path: PathBuf,
+    text_address: Option<u32>,
+}
+
+/// Parses an extra ELF given as `<path>[@addr]`.
+fn parse_extra_elf(value: &str) -> Result<ExtraElf, String> {
+    match value.rsplit_once('@') {
+        Some((path, address)) => Ok(ExtraElf { path: PathBuf::from(path), text_address: Some(parse_u32(address)?) }),
+        None => Ok(ExtraElf { path: PathBuf::from(value), text_address: None }),
+    }
+}
+
+/// Parses a percentage in `0..=100`, with an optional `%` suffix.
+fn parse_percent(value: &str) -> Result<f64, String> {
+    let percent = value.strip_suffix('%').unwrap_or(value).parse::<f64>()
+        .map_err(|e| format!("invalid percentage '{value}': {e}"))?;
+
+    if !(0.0..=100.0).contains(&percent) {
+        return Err(format!("{percent} is not in 0..=100"));
+    }
+
+    Ok(percent)
+}
+
+/// Parses a `u8` given either as hexadecimal with `0x` prefix or as decimal.
+fn parse_u8(value: &str) -> Result<u8, String> {
+    u8::try_from(parse_u32(value)?).map_err(|e| format!("invalid byte '{value}': {e}"))
+}
+
+/// Parses the breakpoint locations to keep enabled: `all` or a 1-based location index.
+fn parse_break_location(value: &str) -> Result<BreakpointLocations, String> {
+    match value {
+        "all" => Ok(BreakpointLocations::All),
+        index => match index.parse::<u32>() {
+            Ok(index) if index > 0 => Ok(BreakpointLocations::Only(index)),
+            _ => Err(format!("expected 'all' or a location index from 1, got '{index}'")),
+        },
+    }
+}
+
+/// Parses an address which could hold code: halfword aligned and inside
+/// the Cortex-M code region or the external memory regions.
+fn parse_code_address(value: &str) -> Result<u32, String> {
+    let address = parse_u32(value)?;
+
+    let in_code_region = address < 0x2000_0000 || (0x6000_0000..0xA000_0000).contains(&address);
+    if address % 2 != 0 || !in_code_region {
+        return Err(format!("{address:#010x} is not a plausible code address"));
+    }
+
+    Ok(address)
+}
+
+impl Cli {
+    /// Builds the flash target from the selected preset and overrides.
+    fn flash_target(&self) -> FlashTarget {
+        let preset = match self.flash_kind {
+            FlashKind::External => FlashTarget::external(),
+            FlashKind::Internal => FlashTarget::internal(),
+        };
+
+        FlashTarget {
+            copy_function_name: self.copy_function_name.clone().unwrap_or(preset.copy_function_name),
+            sector_size: self.sector_size.unwrap_or(preset.sector_size),
+            flash_size: self.flash_size.or(preset.flash_size),
+            copy_error_value: self.copy_error_value,
+            copy_status_variable: self.copy_status_variable.clone(),
+            copy_function_address: self.copy_function_address,
+            copy_function_signature: self.copy_function_signature.clone(),
+            checksum_function_name: self.checksum_function_name.clone().unwrap_or(preset.checksum_function_name),
+            checksum_width: self.checksum_width,
+            checksum_endianness: self.checksum_endianness,
+            copy_confirmation: self.confirm_regex.clone().map(|pattern| CopyConfirmation {
+                pattern,
+                timeout: Duration::from_millis(self.target_output_timeout_ms),
+            }),
+            flash_base: self.flash_base.or(preset.flash_base),
+            copy_returns: match self.write_only {
+                true => CopyReturns::Void,
+                false => self.copy_returns,
+            },
+            ..preset
+        }
+    }
+
+    /// Builds the upload configuration of the session.
+    fn session_config(&self) -> SessionConfig {
+        SessionConfig {
+            ram_buffer: self.ram_buffer(),
+            flash_target: self.flash_target(),
+            chunk_size: self.chunk_size_bytes,
+            options: self.upload_options(),
+            steps: UploadSteps {
+                rmw: self.rmw,
+                erase: self.erase,
+                staging: self.staging_offset.map(|staging_offset| Staging {
+                    offset: staging_offset as usize,
+                    commit_function_name: self.commit_function_name.clone(),
+                }),
+            },
+        }
+    }
+
+    /// Returns when the output is colored, `--no-color` forces plain output.
+    fn color_choice(&self) -> ColorChoice {
+        match self.no_color {
+            true => ColorChoice::Never,
+            false => self.color,
+        }
+    }
+
+    /// Returns the RAM buffer, by address if given, otherwise by name.
+    fn ram_buffer(&self) -> RamBuffer {
+        match self.ram_buffer_address {
+            Some(address) => RamBuffer::Address(address),
+            None => RamBuffer::Symbol(self.ram_buffer_name.clone()),
+        }
+    }
+
+    /// Builds the monitor commands of the selected probe with overrides applied.
+    fn monitor_commands(&self) -> MonitorCommands {
+        let preset = self.probe.monitor_commands();
+        MonitorCommands {
+            reset: self.monitor_reset.clone().unwrap_or(preset.reset),
+            reset_halt: self.monitor_reset_halt.clone().or(preset.reset_halt),
+            halt: self.monitor_halt.clone().or(preset.halt),
+            sleep: self.monitor_sleep.clone().or(preset.sleep),
+            flash_erase: self.monitor_erase.clone().or(preset.flash_erase),
+            ..preset
+        }
+    }
+
+    /// Returns the scratch region if both its offset and size are given.
+    fn scratch_region(&self) -> Option<ScratchRegion> {
+        match (self.scratch_offset, self.scratch_size) {
+            (Some(offset), Some(size)) => Some(ScratchRegion { offset: offset as usize, size }),
+            _ => None,
+        }
+    }
+
+    /// Builds the upload options.
+    fn upload_options(&self) -> UploadOptions {
+        let verify_seed = self.verify_seed.unwrap_or_else(random_seed);
+        UploadOptions {
+            retry: RetryPolicy {
+                max_retries: self.retries,
+                backoff: Duration::from_millis(self.retry_backoff_ms),
+                max_backoff: Duration::from_millis(self.retry_backoff_max_ms),
+                halt_before_retry: self.retry_halt,
+            },
+            chunk_delay: Duration::from_millis(self.chunk_delay_ms),
+            pad_last: self.pad_last.then_some(self.pad_byte),
+            ram_write: self.ram_write,
+            on_mismatch: self.on_mismatch,
+            auto_split: self.auto_split,
+            keep_alive: self.keep_alive_ms.map(Duration::from_millis),
+            adaptive: self.adaptive_chunk.then_some(AdaptiveChunking {
+                min_chunk: self.min_chunk,
+                max_chunk: self.max_chunk,
+            }),
+            cumulative_checksum: self.cumulative_checksum,
+            verify_sample: self.verify_sample_percent.map(|percent| VerifySample {
+                percent,
+                seed: verify_seed,
+            }),
+            spot_check: self.spot_check_count.map(|count| SpotCheck {
+                count: count as usize,
+                seed: verify_seed,
+            }),
+            scratch: self.scratch_region(),
+            resume_verify: self.resume_verify,
+            max_duration: self.max_upload_duration_ms.map(Duration::from_millis),
+            skip_blank: self.skip_blank,
+            verify_ram: self.verify_ram,
+            verify_read_chunk_size: self.verify_read_chunk_size.map(|size| size as usize),
+            continue_on_mismatch: self.continue_on_mismatch,
+            program_page_size: self.program_page_size.map(|size| size as usize),
+        }
+    }
+}
+
+/// Returns a seed from the current time, printed with the verified chunks so a run can be reproduced.
+fn random_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or_default()
+}
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() {
     let cli_args = Cli::parse();
+    let needs_binary = matches!(cli_args.command, None | Some(Command::Checksum { .. } | Command::Verify)) && !cli_args.connect_only && cli_args.verify_checksum_only.is_none() && cli_args.manifest_path.is_none();
+    if needs_binary && cli_args.binary_path.is_none() {
+        Cli::command()
+            .error(clap::error::ErrorKind::MissingRequiredArgument, "the argument '--binary <BINARY_PATH>' is required")
+            .exit();
+    }
     
-    if cli_args.debug {
-        env_logger::builder()
-            .filter_level(log::LevelFilter::Debug)
-            .format_timestamp_millis()
-            .format_file(true)
-            .format_file(true)
-            .format_line_number(true)
-            .init();
+    if matches!(cli_args.command, Some(Command::Selftest { .. })) && cli_args.scratch_offset.is_none() {
+        Cli::command()
+            .error(clap::error::ErrorKind::MissingRequiredArgument, "the argument '--scratch-offset <FLASH_OFFSET>' is required")
+            .exit();
     }
 
+    // The running checksum and the error value are values returned by the copy function, a void one has none.
+    if cli_args.copy_returns == CopyReturns::Void {
+        let conflicting = [("--cumulative-checksum", cli_args.cumulative_checksum), ("--copy-error-value", cli_args.copy_error_value.is_some())];
+        if let Some((conflicting_arg, _)) = conflicting.iter().find(|(_, given)| *given) {
+            Cli::command()
+                .error(clap::error::ErrorKind::ArgumentConflict, format!("the argument '--copy-returns void' cannot be used with '{conflicting_arg}'"))
+                .exit();
+        }
+    }
+
+    // Warnings and errors, e.g. GDB errors printed on stderr, are visible without debug too.
+    color::init(cli_args.color_choice());
+    logger::init(cli_args.debug, cli_args.trace_parsing, cli_args.color_choice().write_style());
+
     log::debug!("Got args: '{:?}'.", cli_args);
 
-    run_procedure(cli_args)
-        .await
-        .map_err(|e| e.into())
+    progress::connect(cli_args.progress_socket.as_deref(), cli_args.progress_port);
+
+    let fail_on_warning = cli_args.fail_on_warning;
+    let print_summary = cli_args.summary;
+    if let Err(e) = run_procedure(cli_args).await {
+        if print_summary {
+            print!("{}", summary::render(Some(&e.to_string())));
+        }
+        eprintln!("Error: {e}");
+        let exit_code = error::exit_code(&e);
+        progress::send_finished(exit_code, Some(&e.to_string()));
+        std::process::exit(exit_code);
+    }
+
+    let warnings = logger::warnings();
+    if fail_on_warning && !warnings.is_empty() {
+        eprintln!("{} warning(s) occurred:", warnings.len());
+        for warning in &warnings {
+            eprintln!("- {warning}");
+        }
+        if print_summary {
+            print!("{}", summary::render(Some("Failed on warnings (--fail-on-warning)")));
+        }
+        eprintln!("Error: Failed on warnings (--fail-on-warning)");
+        progress::send_finished(error::EXIT_WARNINGS, Some("Failed on warnings (--fail-on-warning)"));
+        std::process::exit(error::EXIT_WARNINGS);
+    }
+
+    if print_summary {
+        print!("{}", summary::render(None));
+    }
+    progress::send_finished(0, None);
+}
+
+fn per_chunk_handler(chunk: &ChunkResult, progress: &UploadProgress) {
+    let chunks_done = chunk.index + 1;
+    let line = format!("{} ms, chunk={chunks_done}/{}, bytes={}/{}B;",
+        progress.start_timestamp_ms, progress.chunks_count, progress.bytes_done, progress.total_bytes
+    );
+    // A chunk which needed retries or mismatched is highlighted, it is the one to look at on unreliable hardware.
+    match (chunk.target_checksum, chunk.retries) {
+        (Some(target_checksum), _) if target_checksum != chunk.host_checksum => println!("{}", color::red(&line)),
+        (_, 0) => println!("{}", color::green(&line)),
+        _ => println!("{}", color::yellow(&line)),
+    }
+    progress::send_chunk(chunks_done, progress.chunks_count, progress.bytes_done, progress.total_bytes, progress.start_timestamp_ms);
+}
+
+/// Compares the value of the build ID symbol in the ELF with the one on the connected target.
+async fn verify_build_id(gdb: &mut Gdb, elf_path: &Path, build_id_symbol: &str) -> io::Result<()> {
+    let (address, elf_build_id) = elf::read_symbol_bytes(elf_path, build_id_symbol)?;
+    let target_build_id = gdb.read_memory(address, elf_build_id.len()).await?;
+
+    if elf_build_id != target_build_id {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Build ID '{build_id_symbol}' mismatch, ELF={elf_build_id:02x?} target={target_build_id:02x?}. Wrong firmware or board?")
+        ));
+    }
+
+    log::info!("Build ID '{build_id_symbol}' matches: {elf_build_id:02x?}");
+    Ok(())
+}
+
+/// Compares the loader protocol version reported by the firmware with the expected one.
+async fn verify_protocol_version(gdb: &mut Gdb, protocol_version_function_name: &str, expected_version: u32) -> io::Result<()> {
+    let target_version = gdb.call_resulting_u32(protocol_version_function_name).await
+        .with_context(|| format!("protocol version function '{protocol_version_function_name}' call failed"))?;
+
+    if target_version != expected_version {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Loader protocol version mismatch, expected={expected_version} target={target_version}. Update the firmware or the tool.")
+        ));
+    }
+
+    log::info!("Loader protocol version {target_version} matches.");
+    Ok(())
+}
+
+/// Resolves the flash offset from the value of an ELF symbol, absolute addresses are made relative to the flash base.
+fn resolve_offset_symbol(elf_path: &Path, offset_symbol: &str, flash_target: &FlashTarget) -> io::Result<usize> {
+    let value = elf::read_symbol_value(elf_path, offset_symbol)?;
+    let address_base = flash_target.address_base as u64;
+    let offset = if value >= address_base { value - address_base } else { value };
+
+    log::info!("Flash offset {offset:#x} from symbol '{offset_symbol}' = {value:#x}.");
+    usize::try_from(offset)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("Symbol '{offset_symbol}' value {value:#x} out of range")))
+}
+
+/// Returns the flash offset, from --offset-symbol if given, otherwise --offset.
+fn flash_start_offset(cli_args: &Cli, flash_target: &FlashTarget) -> io::Result<usize> {
+    match &cli_args.offset_symbol {
+        Some(offset_symbol) => resolve_offset_symbol(&cli_args.elf_path, offset_symbol, flash_target),
+        None => Ok(cli_args.flash_save_offset),
+    }
+}
+
+/// Runs the upload of the binary, surrounded by the session calls.
+async fn run_upload(session: &mut Session, cli_args: &Cli) -> io::Result<UploadStats> {
+    let binary_path = cli_args.binary_path.as_ref()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Binary path missing"))?;
+
+    let (gdb, session_config, _) = session.split_mut();
+    let flash_start_offset = flash_start_offset(cli_args, &session_config.flash_target)?;
+
+    // Read-modify-write restores the sector content below the offset.
+    if cli_args.copy_erases_sectors && !cli_args.rmw {
+        ensure_erase_aligned(flash_start_offset, session_config.flash_target.sector_size)?;
+    }
+
+    if let Some(pre_session_function_name) = &cli_args.pre_session_function_name {
+        gdb.call(pre_session_function_name, false).await?;
+    }
+
+    let upload_result = session.upload(
+        binary_path,
+        flash_start_offset,
+        |plan| {
+            summary::record_plan(&binary_path.display().to_string(), plan.total_size, plan.flash_start_offset);
+            if let Some(plan_format) = cli_args.show_plan {
+                print!("{}", plan_format.render(plan));
+            }
+        },
+        Some(per_chunk_handler)
+    ).await;
+
+    let post_session_result = call_post_session(session.gdb_mut(), cli_args).await;
+    upload_result.and_then(|stats| post_session_result.map(|_| stats))
+}
+
+/// Calls the post session function, if given. Called even if the upload failed, so the target
+/// is not left e.g. with unlocked flash, a failed call is logged.
+///
+/// # Returns
+/// An `io::Result<()>` with the error of the call.
+async fn call_post_session(gdb: &mut Gdb, cli_args: &Cli) -> io::Result<()> {
+    let Some(post_session_function_name) = &cli_args.post_session_function_name else {
+        return Ok(());
+    };
+
+    let post_session_result = gdb.call(post_session_function_name, false).await.map(|_| ());
+    if let Err(e) = &post_session_result {
+        log::error!("Post session call '{post_session_function_name}' failed: {e}");
+    }
+    post_session_result
+}
+
+/// Uploads every image of the manifest in one session, each verified as a whole after its chunks,
+/// and prints the outcome of every image.
+///
+/// # Returns
+/// An `io::Result` with the number of chunks of all images not checked after their copy.
+async fn run_manifest_upload(gdb: &mut Gdb, cli_args: &Cli, session_config: &SessionConfig) -> io::Result<usize> {
+    let manifest_path = cli_args.manifest_path.as_ref()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Manifest path missing"))?;
+    let flash_target = &session_config.flash_target;
+    let upload_options = &session_config.options;
+
+    let mut images = load_manifest(manifest_path)?;
+    for image in &mut images {
+        pad_last_sector(&mut image.data, image.offset, flash_target, upload_options);
+    }
+
+    // Everything is checked before the first image is written, so a bad manifest leaves flash untouched.
+    check_overlaps(&images)?;
+    let mut plans = Vec::with_capacity(images.len());
+    for image in &images {
+        if cli_args.copy_erases_sectors {
+            ensure_erase_aligned(image.offset, flash_target.sector_size)
+                .with_context(|| format!("image '{}'", image.name))?;
+        }
+        let plan = plan_upload(&image.data, session_config.chunk_size, image.offset, flash_target, upload_options)
+            .with_context(|| format!("planning image '{}' failed", image.name))?;
+        if let Some(plan_format) = cli_args.show_plan {
+            print!("{}", plan_format.render(&plan));
+        }
+        plans.push(plan);
+    }
+    log::info!("Loaded manifest {manifest_path:?} with {} images.", images.len());
+
+    if let Some(pre_session_function_name) = &cli_args.pre_session_function_name {
+        gdb.call(pre_session_function_name, false).await?;
+    }
+
+    let mut outcomes = Vec::with_capacity(images.len());
+    for (image, plan) in images.iter().zip(&plans) {
+        log::info!("Uploading image '{}' to {:#x}...", image.name, image.offset);
+        let outcome = upload_manifest_image(gdb, session_config, image, plan).await;
+        let failed = outcome.is_err();
+        outcomes.push(outcome);
+        if failed {
+            break;
+        }
+    }
+
+    let post_session_result = call_post_session(gdb, cli_args).await;
+
+    println!("{:<24} {:>10} {:>10} {:>7} {:>7}  Result", "Image", "Offset", "Size", "Chunks", "Retries");
+    for (index, image) in images.iter().enumerate() {
+        let (chunks, retries, result) = match outcomes.get(index) {
+            Some(Ok(stats)) => (stats.chunks_count.to_string(), stats.retries.to_string(), color::green("OK")),
+            Some(Err(e)) => (String::from("-"), String::from("-"), color::red(&format!("FAILED ({e})"))),
+            None => (String::from("-"), String::from("-"), String::from("not written")),
+        };
+        println!("{:<24} {:>#10x} {:>10} {:>7} {:>7}  {result}", image.name, image.offset, image.data.len(), chunks, retries);
+    }
+
+    let unverified_chunks = outcomes.iter().flatten().map(|stats| stats.unverified_chunks).sum();
+    match outcomes.pop() {
+        Some(Err(e)) => Err(e),
+        _ => post_session_result.map(|_| unverified_chunks),
+    }
+}
+
+/// Uploads a single image of the manifest and checks its whole range with the checksum function.
+async fn upload_manifest_image(
+    gdb: &mut Gdb,
+    session_config: &SessionConfig,
+    image: &LoadedImage,
+    plan: &UploadPlan
+) -> io::Result<UploadStats> {
+    let flash_target = &session_config.flash_target;
+    if session_config.steps.erase {
+        let erase_address = flash_target.copy_address(plan.flash_start_offset)?;
+        log::info!("Erasing {} B of flash at {erase_address:#x}...", plan.total_size);
+        gdb.monitor_flash_erase(erase_address, plan.total_size).await
+            .with_context(|| format!("erasing flash at {erase_address:#x} failed"))?;
+    }
+
+    let stats = execute_plan(
+        gdb,
+        plan,
+        &image.data,
+        &session_config.ram_buffer,
+        flash_target,
+        &session_config.options,
+        Some(per_chunk_handler)
+    ).await?;
+
+    if stats.incomplete {
+        return Err(GdbError::Incomplete {
+            chunks_written: stats.chunks_count,
+            chunks_count: stats.planned_chunks_count,
+        }.into());
+    }
+
+    verify_flash_checksum(gdb, flash_target, image.offset, image.data.len(), checksum_sum32(&image.data)).await
+        .with_context(|| format!("verifying image '{}' failed", image.name))?;
+
+    Ok(stats)
+}
+
+/// Runs the self test and prints the outcome of every step.
+async fn run_self_test(
+    gdb: &mut Gdb,
+    cli_args: &Cli,
+    flash_target: &FlashTarget,
+    pattern_size: usize,
+    readback: bool
+) -> io::Result<()> {
+    let scratch_offset = cli_args.scratch_offset
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Self test needs --scratch-offset"))?;
+    let scratch = ScratchRegion {
+        offset: scratch_offset as usize,
+        size: cli_args.scratch_size.unwrap_or(pattern_size),
+    };
+
+    let steps = self_test(gdb, &cli_args.ram_buffer(), flash_target, &scratch, pattern_size, readback).await?;
+
+    for step in &steps {
+        println!("{}: {} ({})", step.name, if step.passed { color::green("PASS") } else { color::red("FAIL") }, step.details);
+    }
+
+    match steps.iter().find(|step| !step.passed) {
+        Some(failed_step) => Err(io::Error::other(format!("Self test failed at step '{}'", failed_step.name))),
+        None => Ok(()),
+    }
+}
+
+/// Writes a test pattern across the flash region at the offset and prints the bad addresses.
+async fn run_pattern(
+    gdb: &mut Gdb,
+    cli_args: &Cli,
+    flash_target: &FlashTarget,
+    pattern: TestPattern,
+    length: usize,
+    readback: bool
+) -> io::Result<()> {
+    let flash_start_offset = flash_start_offset(cli_args, flash_target)?;
+    // Every byte is written, a health check must not rely on the flash being erased. A mismatching chunk
+    // does not stop the write, the bad addresses of all chunks are located afterwards.
+    let upload_options = UploadOptions { skip_blank: false, continue_on_mismatch: true, ..cli_args.upload_options() };
+    let data = pattern.generate(length);
+    let plan = plan_upload(&data, cli_args.chunk_size_bytes, flash_start_offset, flash_target, &upload_options)?;
+
+    match execute_plan(gdb, &plan, &data, &cli_args.ram_buffer(), flash_target, &upload_options, Some(per_chunk_handler)).await {
+        Err(e) if !matches!(error::as_gdb_error(&e), Some(GdbError::ChecksumMismatch { .. })) => return Err(e),
+        _ => {},
+    }
+    let bad_regions = find_bad_regions(gdb, flash_target, &plan, &data, readback, upload_options.verify_read_chunk_size).await?;
+
+    if bad_regions.is_empty() {
+        println!("Pattern {pattern:?}: offset={flash_start_offset:#x}, length={length} B, no bad addresses.");
+        return Ok(());
+    }
+
+    let bad_bytes_count: u32 = bad_regions.iter().map(|region| region.end - region.start).sum();
+    println!("Pattern {pattern:?}: offset={flash_start_offset:#x}, length={length} B, {bad_bytes_count} B bad:");
+    for region in &bad_regions {
+        println!("  {:#010x}..{:#010x} ({} B)", region.start, region.end, region.end - region.start);
+    }
+
+    Err(io::Error::new(io::ErrorKind::InvalidData, format!("Flash differs from pattern {pattern:?} in {} regions", bad_regions.len())))
+}
+
+/// Compares the flash at the offset with the expected checksum of --verify-checksum-only, otherwise with the binary,
+/// and prints the outcome.
+async fn run_verify_checksum_only(session: &mut Session, cli_args: &Cli) -> io::Result<()> {
+    let flash_offset = flash_start_offset(cli_args, &session.config().flash_target)?;
+
+    let (range, result) = match cli_args.verify_checksum_only {
+        Some(expected_checksum) => {
+            let length = cli_args.length
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Length missing"))?;
+            (format!("offset={flash_offset:#x}, length={length} B"), session.verify_checksum(flash_offset, length, expected_checksum).await)
+        },
+        None => {
+            let binary_path = cli_args.binary_path.as_ref()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Binary path missing"))?;
+            (format!("binary={binary_path:?}, offset={flash_offset:#x}"), session.verify(binary_path, flash_offset).await)
+        },
+    };
+
+    match result {
+        Ok(target_checksum) => {
+            println!("Checksum match: {range}, checksum={target_checksum:#010x}");
+            Ok(())
+        },
+        Err(e) => {
+            if let Some(GdbError::ChecksumMismatch { host_checksum, target_checksum }) = error::as_gdb_error(&e) {
+                println!("Checksum mismatch: {range}, expected={host_checksum:#010x}, target={target_checksum:#010x}");
+            }
+            Err(e)
+        },
+    }
+}
+
+/// Reads the flash region at the offset back into the output file.
+async fn run_dump(session: &mut Session, cli_args: &Cli, output_path: &Path, length: usize) -> io::Result<()> {
+    let flash_offset = flash_start_offset(cli_args, &session.config().flash_target)?;
+    session.dump(output_path, flash_offset, length).await?;
+    println!("Dumped {length} B of flash at offset {flash_offset:#x} to {output_path:?}.");
+    Ok(())
+}
+
+/// Runs the commands of the commands file in order, stopping on the first failing one unless continuing on errors.
+async fn run_commands_file(gdb: &mut Gdb, commands_path: &Path, continue_on_error: bool) -> io::Result<()> {
+    let commands = load_commands_file(commands_path)?;
+    log::info!("Running {} commands from {commands_path:?}.", commands.len());
+
+    for cmd in &commands {
+        match gdb.execute(cmd).await {
+            Ok(lines) => log::info!("'{cmd}': {lines:?}"),
+            Err(e) if continue_on_error => log::warn!("{e}, continuing."),
+            Err(e) => return Err(e),
+        }
+    }
+
+    // Output arriving after a command's response window would be taken for the response of the next request.
+    let late_lines = gdb.flush_responses(Duration::from_millis(100)).await;
+    if !late_lines.is_empty() {
+        log::info!("Late output of commands: {late_lines:?}");
+    }
+
+    Ok(())
+}
+
+/// Prints the GDB version and the target status of a fresh connection.
+async fn run_connect_only(gdb: &mut Gdb) -> io::Result<()> {
+    for line in gdb.show_version().await?.iter().take(1) {
+        println!("{line}");
+    }
+
+    let connection_info = gdb.connection_info();
+    println!("Remote: {}", connection_info.remote);
+    if let Some(initial_pc) = connection_info.initial_pc {
+        println!("Initial PC: {initial_pc:#010x}");
+    }
+    for warning in &connection_info.warnings {
+        println!("Warning: {warning}");
+    }
+
+    for line in gdb.target_status().await? {
+        println!("{line}");
+    }
+
+    println!("Connected.");
+    Ok(())
 }
 
-fn per_chunk_handler(
-    chunk_idx: usize, 
-    chunks_total_count: usize, 
-    processed_data: usize,
-    total_data: usize,
-    millis_since_start: u128
-) {
-    let chunks_done = chunk_idx + 1;
-    println!("{millis_since_start} ms, chunk={chunks_done}/{chunks_total_count}, bytes={processed_data}/{total_data}B;")
+/// Number of connection attempts to a launched server whose readiness line was not seen.
+const SERVER_CONNECT_ATTEMPTS: usize = 10;
+/// Delay between connection attempts to a launched server.
+const SERVER_CONNECT_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Starts GDB and connects it, retrying failed connections, e.g. to a server still starting up.
+async fn connect_gdb(cli_args: &Cli, gdb_options: GdbOptions, attempts: usize) -> io::Result<Gdb> {
+    let mut attempt = 1;
+    loop {
+        let result = Gdb::try_new(
+            cli_args.gdb_path.clone(), 
+            cli_args.elf_path.clone(), 
+            cli_args.server_address.clone(),
+            gdb_options.clone()
+        ).await;
+
+        match result {
+            Err(e) if attempt < attempts && matches!(error::as_gdb_error(&e), Some(GdbError::Connection(_))) => {
+                log::info!("{e}, retrying {attempt}/{}...", attempts - 1);
+                attempt += 1;
+                tokio::time::sleep(SERVER_CONNECT_RETRY_DELAY).await;
+            },
+            result => return result,
+        }
+    }
 }
 
 async fn run_procedure(cli_args: Cli) -> io::Result<()> {
-    let mut gdb = Gdb::try_new(
-        cli_args.gdb_path, 
-        cli_args.elf_path, 
-        cli_args.server_address
+    if cli_args.dry_run {
+        return run_dry_run(&cli_args).await;
+    }
+
+    if let Some(Command::Checksum { per_chunk }) = cli_args.command {
+        return run_checksum(&cli_args, per_chunk).await;
+    }
+
+    let config = match &cli_args.config_path {
+        Some(config_path) => load_config(config_path)?,
+        None => Config::default(),
+    };
+
+    // The launched server lives until the procedure ends, it is killed when dropped.
+    let (_server, server_ready) = match &cli_args.server_launch {
+        Some(server_command) => {
+            let ready_regex = cli_args.server_ready_regex.clone()
+                .or_else(|| cli_args.probe.server_ready_regex().map(|pattern| Regex::new(pattern).unwrap()));
+            let (server, ready) = server::launch(
+                server_command,
+                ready_regex.as_ref(),
+                Duration::from_millis(cli_args.server_ready_timeout_ms)
+            ).await?;
+            (Some(server), ready)
+        },
+        None => (None, true),
+    };
+
+    let gdb_options = GdbOptions {
+        encoding: cli_args.response_encoding,
+        timeouts: cli_args.timeouts.resolve(&config.timeouts),
+        unclassified_stderr_as_error: cli_args.unclassified_stderr_as_error,
+        response_limits: ResponseLimits {
+            max_line_length: cli_args.max_line_length,
+            max_lines: cli_args.max_response_lines,
+            max_total_bytes: cli_args.max_response_bytes,
+        },
+        monitor: cli_args.monitor_commands(),
+        line_ending: cli_args.line_ending.unwrap_or_default(),
+        init_script: cli_args.gdb_script.clone(),
+    };
+    let connect_attempts = if server_ready { 1 } else { SERVER_CONNECT_ATTEMPTS };
+    let gdb = connect_gdb(&cli_args, gdb_options, connect_attempts).await?;
+    let mut session = Session::new(gdb, cli_args.session_config());
+
+    let session_result = run_session(&mut session, &cli_args).await;
+    if session_result.is_err() {
+        log::error!("Session failed in phase '{}'.", session.phase());
+    }
+
+    // The diagnostics are best effort, the original error is returned in any case.
+    if let (Err(e), Some(dump_dir)) = (&session_result, &cli_args.dump_on_failure_dir) {
+        match diagnostics::dump_failure_bundle(session.gdb_mut(), dump_dir, e).await {
+            Ok(bundle_path) => eprintln!("Diagnostic bundle saved to {bundle_path:?}."),
+            Err(dump_error) => log::error!("Saving diagnostic bundle failed: {dump_error}"),
+        }
+    }
+
+    session_result
+}
+
+/// Prints the upload plan without starting GDB, flash geometry comes from the CLI values only.
+async fn run_dry_run(cli_args: &Cli) -> io::Result<()> {
+    let binary_path = cli_args.binary_path.as_ref()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Binary path missing"))?;
+    let flash_target = cli_args.flash_target();
+    let flash_start_offset = flash_start_offset(cli_args, &flash_target)?;
+
+    let (_, plan) = prepare_upload(
+        binary_path,
+        cli_args.chunk_size_bytes,
+        flash_start_offset,
+        &flash_target,
+        &cli_args.upload_options()
     ).await?;
 
-    gdb.monitor_reset().await?;
+    let plan_format = cli_args.show_plan.unwrap_or(PlanFormat::Table);
+    print!("{}", plan_format.render(&plan));
+    Ok(())
+}
+
+/// Prints the checksum of the input file, and of its chunks if requested, without starting GDB.
+/// The data is padded and the checksums are truncated to the checksum width as for the upload.
+async fn run_checksum(cli_args: &Cli, per_chunk: bool) -> io::Result<()> {
+    let binary_path = cli_args.binary_path.as_ref()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Binary path missing"))?;
+    let flash_target = cli_args.flash_target();
+    let flash_start_offset = flash_start_offset(cli_args, &flash_target)?;
+
+    let (file_data, plan) = prepare_upload(
+        binary_path,
+        cli_args.chunk_size_bytes,
+        flash_start_offset,
+        &flash_target,
+        &cli_args.upload_options()
+    ).await?;
+
+    let checksum_width = flash_target.checksum_width;
+    println!("{binary_path:?}: {} B, Sum32 {:#010x} ({}-bit)",
+        file_data.len(), checksum_width.mask(checksum_sum32(&file_data)), checksum_width.bits()
+    );
+
+    if per_chunk {
+        println!("{:>6}  {:>12}  {:>10}  {:>10}", "chunk", "flash offset", "size", "checksum");
+        for chunk in &plan.chunks {
+            let checksum = checksum_width.mask(checksum_sum32(&file_data[chunk.file_range.clone()]));
+            println!("{:>6}  {:>#12x}  {:>10}  {:>#10x}", chunk.index, chunk.flash_offset, chunk.size, checksum);
+        }
+    }
+    Ok(())
+}
+
+/// Runs the selected operation on the connected target and quits GDB.
+async fn run_session(session: &mut Session, cli_args: &Cli) -> io::Result<()> {
+    let (gdb, session_config, phase) = session.split_mut();
+    let flash_target = &mut session_config.flash_target;
+
+    // Checked up front, the read back would otherwise fail only after flash was written.
+    let reads_flash_back = cli_args.rmw
+        || cli_args.on_mismatch == MismatchAction::Dump
+        || matches!(cli_args.command, Some(Command::Selftest { readback: true, .. } | Command::Pattern { readback: true, .. } | Command::Dump { .. }));
+    if reads_flash_back && flash_target.flash_base.is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Reading flash back needs its memory mapped address, pass --flash-base"
+        ));
+    }
+
+    for extra_elf in &cli_args.extra_elfs {
+        gdb.add_symbol_file(&extra_elf.path, extra_elf.text_address).await?;
+    }
+
+    // All missing symbols are reported at once, so a wrong ELF is recognized from a single run.
+    let missing_symbols = gdb.missing_symbols(&cli_args.required_symbols).await?;
+    if !missing_symbols.is_empty() {
+        return Err(io::Error::from(GdbError::SymbolNotFound(missing_symbols.join("', '"))))
+            .with_context(|| format!("{} of {} required symbols missing", missing_symbols.len(), cli_args.required_symbols.len()));
+    }
+
+    if cli_args.show_target_output {
+        gdb.enable_semihosting().await?;
+        gdb.set_target_output_handler(Box::new(|line| println!("[target] {line}")));
+    } else if cli_args.confirm_regex.is_some() {
+        // The confirmations are printed through semihosting.
+        gdb.enable_semihosting().await?;
+    }
+
+    if cli_args.connect_only {
+        run_connect_only(gdb).await?;
+        phase.enter(Phase::Quit)?;
+        return gdb.quit_and_wait().await;
+    }
 
-    gdb.break_at(&cli_args.break_function_name).await?;
+    // A plain reset lets the firmware run while the breakpoint is being set, a fast one may pass it meanwhile.
+    match cli_args.halt_before_break {
+        true => gdb.monitor_reset_halt().await?,
+        false => gdb.monitor_reset().await?,
+    };
+
+    // The order is reset, load, break, continue: a breakpoint set before the load may point into overwritten code,
+    // any present one is checked. The load leaves the PC at the ELF entry point.
+    if cli_args.load_elf {
+        gdb.load().await?;
+        gdb.refresh_breakpoints().await?;
+    }
+    phase.enter(Phase::Reset)?;
+
+    // Number of the set breakpoint, `None` if it cannot be parsed from the response.
+    let break_number = match cli_args.break_address {
+        Some(address) => extract_breakpoint_number(&gdb.break_at_address(address).await?),
+        None if cli_args.break_entry => {
+            let entry_address = elf::read_entry_address(&cli_args.elf_path)?;
+            log::info!("Breaking at ELF entry point {entry_address:#x}.");
+            extract_breakpoint_number(&gdb.tbreak_at_address(entry_address).await?)
+        },
+        None => Some(gdb.set_breakpoint(&cli_args.break_function_name, cli_args.break_location).await?.number),
+    };
+
+    if cli_args.breakpoint_hit_count > 1 {
+        let number = break_number
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Breakpoint not set"))?;
+        gdb.ignore_breakpoint(number, cli_args.breakpoint_hit_count - 1).await?;
+    }
+    phase.enter(Phase::BreakpointSet)?;
 
     // tokio::time::sleep(Duration::from_secs(1)).await;
 
-    gdb.continue_execution().await?;
+    // Abort if the target faulted or exited instead of hitting the breakpoint.
+    match gdb.continue_execution().await? {
+        StopReason::Breakpoint { number } if cli_args.breakpoint_hit_count > 1 => {
+            let hit_count = gdb.breakpoint_hit_count(number).await?;
+            if hit_count != cli_args.breakpoint_hit_count {
+                log::warn!("Breakpoint {number} hit {hit_count} times, expected {}.", cli_args.breakpoint_hit_count);
+            }
+            log::info!("Target stopped at breakpoint {number}, hit {hit_count} times.");
+        },
+        StopReason::Breakpoint { number } => log::info!("Target stopped at breakpoint {number}."),
+        stop_reason => {
+            return Err(io::Error::other(format!("Target stopped on {stop_reason} instead of the breakpoint")));
+        }
+    }
 
     // tokio::time::sleep(Duration::from_secs(1)).await;
 
     gdb.monitor_halt().await?;
+    if cli_args.verify_halt {
+        gdb.verify_halted().await?;
+    }
+    phase.enter(Phase::AtBreakpoint)?;
 
     // tokio::time::sleep(Duration::from_secs(1)).await;
 
-    // Chunk size should match bock size
-    upload_binary_file_to_external_flash(
-        &mut gdb,
-        cli_args.binary_path, 
-        &cli_args.ram_buffer_name, 
-        cli_args.chunk_size_bytes, 
-        cli_args.flash_save_offset, 
-        &cli_args.copy_function_name,
-        Some(per_chunk_handler)
-    ).await?;
+    if let Some(commands_file) = &cli_args.commands_file {
+        run_commands_file(gdb, commands_file, cli_args.continue_on_error).await?;
+    }
 
+    if let Some(build_id_symbol) = &cli_args.build_id_symbol {
+        verify_build_id(gdb, &cli_args.elf_path, build_id_symbol).await?;
+    }
+
+    if let (Some(protocol_version_function_name), Some(expected_version)) = (&cli_args.protocol_version_function_name, cli_args.expected_protocol_version) {
+        verify_protocol_version(gdb, protocol_version_function_name, expected_version).await?;
+    }
+
+    if cli_args.query_geometry {
+        query_flash_geometry(
+            gdb,
+            &cli_args.sector_size_function_name,
+            &cli_args.flash_size_function_name,
+            flash_target
+        ).await;
+    }
+
+    let mut incomplete_stats = None;
+    match (&cli_args.command, cli_args.verify_checksum_only) {
+        (Some(Command::Selftest { pattern_size, readback }), _) => {
+            phase.require(Phase::AtBreakpoint)?;
+            run_self_test(gdb, cli_args, flash_target, *pattern_size, *readback).await?;
+            phase.enter(Phase::Uploaded)?;
+            phase.enter(Phase::Verified)?;
+        },
+        (Some(Command::Checksum { .. }), _) => unreachable!("The checksum command runs before GDB is started"),
+        (Some(Command::Pattern { pattern, length, readback }), _) => {
+            phase.require(Phase::AtBreakpoint)?;
+            run_pattern(gdb, cli_args, flash_target, *pattern, *length as usize, *readback).await?;
+            // Every chunk matched its checksum, a bad region fails the run.
+            phase.enter(Phase::Uploaded)?;
+            phase.enter(Phase::Verified)?;
+        },
+        (Some(Command::Verify), _) | (None, Some(_)) => {
+            run_verify_checksum_only(session, cli_args).await?;
+        },
+        (Some(Command::Dump { output, length }), _) => {
+            run_dump(session, cli_args, output, *length as usize).await?;
+        },
+        (None, None) if cli_args.manifest_path.is_some() => {
+            phase.require(Phase::AtBreakpoint)?;
+            let unverified_chunks = run_manifest_upload(gdb, cli_args, session_config).await?;
+            phase.enter(Phase::Uploaded)?;
+            if unverified_chunks == 0 {
+                phase.enter(Phase::Verified)?;
+            }
+        },
+        (None, None) => {
+            let stats = run_upload(session, cli_args).await?;
+            summary::record_stats(&stats);
+            log::info!("Uploaded {} B in {} chunks of up to {} B, {} retries, {} warnings.",
+                stats.bytes_transferred, stats.chunks_count, stats.chunk_size, stats.retries, stats.warnings.len()
+            );
+            if cli_args.resume_verify {
+                println!("Skipped {} chunks already in flash, rewritten {}.",
+                    stats.skipped_chunks, stats.chunks_count - stats.skipped_chunks - stats.blank_chunks
+                );
+            }
+            if cli_args.skip_blank {
+                println!("Skipped {} blank chunks.", stats.blank_chunks);
+            }
+            if let Some(verify_seed) = stats.verify_seed {
+                if cli_args.verify_sample_percent.is_some() {
+                    println!("Verified chunks (seed {verify_seed}): {:?}", stats.verified_chunks);
+                }
+                if cli_args.spot_check_count.is_some() {
+                    println!("Spot checked {} words (seed {verify_seed}).", stats.spot_checked_words);
+                }
+            }
+            if stats.incomplete {
+                println!("Partial upload: {} of {} chunks, {} B written.",
+                    stats.chunks_count, stats.planned_chunks_count, stats.bytes_transferred
+                );
+                incomplete_stats = Some(stats);
+            }
+        },
+    }
+
+    session.enter(Phase::Quit)?;
+    let gdb = session.gdb_mut();
     gdb.monitor_sleep(250).await?;
  
     gdb.quit_and_wait().await?; // TODO implement drop
 
+    // Reported after the session is closed, so the target is left in the same state as after a full upload.
+    if let Some(stats) = incomplete_stats {
+        return Err(GdbError::Incomplete {
+            chunks_written: stats.chunks_count,
+            chunks_count: stats.planned_chunks_count,
+        }.into());
+    }
+
     Ok(())
 }
\ No newline at end of file
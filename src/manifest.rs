@@ -0,0 +1,176 @@
+use std::fs::File;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use tokio::io;
+
+/// Manifest of images flashed one after another in a single session, file paths are relative to the manifest.
+///
+/// Example:
+/// ```toml
+/// # Optional, the files are then entries of the tar archive instead of files next to the manifest.
+/// archive = "release.tar"
+///
+/// [[image]]
+/// file = "bootloader.bin"
+/// offset = 0x0
+///
+/// [[image]]
+/// file = "assets/fonts.bin"
+/// offset = 0x100000
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Uncompressed tar archive holding the images, if any.
+    #[serde(default)]
+    pub archive: Option<PathBuf>,
+
+    /// The images in flashing order.
+    #[serde(rename = "image", default)]
+    pub images: Vec<ManifestImage>,
+}
+
+/// Single image of the [`Manifest`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestImage {
+    /// Path of the image file, or of the archive entry.
+    pub file: PathBuf,
+
+    /// Flash offset the image is written to.
+    pub offset: usize,
+}
+
+/// Image of the manifest with its content loaded.
+#[derive(Debug, Clone)]
+pub struct LoadedImage {
+    /// Name of the image as given in the manifest.
+    pub name: String,
+    /// Flash offset the image is written to.
+    pub offset: usize,
+    /// Content of the image.
+    pub data: Vec<u8>,
+}
+
+impl LoadedImage {
+    /// Flash offsets the image is written to.
+    pub fn flash_range(&self) -> Range<usize> {
+        self.offset..self.offset + self.data.len()
+    }
+}
+
+/// Loads the TOML manifest and the content of all of its images.
+///
+/// # Parameters
+/// - `manifest_path`: The path to the manifest file.
+///
+/// # Returns
+/// An `io::Result` with the images in manifest order or an error if the manifest is corrupted
+/// or an image cannot be read.
+pub fn load_manifest<P>(manifest_path: P) -> io::Result<Vec<LoadedImage>>
+where
+    P: AsRef<Path>
+{
+    let manifest_path = manifest_path.as_ref();
+    let content = std::fs::read_to_string(manifest_path)?;
+    let manifest: Manifest = toml::from_str(&content)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Manifest {manifest_path:?} corrupted: {e}")))?;
+
+    if manifest.images.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Manifest {manifest_path:?} lists no images")));
+    }
+
+    let base_dir = manifest_path.parent().unwrap_or(Path::new(""));
+    let mut images: Vec<LoadedImage> = manifest.images.iter()
+        .map(|image| LoadedImage {
+            name: image.file.display().to_string(),
+            offset: image.offset,
+            data: Vec::new(),
+        })
+        .collect();
+
+    match &manifest.archive {
+        Some(archive) => read_archive_images(&base_dir.join(archive), &manifest.images, &mut images)?,
+        None => {
+            for (image, loaded) in manifest.images.iter().zip(images.iter_mut()) {
+                let image_path = base_dir.join(&image.file);
+                loaded.data = std::fs::read(&image_path)
+                    .map_err(|e| io::Error::new(e.kind(), format!("reading image {image_path:?} failed: {e}")))?;
+            }
+        },
+    }
+
+    Ok(images)
+}
+
+/// Reads the images from the entries of a tar archive in a single pass, an image may be listed more than once.
+fn read_archive_images(archive_path: &Path, manifest_images: &[ManifestImage], images: &mut [LoadedImage]) -> io::Result<()> {
+    let archive_file = File::open(archive_path)
+        .map_err(|e| io::Error::new(e.kind(), format!("opening archive {archive_path:?} failed: {e}")))?;
+    let mut archive = tar::Archive::new(archive_file);
+    let mut found = vec![false; manifest_images.len()];
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+        let matching: Vec<usize> = manifest_images.iter()
+            .enumerate()
+            .filter(|(_, image)| normalize(&image.file) == normalize(&entry_path))
+            .map(|(index, _)| index)
+            .collect();
+
+        if matching.is_empty() {
+            continue;
+        }
+
+        let mut data = Vec::with_capacity(entry.size() as usize);
+        std::io::Read::read_to_end(&mut entry, &mut data)?;
+        for index in matching {
+            images[index].data = data.clone();
+            found[index] = true;
+        }
+    }
+
+    match found.iter().position(|found| !found) {
+        Some(index) => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Image {:?} not found in archive {archive_path:?}", manifest_images[index].file)
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Drops `./` components, tar tools store entry paths both with and without them.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| !matches!(component, std::path::Component::CurDir))
+        .collect()
+}
+
+/// Checks that no two images are written to the same flash bytes.
+///
+/// # Parameters
+/// - `images`: The images, in any order.
+///
+/// # Returns
+/// An `io::Result<()>`, an error naming the first two overlapping images.
+pub fn check_overlaps(images: &[LoadedImage]) -> io::Result<()> {
+    let mut sorted: Vec<&LoadedImage> = images.iter().collect();
+    sorted.sort_by_key(|image| image.offset);
+
+    for pair in sorted.windows(2) {
+        let (first, second) = (pair[0].flash_range(), pair[1].flash_range());
+        if second.start < first.end {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Image '{}' at {:#x}..{:#x} overlaps image '{}' at {:#x}..{:#x}",
+                    pair[0].name, first.start, first.end, pair[1].name, second.start, second.end
+                )
+            ));
+        }
+    }
+
+    Ok(())
+}
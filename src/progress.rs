@@ -0,0 +1,107 @@
+use std::io::Write;
+use std::net::TcpStream;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+/// Connection to a front-end receiving progress events as NDJSON, one JSON object per line.
+struct ProgressSocket {
+    stream: Mutex<Option<Box<dyn Write + Send>>>,
+}
+
+static PROGRESS_SOCKET: OnceLock<ProgressSocket> = OnceLock::new();
+
+/// Connects to the progress socket of a front-end, a failure is only warned about and the run continues without it.
+///
+/// # Parameters
+/// - `socket_path`: Path of a Unix socket to connect to, if any.
+/// - `port`: Local TCP port to connect to, if any, used if no socket path is given.
+pub fn connect(socket_path: Option<&Path>, port: Option<u16>) {
+    let (stream, destination): (std::io::Result<Box<dyn Write + Send>>, String) = match (socket_path, port) {
+        (Some(socket_path), _) => (connect_unix(socket_path), socket_path.display().to_string()),
+        (None, Some(port)) => (
+            TcpStream::connect(("127.0.0.1", port)).map(|stream| Box::new(stream) as Box<dyn Write + Send>),
+            format!("127.0.0.1:{port}")
+        ),
+        (None, None) => return,
+    };
+
+    match stream {
+        Ok(stream) => {
+            log::info!("Progress events are sent to {destination}.");
+            let _ = PROGRESS_SOCKET.set(ProgressSocket { stream: Mutex::new(Some(stream)) });
+        },
+        Err(e) => log::warn!("Progress socket {destination} not available, continuing without it: {e}"),
+    }
+}
+
+#[cfg(unix)]
+fn connect_unix(socket_path: &Path) -> std::io::Result<Box<dyn Write + Send>> {
+    std::os::unix::net::UnixStream::connect(socket_path).map(|stream| Box::new(stream) as Box<dyn Write + Send>)
+}
+
+#[cfg(not(unix))]
+fn connect_unix(_socket_path: &Path) -> std::io::Result<Box<dyn Write + Send>> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "Unix sockets are not supported on this platform"))
+}
+
+/// Sends the event of a written chunk, e.g.
+/// `{"event":"chunk","chunk":3,"chunks":40,"bytes":16384,"total_bytes":163840,"timestamp_ms":1700000000000}`.
+///
+/// # Parameters
+/// - `chunk`: Number of chunks done.
+/// - `chunks`: Number of chunks in total.
+/// - `bytes`: Number of bytes done.
+/// - `total_bytes`: Number of bytes in total.
+/// - `timestamp_ms`: Time of the event in milliseconds.
+pub fn send_chunk(chunk: usize, chunks: usize, bytes: usize, total_bytes: usize, timestamp_ms: u128) {
+    send(&format!(
+        "{{\"event\":\"chunk\",\"chunk\":{chunk},\"chunks\":{chunks},\"bytes\":{bytes},\"total_bytes\":{total_bytes},\"timestamp_ms\":{timestamp_ms}}}"
+    ));
+}
+
+/// Sends the final event of the run, e.g. `{"event":"finished","ok":false,"exit_code":4,"error":"Checksum not match host=1 target=2"}`.
+///
+/// # Parameters
+/// - `exit_code`: The process exit code.
+/// - `error`: The error message of a failed run.
+pub fn send_finished(exit_code: i32, error: Option<&str>) {
+    let error = match error {
+        Some(error) => format!("\"{}\"", escape_json(error)),
+        None => String::from("null"),
+    };
+    send(&format!("{{\"event\":\"finished\",\"ok\":{},\"exit_code\":{exit_code},\"error\":{error}}}", exit_code == 0));
+}
+
+/// Writes a single NDJSON line, the socket is dropped after the first failed write.
+fn send(event: &str) {
+    let Some(socket) = PROGRESS_SOCKET.get() else {
+        return;
+    };
+    let Ok(mut stream) = socket.stream.lock() else {
+        return;
+    };
+
+    if let Some(writer) = stream.as_mut() {
+        if let Err(e) = writer.write_all(format!("{event}\n").as_bytes()).and_then(|_| writer.flush()) {
+            log::warn!("Progress socket closed, no more progress events are sent: {e}");
+            *stream = None;
+        }
+    }
+}
+
+/// Escapes a string for a JSON string literal.
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
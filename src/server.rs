@@ -0,0 +1,95 @@
+use std::process::Stdio;
+
+use regex::Regex;
+use tokio::io::{self, AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration};
+
+use crate::error::GdbError;
+
+/// Number of server output lines buffered while waiting for readiness, further lines are only logged.
+const SERVER_OUTPUT_CAPACITY: usize = 64;
+
+/// A GDB server started by the loader, e.g. OpenOCD, killed when dropped.
+pub struct LaunchedServer {
+    _child: Child,
+}
+
+/// Starts a GDB server and waits until it prints its readiness line.
+///
+/// The server output is read in the background for the whole session, so the server never
+/// blocks on a full pipe, and is logged as debug.
+///
+/// # Parameters
+/// - `command_line`: The server command split on whitespace, e.g. `openocd -f board/stm32u5.cfg`.
+/// - `ready_regex`: Pattern of the readiness line on stdout or stderr, not awaited if `None`.
+/// - `ready_timeout`: How long to wait for the readiness line.
+///
+/// # Returns
+/// An `io::Result` with the server and whether its readiness line was seen, or an error
+/// if the server cannot be started or exits while it is awaited.
+pub async fn launch(command_line: &str, ready_regex: Option<&Regex>, ready_timeout: Duration) -> io::Result<(LaunchedServer, bool)> {
+    let mut words = command_line.split_whitespace();
+    let program = words.next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Server command is empty"))?;
+
+    log::info!("Launching GDB server '{command_line}'...");
+    let mut child = Command::new(program)
+        .args(words)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| io::Error::new(e.kind(), format!("Failed to start GDB server '{program}': {e}")))?;
+
+    let (sender, mut receiver) = mpsc::channel(SERVER_OUTPUT_CAPACITY);
+    if let Some(stdout) = child.stdout.take() {
+        spawn_output_reader(stdout, sender.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        spawn_output_reader(stderr, sender);
+    }
+
+    let Some(ready_regex) = ready_regex else {
+        return Ok((LaunchedServer { _child: child }, false));
+    };
+
+    let ready = timeout(ready_timeout, async {
+        while let Some(line) = receiver.recv().await {
+            if ready_regex.is_match(&line) {
+                return true;
+            }
+        }
+        false
+    })
+    .await
+    .unwrap_or(false);
+
+    if let Some(status) = child.try_wait()? {
+        return Err(GdbError::Connection(format!("GDB server exited before it was ready ({status})")).into());
+    }
+
+    match ready {
+        true => log::info!("GDB server ready."),
+        false => log::warn!("GDB server readiness not seen within {} ms, connecting with retries.", ready_timeout.as_millis()),
+    }
+
+    Ok((LaunchedServer { _child: child }, ready))
+}
+
+/// Reads server output lines in the background, logging them and passing them on while awaited.
+fn spawn_output_reader<R>(reader: R, sender: mpsc::Sender<String>)
+where
+    R: AsyncRead + Unpin + Send + 'static
+{
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            log::debug!("Server: {line}");
+            // Nobody awaits the lines once the server is ready.
+            let _ = sender.try_send(line);
+        }
+    });
+}
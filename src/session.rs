@@ -0,0 +1,331 @@
+use std::fmt::Debug;
+use std::path::Path;
+
+use tokio::fs;
+use tokio::io;
+use tokio::io::AsyncWriteExt;
+
+use crate::error::ResultExt;
+use crate::gdb::Gdb;
+use crate::loader::{checksum_sum32, commit_staged_image, ensure_staging_fits, execute_plan, plan_upload, prepare_upload, read_binary_input, read_flash, read_modify_write, verify_flash_checksum, ChunkResult, FlashTarget, RamBuffer, UploadOptions, UploadPlan, UploadProgress, UploadStats};
+
+/// Upload parameters shared by all operations of a [`Session`].
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    /// RAM buffer on the target the chunks are written into.
+    pub ram_buffer: RamBuffer,
+    /// Flash description with the copy and checksum functions, address base and checksum width.
+    pub flash_target: FlashTarget,
+    /// Maximum size of a chunk in bytes.
+    pub chunk_size: usize,
+    /// Retry policy, RAM write method and the other upload options.
+    pub options: UploadOptions,
+    /// Steps of [`Session::upload`] around the chunk writes.
+    pub steps: UploadSteps,
+}
+
+/// Steps of [`Session::upload`] around the chunk writes, none by default.
+#[derive(Debug, Clone, Default)]
+pub struct UploadSteps {
+    /// Extend the data to whole sectors with the flash content around it, see [`read_modify_write`].
+    pub rmw: bool,
+    /// Erase the planned range with the probe erase command before the writes.
+    pub erase: bool,
+    /// Write the image to a staging region first and activate it with a commit function.
+    pub staging: Option<Staging>,
+}
+
+/// Staging region of a two-phase upload, see [`commit_staged_image`].
+#[derive(Debug, Clone)]
+pub struct Staging {
+    /// Flash offset the chunks are written to.
+    pub offset: usize,
+    /// Name of the firmware function activating the staged image.
+    pub commit_function_name: String,
+}
+
+/// Phase of a session, entered in order with [`Phase::enter`]:
+/// `Connected → Reset → BreakpointSet → AtBreakpoint → Uploaded → Verified`,
+/// an operation which only verifies goes from `AtBreakpoint` to `Verified`, `Quit` may follow any phase.
+/// Operations check the phase they need with [`Phase::require`] before touching the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    /// GDB is connected to the target, the ELF symbols are checked.
+    Connected,
+    /// The target was reset, halted only with `--halt-before-break`, and the ELF loaded if requested.
+    Reset,
+    /// The breakpoint the firmware initializes up to is set.
+    BreakpointSet,
+    /// The firmware stopped at the breakpoint and the core is halted, the loader functions may be called.
+    AtBreakpoint,
+    /// The data was written to flash.
+    Uploaded,
+    /// The flash content was verified.
+    Verified,
+    /// GDB is being closed.
+    Quit,
+}
+
+impl Phase {
+    /// Tells whether `next` may follow the phase.
+    fn allows(self, next: Phase) -> bool {
+        matches!((self, next),
+            (Phase::Connected, Phase::Reset)
+            | (Phase::Reset, Phase::BreakpointSet)
+            | (Phase::BreakpointSet, Phase::AtBreakpoint)
+            | (Phase::AtBreakpoint, Phase::Uploaded | Phase::Verified)
+            | (Phase::Uploaded, Phase::Verified)
+        ) || (next == Phase::Quit && self != Phase::Quit)
+    }
+
+    /// Moves to the next phase, logging its entry.
+    ///
+    /// # Parameters
+    /// - `next`: The phase to enter.
+    ///
+    /// # Returns
+    /// An `io::Result<()>`, an error if `next` cannot follow the current phase, e.g. an upload before
+    /// the breakpoint was hit.
+    pub fn enter(&mut self, next: Phase) -> io::Result<()> {
+        if !self.allows(next) {
+            return Err(io::Error::other(format!("Phase '{next}' cannot follow phase '{self}'")));
+        }
+
+        log::info!("Entering phase '{next}'.");
+        *self = next;
+        Ok(())
+    }
+
+    /// Checks that an operation may run: the session reached `phase` or a later one and is not quitting.
+    ///
+    /// # Parameters
+    /// - `phase`: The earliest phase the operation runs in, e.g. [`Phase::AtBreakpoint`] for calls of the loader functions.
+    ///
+    /// # Returns
+    /// An `io::Result<()>`, an error if the session is in an earlier phase or quitting.
+    pub fn require(self, phase: Phase) -> io::Result<()> {
+        if self < phase || self == Phase::Quit {
+            return Err(io::Error::other(format!("Operation needs phase '{phase}', session is in phase '{self}'")));
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Phase::Connected => write!(f, "connected"),
+            Phase::Reset => write!(f, "reset"),
+            Phase::BreakpointSet => write!(f, "breakpoint set"),
+            Phase::AtBreakpoint => write!(f, "at breakpoint"),
+            Phase::Uploaded => write!(f, "uploaded"),
+            Phase::Verified => write!(f, "verified"),
+            Phase::Quit => write!(f, "quit"),
+        }
+    }
+}
+
+/// A connected GDB together with the upload configuration and the phase the session is in,
+/// so repeated operations take only the file and the flash offset.
+pub struct Session {
+    gdb: Gdb,
+    config: SessionConfig,
+    phase: Phase,
+}
+
+impl Session {
+    /// Creates a session on an already connected GDB.
+    ///
+    /// # Parameters
+    /// - `gdb`: The GDB connection, owned by the session.
+    /// - `config`: The upload configuration used by all operations.
+    pub fn new(gdb: Gdb, config: SessionConfig) -> Self {
+        log::info!("Session started in phase '{}'.", Phase::Connected);
+        Self { gdb, config, phase: Phase::Connected }
+    }
+
+    /// Returns the current phase, e.g. to report where a failed session stopped.
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// Moves the session to the next phase, see [`Phase::enter`].
+    pub fn enter(&mut self, next: Phase) -> io::Result<()> {
+        self.phase.enter(next)
+    }
+
+    /// Returns the upload configuration.
+    pub fn config(&self) -> &SessionConfig {
+        &self.config
+    }
+
+    /// Returns the GDB connection, for commands outside the upload, e.g. breakpoints.
+    pub fn gdb_mut(&mut self) -> &mut Gdb {
+        &mut self.gdb
+    }
+
+    /// Returns the GDB connection, the upload configuration and the phase at once, e.g. to update
+    /// the flash geometry queried from the target.
+    pub fn split_mut(&mut self) -> (&mut Gdb, &mut SessionConfig, &mut Phase) {
+        (&mut self.gdb, &mut self.config, &mut self.phase)
+    }
+
+    /// Uploads a binary file to flash with the steps of the configuration, see [`prepare_upload`] and [`execute_plan`].
+    ///
+    /// # Parameters
+    /// - `binary_filepath`: The path to the binary file, `-` reads it from stdin.
+    /// - `flash_offset`: The offset in flash memory the file is written to.
+    /// - `on_plan`: Called with the plan before anything is written, e.g. to print it.
+    /// - `per_chunk_handler`: Called after every written chunk, e.g. to print the progress.
+    ///
+    /// # Returns
+    /// An `io::Result` with the upload statistics.
+    pub async fn upload<P, F>(
+        &mut self,
+        binary_filepath: P,
+        flash_offset: usize,
+        on_plan: impl FnOnce(&UploadPlan),
+        per_chunk_handler: Option<F>
+    ) -> io::Result<UploadStats>
+    where
+        P: AsRef<Path> + Debug,
+        F: Fn(&ChunkResult, &UploadProgress) + 'static
+    {
+        self.phase.require(Phase::AtBreakpoint)?;
+        let config = &self.config;
+        let flash_target = &config.flash_target;
+
+        // A two-phase upload writes the chunks to the staging region, the image reaches its offset on commit.
+        let write_offset = config.steps.staging.as_ref().map_or(flash_offset, |staging| staging.offset);
+        let (file_data, mut plan) = prepare_upload(
+            binary_filepath,
+            config.chunk_size,
+            write_offset,
+            flash_target,
+            &config.options
+        ).await?;
+        if let Some(staging) = &config.steps.staging {
+            ensure_staging_fits(flash_target, staging.offset, flash_offset, file_data.len())?;
+        }
+
+        // Extend the data with the flash content of the partial sectors and plan the whole sectors instead.
+        let rmw = match config.steps.rmw {
+            true => {
+                let rmw = read_modify_write(&mut self.gdb, flash_target, flash_offset, &file_data).await?;
+                plan = plan_upload(&rmw.data, config.chunk_size, rmw.flash_start_offset, flash_target, &config.options)?;
+                Some(rmw)
+            },
+            false => None,
+        };
+        let upload_data = rmw.as_ref().map_or(file_data.as_slice(), |rmw| rmw.data.as_slice());
+        on_plan(&plan);
+
+        if config.steps.erase {
+            let erase_address = flash_target.copy_address(plan.flash_start_offset)?;
+            log::info!("Erasing {} B of flash at {erase_address:#x}...", plan.total_size);
+            self.gdb.monitor_flash_erase(erase_address, plan.total_size).await
+                .with_context(|| format!("erasing flash at {erase_address:#x} failed"))?;
+        }
+
+        let stats = execute_plan(
+            &mut self.gdb,
+            &plan,
+            upload_data,
+            &config.ram_buffer,
+            flash_target,
+            &config.options,
+            per_chunk_handler
+        ).await?;
+
+        match (&rmw, &config.steps.staging) {
+            (Some(rmw), _) => rmw.verify(&mut self.gdb, flash_target).await?,
+            (None, Some(staging)) => commit_staged_image(
+                &mut self.gdb,
+                flash_target,
+                staging.offset,
+                flash_offset,
+                &file_data,
+                &staging.commit_function_name
+            ).await?,
+            (None, None) => {},
+        }
+
+        self.phase.enter(Phase::Uploaded)?;
+        // Every written chunk matched its checksum, an incomplete or unchecked upload stays just uploaded.
+        if !stats.incomplete && stats.unverified_chunks == 0 {
+            self.phase.enter(Phase::Verified)?;
+        }
+        Ok(stats)
+    }
+
+    /// Compares a binary file with the flash content using the flash checksum function, nothing is written.
+    ///
+    /// # Parameters
+    /// - `binary_filepath`: The path to the binary file, `-` reads it from stdin.
+    /// - `flash_offset`: The offset in flash memory the file was written to.
+    ///
+    /// # Returns
+    /// An `io::Result` with the target checksum, or [`crate::error::GdbError::ChecksumMismatch`] if the flash differs.
+    pub async fn verify<P>(&mut self, binary_filepath: P, flash_offset: usize) -> io::Result<u32>
+    where
+        P: AsRef<Path> + Debug
+    {
+        let file_data = read_binary_input(&binary_filepath).await
+            .with_context(|| format!("reading binary {binary_filepath:?} failed"))?;
+
+        self.verify_checksum(flash_offset, file_data.len(), checksum_sum32(&file_data)).await
+    }
+
+    /// Compares the checksum of a flash range with an expected one, e.g. of an image written before.
+    ///
+    /// # Parameters
+    /// - `flash_offset`: The offset in flash memory of the range.
+    /// - `length`: The length of the range in bytes.
+    /// - `expected_checksum`: The Sum32 of the data expected in the range.
+    ///
+    /// # Returns
+    /// An `io::Result` with the target checksum, or [`crate::error::GdbError::ChecksumMismatch`] if the flash differs.
+    pub async fn verify_checksum(&mut self, flash_offset: usize, length: usize, expected_checksum: u32) -> io::Result<u32> {
+        self.phase.require(Phase::AtBreakpoint)?;
+        let target_checksum = verify_flash_checksum(&mut self.gdb, &self.config.flash_target, flash_offset, length, expected_checksum).await?;
+
+        if self.phase != Phase::Verified {
+            self.phase.enter(Phase::Verified)?;
+        }
+        Ok(target_checksum)
+    }
+
+    /// Reads a flash region back into a file, in reads of the verify read chunk size, by default the chunk size.
+    ///
+    /// The flash must be memory mapped at the flash base.
+    ///
+    /// # Parameters
+    /// - `output_path`: The file the region is saved to, overwritten if it exists.
+    /// - `flash_offset`: The offset in flash memory of the region.
+    /// - `length`: The length of the region in bytes.
+    ///
+    /// # Returns
+    /// An `io::Result<()>` with an error if reading or saving fails.
+    pub async fn dump<P>(&mut self, output_path: P, flash_offset: usize, length: usize) -> io::Result<()>
+    where
+        P: AsRef<Path> + Debug
+    {
+        self.phase.require(Phase::AtBreakpoint)?;
+        let mut output_file = fs::File::create(&output_path).await
+            .with_context(|| format!("creating dump file {output_path:?} failed"))?;
+
+        // Written per chunk, so a large region is not held in memory at once.
+        let read_chunk_size = self.config.options.verify_read_chunk_size.unwrap_or(self.config.chunk_size);
+        let mut done_bytes = 0;
+        while done_bytes < length {
+            let read_bytes = (length - done_bytes).min(read_chunk_size);
+            let bytes = read_flash(&mut self.gdb, &self.config.flash_target, flash_offset + done_bytes, read_bytes, None).await?;
+            output_file.write_all(&bytes).await?;
+            done_bytes += read_bytes;
+        }
+
+        output_file.flush().await?;
+        log::info!("Dumped {length} B of flash at offset {flash_offset:#x} to {output_path:?}.");
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use tokio::io;
+use tokio::sync::{Mutex, MutexGuard};
+use tokio::time::Duration;
+
+use crate::gdb::Gdb;
+
+/// A [`Gdb`] shared between tasks, e.g. the upload flow and a background status poller.
+///
+/// GDB is a single serial process: a command and its response must never interleave with another
+/// command, which is why every [`Gdb`] method takes `&mut self`. The connection is guarded by an
+/// async mutex, so commands stay strictly serialized. A sequence which must not be interrupted,
+/// e.g. writing the RAM buffer and calling the copy function, holds one guard for all of its commands.
+#[derive(Clone)]
+#[allow(unused)]
+pub struct SharedGdb {
+    inner: Arc<Mutex<Gdb>>,
+}
+
+#[allow(unused)]
+impl SharedGdb {
+    /// Wraps a connected GDB for sharing, the clones refer to the same connection.
+    ///
+    /// # Parameters
+    /// - `gdb`: The GDB connection.
+    pub fn new(gdb: Gdb) -> Self {
+        Self { inner: Arc::new(Mutex::new(gdb)) }
+    }
+
+    /// Waits until no other task issues commands and takes the connection.
+    ///
+    /// # Returns
+    /// A guard giving exclusive access until it is dropped.
+    pub async fn lock(&self) -> MutexGuard<'_, Gdb> {
+        self.inner.lock().await
+    }
+
+    /// Takes the connection only if no other task holds it, so an occasional query does not delay the main flow.
+    ///
+    /// # Returns
+    /// A guard giving exclusive access until it is dropped, `None` if the connection is busy.
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, Gdb>> {
+        self.inner.try_lock().ok()
+    }
+
+    /// Sends a keep-alive packet if the connection is idle, see [`Gdb::keep_alive`], skipped while busy.
+    ///
+    /// # Parameters
+    /// - `idle_interval`: The idle time after which the packet is sent.
+    ///
+    /// # Returns
+    /// An `io::Result<bool>`, `false` if another task held the connection.
+    pub async fn keep_alive_if_idle(&self, idle_interval: Duration) -> io::Result<bool> {
+        match self.try_lock() {
+            Some(mut gdb) => gdb.keep_alive(idle_interval).await.map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Unwraps the connection once all other clones are dropped, e.g. to quit GDB.
+    ///
+    /// # Returns
+    /// The GDB connection, or `Err(self)` if another clone is still alive.
+    pub fn into_inner(self) -> Result<Gdb, Self> {
+        Arc::try_unwrap(self.inner)
+            .map(Mutex::into_inner)
+            .map_err(|inner| Self { inner })
+    }
+}
@@ -0,0 +1,117 @@
+use std::ops::Range;
+use std::sync::Mutex;
+
+use crate::color;
+use crate::loader::UploadStats;
+
+/// What is known about the upload of the run, filled in as it proceeds.
+#[derive(Debug, Default)]
+struct UploadRecord {
+    /// The input file.
+    file: String,
+    /// Size of the data to write, including padding.
+    size: usize,
+    /// Flash offsets the data is written to.
+    flash_range: Range<usize>,
+    /// Statistics of a finished upload.
+    stats: Option<UploadStats>,
+}
+
+static UPLOAD_RECORD: Mutex<Option<UploadRecord>> = Mutex::new(None);
+
+/// Records the planned upload, so a failed run still shows what was being written.
+///
+/// # Parameters
+/// - `file`: The input file.
+/// - `size`: The size of the data to write.
+/// - `flash_start_offset`: The flash offset the data is written to.
+pub fn record_plan(file: &str, size: usize, flash_start_offset: usize) {
+    if let Ok(mut record) = UPLOAD_RECORD.lock() {
+        *record = Some(UploadRecord {
+            file: file.to_string(),
+            size,
+            flash_range: flash_start_offset..flash_start_offset + size,
+            stats: None,
+        });
+    }
+}
+
+/// Records the statistics of a finished upload.
+///
+/// # Parameters
+/// - `stats`: The upload statistics.
+pub fn record_stats(stats: &UploadStats) {
+    if let Ok(mut record) = UPLOAD_RECORD.lock() {
+        if let Some(record) = record.as_mut() {
+            record.stats = Some(stats.clone());
+        }
+    }
+}
+
+/// Renders the summary of the run as an aligned two-column table, e.g.
+/// ```text
+/// File         fw.bin
+/// Size         163840 B
+/// Flash range  0x100000..0x128000
+/// Chunks       10 of 10
+/// Retries      1
+/// Elapsed      2.481 s
+/// Throughput   64.5 KiB/s
+/// Verify       per-chunk checksums
+/// Result       OK
+/// ```
+///
+/// # Parameters
+/// - `error`: The error the run failed with, if any.
+///
+/// # Returns
+/// The table, with only the result row if no upload was recorded.
+pub fn render(error: Option<&str>) -> String {
+    let mut rows = Vec::new();
+
+    if let Some(record) = UPLOAD_RECORD.lock().ok().as_deref().and_then(Option::as_ref) {
+        rows.push(("File", record.file.clone()));
+        rows.push(("Size", format!("{} B", record.size)));
+        rows.push(("Flash range", format!("{:#x}..{:#x}", record.flash_range.start, record.flash_range.end)));
+
+        match &record.stats {
+            Some(stats) => {
+                let elapsed_secs = stats.elapsed.as_secs_f64();
+                rows.push(("Chunks", format!("{} of {}", stats.chunks_count, stats.planned_chunks_count)));
+                rows.push(("Retries", match stats.retries {
+                    0 => stats.retries.to_string(),
+                    retries => color::yellow(&retries.to_string()),
+                }));
+                rows.push(("Elapsed", format!("{elapsed_secs:.3} s")));
+                if elapsed_secs > 0.0 {
+                    rows.push(("Throughput", format!("{:.1} KiB/s", stats.bytes_transferred as f64 / 1024.0 / elapsed_secs)));
+                }
+                let mut verify = vec![String::from(match stats.unverified_chunks {
+                    0 => "per-chunk checksums",
+                    _ => "no per-chunk checksums",
+                })];
+                if let Some(seed) = stats.verify_seed {
+                    if !stats.verified_chunks.is_empty() {
+                        verify.push(format!("{} chunks sampled (seed {seed})", stats.verified_chunks.len()));
+                    }
+                    if stats.spot_checked_words > 0 {
+                        verify.push(format!("{} words spot checked (seed {seed})", stats.spot_checked_words));
+                    }
+                }
+                let verify = verify.join(", ");
+                rows.push(("Verify", verify));
+            },
+            None => rows.push(("Chunks", String::from("-"))),
+        }
+    }
+
+    rows.push(("Result", match error {
+        Some(error) => color::red(&format!("FAILED ({error})")),
+        None => color::green("OK"),
+    }));
+
+    let key_width = rows.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+    rows.iter()
+        .map(|(key, value)| format!("{key:<key_width$}  {value}\n"))
+        .collect()
+}
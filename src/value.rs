@@ -0,0 +1,245 @@
+/// A value printed by GDB, e.g. with `print`.
+///
+/// Parsed tolerantly: anything not understood, e.g. pointers to functions, enums
+/// or arrays with `<repeats N times>`, is kept as [`GdbValue::Raw`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum GdbValue {
+    /// An integer, including characters printed as `65 'A'` and addresses printed as `0x20000000 <buf>`.
+    Int(i128),
+    /// A floating point number.
+    Float(f64),
+    /// A string literal with escapes resolved.
+    Str(String),
+    /// An array, `{1, 2, 3}`.
+    Array(Vec<GdbValue>),
+    /// A structure or union, `{a = 1, b = {2, 3}}`.
+    Aggregate(Vec<(String, GdbValue)>),
+    /// The text of a value which could not be parsed.
+    Raw(String),
+}
+
+impl GdbValue {
+    /// Parses the text of a value, the part after `$1 = ` of a `print` response.
+    ///
+    /// # Parameters
+    /// - `text`: The value text, e.g. `{a = 1, b = {2, 3}}`.
+    ///
+    /// # Returns
+    /// The parsed value, [`GdbValue::Raw`] if it is not understood.
+    pub fn parse(text: &str) -> Self {
+        let text = text.trim();
+
+        if let Some(inner) = text.strip_prefix('{').and_then(|rest| rest.strip_suffix('}')) {
+            // A repeated element stands for several, the elements cannot be listed one by one.
+            if inner.contains(" <repeats ") {
+                return GdbValue::Raw(text.to_string());
+            }
+            return parse_braced(inner).unwrap_or_else(|| GdbValue::Raw(text.to_string()));
+        }
+
+        if let Some(string) = parse_string_literal(text) {
+            return GdbValue::Str(string);
+        }
+
+        if let Some(int) = parse_int(text) {
+            return GdbValue::Int(int);
+        }
+
+        if let Ok(float) = text.parse::<f64>() {
+            return GdbValue::Float(float);
+        }
+
+        GdbValue::Raw(text.to_string())
+    }
+}
+
+/// The value returned by a target function call, keeping the representation GDB printed it in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TargetFunctionResult {
+    /// The function returns nothing.
+    Void,
+    /// A decimal integer, including characters printed as `118 'v'`.
+    Int(i64),
+    /// A hexadecimal integer, e.g. a pointer printed as `0x20000000 <buf>`.
+    Hex(u64),
+    /// The text of a value which is not an integer, or a line without a value.
+    Raw(String),
+}
+
+impl TargetFunctionResult {
+    /// Parses the text of a returned value, the part after `$1 = ` of a `call` response.
+    ///
+    /// # Parameters
+    /// - `text`: The value text, e.g. `118 'v'` or `0x1234`.
+    ///
+    /// # Returns
+    /// The parsed result, [`TargetFunctionResult::Raw`] if it is not an integer.
+    pub fn parse(text: &str) -> Self {
+        let text = text.trim();
+        let number = text.split_once(' ').map_or(text, |(number, _)| number);
+
+        match parse_int(text) {
+            Some(value) if number.starts_with("0x") => u64::try_from(value).map_or_else(|_| Self::Raw(text.to_string()), Self::Hex),
+            Some(value) => i64::try_from(value).map_or_else(|_| Self::Raw(text.to_string()), Self::Int),
+            None => Self::Raw(text.to_string()),
+        }
+    }
+
+    /// Returns the result as `u32`, negative values of 32-bit signed types are taken as their bit pattern.
+    ///
+    /// # Returns
+    /// An `Option` containing the value, `None` if the result is not an integer or does not fit 32 bits.
+    pub fn as_u32(&self) -> Option<u32> {
+        match *self {
+            Self::Int(value) => u32::try_from(value).ok()
+                .or_else(|| i32::try_from(value).ok().map(|value| value as u32)),
+            Self::Hex(value) => u32::try_from(value).ok(),
+            Self::Void | Self::Raw(_) => None,
+        }
+    }
+}
+
+/// Parses the content of braces as an array or an aggregate, `None` if the elements are mixed.
+fn parse_braced(inner: &str) -> Option<GdbValue> {
+    let elements = split_top_level(inner)?;
+    if elements.is_empty() {
+        return Some(GdbValue::Array(vec![]));
+    }
+
+    let fields = elements.iter()
+        .map(|element| split_field(element))
+        .collect::<Vec<_>>();
+
+    if fields.iter().all(Option::is_some) {
+        Some(GdbValue::Aggregate(fields.into_iter()
+            .flatten()
+            .map(|(name, value)| (name.to_string(), GdbValue::parse(value)))
+            .collect()))
+    } else if fields.iter().all(Option::is_none) {
+        Some(GdbValue::Array(elements.iter().map(|element| GdbValue::parse(element)).collect()))
+    } else {
+        None
+    }
+}
+
+/// Splits text by commas which are neither nested in braces nor quoted, `None` if unbalanced.
+fn split_top_level(text: &str) -> Option<Vec<&str>> {
+    let mut elements = Vec::new();
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut start = 0;
+
+    for (idx, c) in text.char_indices() {
+        if let Some(quote_char) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == quote_char {
+                quote = None;
+            }
+            continue;
+        }
+
+        match c {
+            '"' | '\'' => quote = Some(c),
+            '{' => depth += 1,
+            '}' => depth = depth.checked_sub(1)?,
+            ',' if depth == 0 => {
+                elements.push(text[start..idx].trim());
+                start = idx + 1;
+            },
+            _ => {},
+        }
+    }
+
+    if depth != 0 || quote.is_some() {
+        return None;
+    }
+
+    let last = text[start..].trim();
+    if !last.is_empty() || !elements.is_empty() {
+        elements.push(last);
+    }
+    Some(elements)
+}
+
+/// Splits an aggregate element `name = value` into its parts.
+fn split_field(element: &str) -> Option<(&str, &str)> {
+    let (name, value) = element.split_once(" = ")?;
+    let mut chars = name.chars();
+    let is_identifier = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    is_identifier.then_some((name, value))
+}
+
+/// Parses a single string literal, resolving the common escapes.
+fn parse_string_literal(text: &str) -> Option<String> {
+    let inner = text.strip_prefix('"')?.strip_suffix('"')?;
+    let mut string = String::new();
+    let mut chars = inner.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                'n' => string.push('\n'),
+                't' => string.push('\t'),
+                'r' => string.push('\r'),
+                '0' => string.push('\0'),
+                escaped => string.push(escaped),
+            },
+            // A quote inside means several literals, e.g. `"ab", '\000' <repeats 14 times>`.
+            '"' => return None,
+            c => string.push(c),
+        }
+    }
+
+    Some(string)
+}
+
+/// Parses a decimal or hexadecimal integer, optionally followed by a character `'A'` or a symbol `<buf>`.
+fn parse_int(text: &str) -> Option<i128> {
+    let (number, rest) = text.split_once(' ').unwrap_or((text, ""));
+    let rest = rest.trim();
+    if !(rest.is_empty() || rest.starts_with('\'') || rest.starts_with('<')) {
+        return None;
+    }
+
+    let (negative, digits) = match number.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, number),
+    };
+
+    let value = match digits.strip_prefix("0x") {
+        Some(hex_digits) => i128::from_str_radix(hex_digits, 16).ok()?,
+        None => digits.parse::<i128>().ok()?,
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn call_result_negative_int_is_bit_pattern() {
+        let result = TargetFunctionResult::parse("-1");
+        assert_eq!(result, TargetFunctionResult::Int(-1));
+        assert_eq!(result.as_u32(), Some(0xFFFF_FFFF));
+        assert_eq!(TargetFunctionResult::parse("-2147483648").as_u32(), Some(0x8000_0000));
+    }
+
+    #[test]
+    fn value_with_repeats_is_raw() {
+        assert_eq!(
+            GdbValue::parse("{0 <repeats 16 times>}"),
+            GdbValue::Raw(String::from("{0 <repeats 16 times>}"))
+        );
+        assert_eq!(
+            GdbValue::parse("{1, 2, 0 <repeats 14 times>}"),
+            GdbValue::Raw(String::from("{1, 2, 0 <repeats 14 times>}"))
+        );
+    }
+}